@@ -0,0 +1,225 @@
+use std::fmt;
+use std::str::FromStr;
+
+use glium::{Display, DrawParameters, Program, Surface};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::index::PrimitiveType;
+use glium::texture::{DepthStencilTexture2d, DepthStencilTexture2dMultisample, MipmapsOption, Texture2d, Texture2dMultisample, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, Uniforms};
+
+use crate::depth_mode::DepthMode;
+use crate::vertex::VertexTex;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AntiAliasing {
+    #[default]
+    Off,
+    Msaa2x,
+    Msaa4x,
+    Msaa8x,
+    Fxaa,
+}
+
+impl AntiAliasing {
+    pub const ALL: [AntiAliasing; 5] = [
+        AntiAliasing::Off,
+        AntiAliasing::Msaa2x,
+        AntiAliasing::Msaa4x,
+        AntiAliasing::Msaa8x,
+        AntiAliasing::Fxaa,
+    ];
+
+    // 0 means the target is not multisampled
+    pub fn samples(&self) -> u16 {
+        match self {
+            AntiAliasing::Msaa2x => 2,
+            AntiAliasing::Msaa4x => 4,
+            AntiAliasing::Msaa8x => 8,
+            AntiAliasing::Off | AntiAliasing::Fxaa => 0,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AntiAliasing::Off => "Off",
+            AntiAliasing::Msaa2x => "MSAA 2x",
+            AntiAliasing::Msaa4x => "MSAA 4x",
+            AntiAliasing::Msaa8x => "MSAA 8x",
+            AntiAliasing::Fxaa => "FXAA",
+        }
+    }
+}
+
+impl fmt::Display for AntiAliasing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for AntiAliasing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" | "none" => Ok(AntiAliasing::Off),
+            "msaa2" | "msaa2x" => Ok(AntiAliasing::Msaa2x),
+            "msaa4" | "msaa4x" | "msaa" => Ok(AntiAliasing::Msaa4x),
+            "msaa8" | "msaa8x" => Ok(AntiAliasing::Msaa8x),
+            "fxaa" => Ok(AntiAliasing::Fxaa),
+            other => Err(format!("Unknown anti-aliasing mode '{}'", other)),
+        }
+    }
+}
+
+/// Offscreen color and depth target the scene is rendered into before being presented.
+/// When MSAA is selected the scene is drawn into multisampled attachments and resolved
+/// into `color`, which is what gets presented or fed to the FXAA pass.
+pub struct SceneTarget {
+    anti_aliasing: AntiAliasing,
+    depth_mode: DepthMode,
+    dimensions: (u32, u32),
+    color: Texture2d,
+    depth: DepthStencilTexture2d,
+    msaa: Option<(Texture2dMultisample, DepthStencilTexture2dMultisample)>,
+}
+
+impl SceneTarget {
+    pub fn new(display: &Display, anti_aliasing: AntiAliasing, depth_mode: DepthMode, width: u32, height: u32) -> Self {
+        let depth_format = depth_mode.depth_stencil_format();
+        let color = Texture2d::empty_with_format(display, UncompressedFloatFormat::U8U8U8U8, MipmapsOption::NoMipmap, width, height).unwrap();
+        let depth = DepthStencilTexture2d::empty_with_format(display, depth_format, MipmapsOption::NoMipmap, width, height).unwrap();
+        let msaa = match anti_aliasing.samples() {
+            0 => None,
+            samples => Some((
+                Texture2dMultisample::empty_with_format(display, UncompressedFloatFormat::U8U8U8U8, MipmapsOption::NoMipmap, width, height, samples as u32).unwrap(),
+                DepthStencilTexture2dMultisample::empty_with_format(display, depth_format, MipmapsOption::NoMipmap, width, height, samples as u32).unwrap(),
+            )),
+        };
+        Self {
+            anti_aliasing,
+            depth_mode,
+            dimensions: (width, height),
+            color,
+            depth,
+            msaa,
+        }
+    }
+
+    pub fn anti_aliasing(&self) -> AntiAliasing {
+        self.anti_aliasing
+    }
+
+    pub fn depth_mode(&self) -> DepthMode {
+        self.depth_mode
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    pub fn is_outdated(&self, anti_aliasing: AntiAliasing, depth_mode: DepthMode, dimensions: (u32, u32)) -> bool {
+        self.anti_aliasing != anti_aliasing || self.depth_mode != depth_mode || self.dimensions != dimensions
+    }
+
+    pub fn framebuffer<'a>(&'a self, display: &Display) -> SimpleFrameBuffer<'a> {
+        match &self.msaa {
+            Some((color, depth)) => SimpleFrameBuffer::with_depth_stencil_buffer(display, color, depth).unwrap(),
+            None => SimpleFrameBuffer::with_depth_stencil_buffer(display, &self.color, &self.depth).unwrap(),
+        }
+    }
+
+    /// Resolves the multisampled attachments into the color texture, no-op without MSAA.
+    pub fn resolve(&self, display: &Display) {
+        if let Some((msaa_color, _)) = &self.msaa {
+            let source = SimpleFrameBuffer::new(display, msaa_color).unwrap();
+            let target = SimpleFrameBuffer::new(display, &self.color).unwrap();
+            source.blit_whole_color_to(&target, &self.blit_target(), MagnifySamplerFilter::Nearest);
+        }
+    }
+
+    pub fn color(&self) -> &Texture2d {
+        &self.color
+    }
+
+    /// Depth and stencil of the scene framebuffer, `None` when it is multisampled.
+    pub fn depth(&self) -> Option<&DepthStencilTexture2d> {
+        match self.msaa {
+            Some(_) => None,
+            None => Some(&self.depth),
+        }
+    }
+
+    pub fn present<S: Surface>(&self, display: &Display, target: &mut S, fxaa: &Fxaa) {
+        self.resolve(display);
+        match self.anti_aliasing {
+            AntiAliasing::Fxaa => fxaa.apply(target, &self.color),
+            _ => self.color.as_surface().blit_whole_color_to(target, &self.blit_target(), MagnifySamplerFilter::Nearest),
+        }
+    }
+
+    fn blit_target(&self) -> glium::BlitTarget {
+        glium::BlitTarget {
+            left: 0,
+            bottom: 0,
+            width: self.dimensions.0 as i32,
+            height: self.dimensions.1 as i32,
+        }
+    }
+}
+
+/// Fullscreen quad for post-processing passes, drawn with `screen_quad.vs.glsl`.
+pub(crate) struct ScreenQuad {
+    vertexes: glium::VertexBuffer<VertexTex>,
+    indexes: glium::IndexBuffer<u16>,
+}
+
+impl ScreenQuad {
+    pub(crate) fn new(display: &Display) -> Self {
+        let quad = [
+            VertexTex::new(-1.0, -1.0, 0.0, [0.0, 0.0]),
+            VertexTex::new(1.0, -1.0, 0.0, [1.0, 0.0]),
+            VertexTex::new(-1.0, 1.0, 0.0, [0.0, 1.0]),
+            VertexTex::new(1.0, 1.0, 0.0, [1.0, 1.0]),
+        ];
+        Self {
+            vertexes: glium::VertexBuffer::new(display, &quad).unwrap(),
+            indexes: glium::IndexBuffer::new(display, PrimitiveType::TrianglesList, &[0, 1, 3, 3, 2, 0u16]).unwrap(),
+        }
+    }
+
+    pub(crate) fn draw<S: Surface, U: Uniforms>(&self, target: &mut S, program: &Program, uniforms: &U, params: &DrawParameters) {
+        target.draw(&self.vertexes, &self.indexes, program, uniforms, params).unwrap();
+    }
+}
+
+pub struct Fxaa {
+    program: Program,
+    quad: ScreenQuad,
+}
+
+impl Fxaa {
+    pub fn new(display: &Display) -> Self {
+        let program = Program::from_source(
+            display,
+            include_str!("../../resources/shaders/screen_quad.vs.glsl"),
+            include_str!("../../resources/shaders/fxaa.fs.glsl"),
+            None,
+        ).unwrap();
+        Self {
+            program,
+            quad: ScreenQuad::new(display),
+        }
+    }
+
+    pub fn apply<S: Surface>(&self, target: &mut S, source: &Texture2d) {
+        let sampler = Sampler::new(source)
+            .magnify_filter(MagnifySamplerFilter::Linear)
+            .minify_filter(MinifySamplerFilter::Linear);
+        let inverse_screen_size = [1.0 / source.width() as f32, 1.0 / source.height() as f32];
+        let uniforms = glium::uniform! {
+            screenTexture: sampler,
+            inverseScreenSize: inverse_screen_size,
+        };
+        self.quad.draw(target, &self.program, &uniforms, &Default::default());
+    }
+}
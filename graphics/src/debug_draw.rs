@@ -0,0 +1,272 @@
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+use glium::{Display, Program, Surface};
+use glium::index::{NoIndices, PrimitiveType};
+use glium::uniforms::UniformValue;
+use math::{Aabb, glm, RawMat4};
+
+use crate::Colors;
+use crate::depth_mode::DepthMode;
+use crate::render_state::{CullMode, DepthFunction, DepthState, RenderState, RenderStateCache};
+use crate::uniform::UniformStorage;
+
+const CIRCLE_SEGMENTS: usize = 32;
+
+#[derive(Copy, Clone, Debug)]
+struct DebugVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+glium::implement_vertex!(DebugVertex, position, color);
+
+struct DebugLine {
+    from: glm::Vec3,
+    to: glm::Vec3,
+    color: [f32; 4],
+    // `None` lines only live until the next `draw`
+    expires: Option<Instant>,
+}
+
+struct DebugLabel {
+    position: glm::Vec3,
+    text: String,
+    color: [f32; 4],
+    expires: Option<Instant>,
+}
+
+/// Label projected on the screen, in pixels from the top left corner.
+#[derive(Debug, Clone)]
+pub struct ScreenLabel {
+    pub x: f32,
+    pub y: f32,
+    pub text: String,
+    pub color: [f32; 4],
+}
+
+/// Immediate mode world space lines and labels. Every shape is flattened into lines uploaded
+/// to one dynamic vertex buffer per frame; a shape without lifetime is drawn once.
+///
+/// Labels are not rasterized here, `screen_labels` gives their position for a UI to paint them.
+pub struct DebugDraw {
+    lines: Vec<DebugLine>,
+    labels: Vec<DebugLabel>,
+    // lines hidden by the scene are not drawn
+    pub depth_test: bool,
+    program: Program,
+    vertexes: Vec<DebugVertex>,
+    buffer: glium::VertexBuffer<DebugVertex>,
+}
+
+impl DebugDraw {
+    pub fn new(display: &Display) -> Self {
+        let program = Program::from_source(
+            display,
+            include_str!("../../resources/shaders/debug_lines.vs.glsl"),
+            include_str!("../../resources/shaders/debug_lines.fs.glsl"),
+            None,
+        ).unwrap();
+        Self {
+            lines: Vec::new(),
+            labels: Vec::new(),
+            depth_test: true,
+            program,
+            vertexes: Vec::new(),
+            buffer: glium::VertexBuffer::empty_dynamic(display, 1024).unwrap(),
+        }
+    }
+
+    pub fn line(&mut self, from: glm::Vec3, to: glm::Vec3, color: Colors, lifetime: Option<Duration>) {
+        self.lines.push(DebugLine {
+            from,
+            to,
+            color: color.into(),
+            expires: lifetime.map(|lifetime| Instant::now() + lifetime),
+        });
+    }
+
+    pub fn arrow(&mut self, from: glm::Vec3, to: glm::Vec3, color: Colors, lifetime: Option<Duration>) {
+        self.line(from, to, color, lifetime);
+        let direction = to - from;
+        let length = direction.norm();
+        if length <= f32::EPSILON {
+            return;
+        }
+        let (side, up) = orthonormal_basis(&(direction / length));
+        let head = length * 0.15;
+        let base = to - direction / length * head;
+        for offset in [side, -side, up, -up].iter() {
+            self.line(to, base + offset * head * 0.5, color, lifetime);
+        }
+    }
+
+    /// Edges of `aabb` moved by `transform`.
+    pub fn wire_box(&mut self, aabb: &Aabb, transform: &glm::Mat4, color: Colors, lifetime: Option<Duration>) {
+        let corners = (0..8)
+            .map(|i| {
+                let corner = glm::vec3(
+                    if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                    if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                    if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+                );
+                transform_point(transform, &corner)
+            })
+            .collect::<Vec<_>>();
+        self.box_edges(&corners, color, lifetime);
+    }
+
+    pub fn circle(&mut self, center: glm::Vec3, normal: glm::Vec3, radius: f32, color: Colors, lifetime: Option<Duration>) {
+        let (side, up) = orthonormal_basis(&glm::normalize(&normal));
+        let point = |i: usize| {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * 2.0 * PI;
+            center + (side * angle.cos() + up * angle.sin()) * radius
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color, lifetime);
+        }
+    }
+
+    /// Three great circles, one per axis.
+    pub fn wire_sphere(&mut self, center: glm::Vec3, radius: f32, color: Colors, lifetime: Option<Duration>) {
+        self.circle(center, glm::vec3(1.0, 0.0, 0.0), radius, color, lifetime);
+        self.circle(center, glm::vec3(0.0, 1.0, 0.0), radius, color, lifetime);
+        self.circle(center, glm::vec3(0.0, 0.0, 1.0), radius, color, lifetime);
+    }
+
+    /// Cone opening around `direction` with a half angle in radians, like a spot light.
+    pub fn cone(&mut self, apex: glm::Vec3, direction: glm::Vec3, half_angle: f32, length: f32, color: Colors, lifetime: Option<Duration>) {
+        let direction = glm::normalize(&direction);
+        let (side, up) = orthonormal_basis(&direction);
+        let center = apex + direction * length;
+        let radius = length * half_angle.tan();
+        self.circle(center, direction, radius, color, lifetime);
+        for offset in [side, -side, up, -up].iter() {
+            self.line(apex, center + offset * radius, color, lifetime);
+        }
+    }
+
+    /// Edges of the volume seen through `view_projection` under `depth_mode`. The far face of an
+    /// infinite reversed projection is drawn `FAR_DEPTH` deep, a thousand times the near distance.
+    pub fn frustum(&mut self, view_projection: &glm::Mat4, depth_mode: DepthMode, color: Colors, lifetime: Option<Duration>) {
+        const FAR_DEPTH: f32 = 1e-3;
+        let inverse = match view_projection.try_inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let (near, far) = depth_mode.ndc_depth_range();
+        let far = far.max(FAR_DEPTH);
+        let corners = (0..8)
+            .map(|i| {
+                let ndc = glm::vec4(
+                    if i & 1 == 0 { -1.0 } else { 1.0 },
+                    if i & 2 == 0 { -1.0 } else { 1.0 },
+                    if i & 4 == 0 { near } else { far },
+                    1.0,
+                );
+                let world = inverse * ndc;
+                world.xyz() / world.w
+            })
+            .collect::<Vec<_>>();
+        self.box_edges(&corners, color, lifetime);
+    }
+
+    /// X, Y and Z axes of `transform` in red, green and blue.
+    pub fn axes(&mut self, transform: &glm::Mat4, size: f32, lifetime: Option<Duration>) {
+        let origin = transform_point(transform, &glm::vec3(0.0, 0.0, 0.0));
+        let axes = [
+            (glm::vec3(size, 0.0, 0.0), Colors::RED),
+            (glm::vec3(0.0, size, 0.0), Colors::GREEN),
+            (glm::vec3(0.0, 0.0, size), Colors::BLUE),
+        ];
+        for (axis, color) in axes.iter() {
+            self.arrow(origin, transform_point(transform, axis), *color, lifetime);
+        }
+    }
+
+    pub fn label(&mut self, position: glm::Vec3, text: &str, color: Colors, lifetime: Option<Duration>) {
+        self.labels.push(DebugLabel {
+            position,
+            text: text.to_string(),
+            color: color.into(),
+            expires: lifetime.map(|lifetime| Instant::now() + lifetime),
+        });
+    }
+
+    /// Labels in front of the camera, projected on a viewport of `dimensions` pixels.
+    pub fn screen_labels(&self, view_projection: &glm::Mat4, dimensions: (u32, u32)) -> Vec<ScreenLabel> {
+        self.labels.iter()
+            .filter_map(|label| {
+                let clip = view_projection * glm::vec4(label.position.x, label.position.y, label.position.z, 1.0);
+                if clip.w <= 0.0 {
+                    return None;
+                }
+                let ndc = clip.xyz() / clip.w;
+                Some(ScreenLabel {
+                    x: (ndc.x + 1.0) * 0.5 * dimensions.0 as f32,
+                    y: (1.0 - ndc.y) * 0.5 * dimensions.1 as f32,
+                    text: label.text.clone(),
+                    color: label.color,
+                })
+            })
+            .collect()
+    }
+
+    /// Draws the lines of the frame then forgets the expired shapes.
+    pub fn draw<S: Surface>(&mut self, display: &Display, target: &mut S, vp: &RawMat4, render_states: &mut RenderStateCache) {
+        self.vertexes.clear();
+        for line in self.lines.iter() {
+            self.vertexes.push(DebugVertex { position: line.from.into(), color: line.color });
+            self.vertexes.push(DebugVertex { position: line.to.into(), color: line.color });
+        }
+        if !self.vertexes.is_empty() {
+            if self.vertexes.len() > self.buffer.len() {
+                self.buffer = glium::VertexBuffer::empty_dynamic(display, self.vertexes.len().next_power_of_two()).unwrap();
+            }
+            let slice = self.buffer.slice(0..self.vertexes.len()).unwrap();
+            slice.write(&self.vertexes);
+            let mut uniforms = UniformStorage::default();
+            uniforms.add("vp", UniformValue::Mat4(*vp));
+            let state = RenderState {
+                cull: CullMode::None,
+                depth: DepthState {
+                    test: if self.depth_test { DepthFunction::LessOrEqual } else { DepthFunction::Always },
+                    write: false,
+                },
+                ..RenderState::default()
+            };
+            target.draw(slice, NoIndices(PrimitiveType::LinesList), &self.program, &uniforms, render_states.get(&state)).unwrap();
+        }
+        self.remove_expired();
+    }
+
+    fn remove_expired(&mut self) {
+        let now = Instant::now();
+        self.lines.retain(|line| line.expires.is_some_and(|expires| expires > now));
+        self.labels.retain(|label| label.expires.is_some_and(|expires| expires > now));
+    }
+
+    // corners indexed by their bits: x for 1, y for 2 and z for 4
+    fn box_edges(&mut self, corners: &[glm::Vec3], color: Colors, lifetime: Option<Duration>) {
+        let edges = [
+            (0, 1), (1, 3), (3, 2), (2, 0),
+            (4, 5), (5, 7), (7, 6), (6, 4),
+            (0, 4), (1, 5), (2, 6), (3, 7),
+        ];
+        for (from, to) in edges.iter() {
+            self.line(corners[*from], corners[*to], color, lifetime);
+        }
+    }
+}
+
+fn transform_point(transform: &glm::Mat4, point: &glm::Vec3) -> glm::Vec3 {
+    (transform * glm::vec4(point.x, point.y, point.z, 1.0)).xyz()
+}
+
+// two unit vectors perpendicular to `direction` and to each other
+fn orthonormal_basis(direction: &glm::Vec3) -> (glm::Vec3, glm::Vec3) {
+    let reference = if direction.y.abs() < 0.99 { glm::vec3(0.0, 1.0, 0.0) } else { glm::vec3(1.0, 0.0, 0.0) };
+    let side = glm::normalize(&glm::cross(direction, &reference));
+    let up = glm::cross(&side, direction);
+    (side, up)
+}
@@ -0,0 +1,222 @@
+use glium::{Display, DrawParameters, Program, Surface};
+use glium::index::{IndicesSource, NoIndices, PrimitiveType};
+use glium::program::SourceCode;
+use glium::uniforms::UniformValue;
+use glium::vertex::VerticesSource;
+
+use crate::render_state::{CullMode, DepthFunction, DepthState, FillMode, RenderState, RenderStateCache};
+use crate::renderer::DrawItem;
+use crate::shader_variant;
+use crate::uniform::UniformStorage;
+use crate::vertex::VertexFlat;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DebugShading {
+    // the shader of the draw
+    #[default]
+    Default,
+    // checker pattern over the texture coordinates
+    UvChecker,
+    // the lights of the draw on a white surface
+    LightingOnly,
+}
+
+impl DebugShading {
+    pub const ALL: [DebugShading; 3] = [DebugShading::Default, DebugShading::UvChecker, DebugShading::LightingOnly];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DebugShading::Default => "Default",
+            DebugShading::UvChecker => "UV checker",
+            DebugShading::LightingOnly => "Lighting only",
+        }
+    }
+}
+
+/// Debug modes applied by `RenderQueue` to every draw, overlays can be combined.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DebugViewSettings {
+    pub shading: DebugShading,
+    pub wireframe: bool,
+    pub normals: bool,
+    pub tangents: bool,
+    pub bounding_boxes: bool,
+    // length of the normal and tangent lines in world units
+    pub vector_length: f32,
+    pub checker_count: f32,
+}
+
+impl Default for DebugViewSettings {
+    fn default() -> Self {
+        Self {
+            shading: DebugShading::Default,
+            wireframe: false,
+            normals: false,
+            tangents: false,
+            bounding_boxes: false,
+            vector_length: 0.2,
+            checker_count: 8.0,
+        }
+    }
+}
+
+impl DebugViewSettings {
+    pub fn has_overlay(&self) -> bool {
+        self.wireframe || self.normals || self.tangents || self.bounding_boxes
+    }
+}
+
+// debug program and its variant compiled with `INSTANCED`, reading the model of each copy
+struct Variants {
+    single: Program,
+    instanced: Program,
+}
+
+impl Variants {
+    fn get(&self, item: &DrawItem) -> &Program {
+        if item.instances.is_some() { &self.instanced } else { &self.single }
+    }
+}
+
+/// Programs and buffers of the debug modes. Meshes only need the vertex attributes a mode
+/// reads (`normal`, `tangent` or `tex_coords`) and shaders the usual `vp` and `model`
+/// uniforms, or an instance buffer, draws missing them are left untouched.
+pub struct DebugView {
+    pub settings: DebugViewSettings,
+    solid_program: Variants,
+    box_program: Variants,
+    normals_program: Variants,
+    tangents_program: Variants,
+    uv_checker_program: Variants,
+    lighting_program: Variants,
+    box_vertexes: glium::VertexBuffer<VertexFlat>,
+    box_indexes: glium::IndexBuffer<u16>,
+}
+
+impl DebugView {
+    pub fn new(display: &Display) -> Self {
+        let solid_fs = include_str!("../../resources/shaders/debug_solid.fs.glsl");
+        let vectors_gs = include_str!("../../resources/shaders/debug_vectors.gs.glsl");
+        let variants = |vertex: &str, compile: &dyn Fn(&str) -> Program| Variants {
+            single: compile(vertex),
+            instanced: compile(&shader_variant(vertex, &["INSTANCED"])),
+        };
+        let program = |vertex: &str, fragment: &str| variants(vertex, &|vertex| Program::from_source(display, vertex, fragment, None).unwrap());
+        let vectors_program = |vertex: &str| variants(vertex, &|vertex| Program::new(display, SourceCode {
+            vertex_shader: vertex,
+            tessellation_control_shader: None,
+            tessellation_evaluation_shader: None,
+            geometry_shader: Some(vectors_gs),
+            fragment_shader: solid_fs,
+        }).unwrap());
+
+        let corners = (0..8)
+            .map(|i| VertexFlat::new((i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32))
+            .collect::<Vec<_>>();
+        let edges = [
+            0, 1, 1, 3, 3, 2, 2, 0,
+            4, 5, 5, 7, 7, 6, 6, 4,
+            0, 4, 1, 5, 2, 6, 3, 7u16,
+        ];
+        Self {
+            settings: DebugViewSettings::default(),
+            solid_program: program(include_str!("../../resources/shaders/debug_solid.vs.glsl"), solid_fs),
+            box_program: program(include_str!("../../resources/shaders/debug_box.vs.glsl"), solid_fs),
+            normals_program: vectors_program(include_str!("../../resources/shaders/debug_normals.vs.glsl")),
+            tangents_program: vectors_program(include_str!("../../resources/shaders/debug_tangents.vs.glsl")),
+            uv_checker_program: program(
+                include_str!("../../resources/shaders/debug_uv_checker.vs.glsl"),
+                include_str!("../../resources/shaders/debug_uv_checker.fs.glsl"),
+            ),
+            lighting_program: program(
+                include_str!("../../resources/shaders/material_lighting.vs.glsl"),
+                include_str!("../../resources/shaders/debug_lighting.fs.glsl"),
+            ),
+            box_vertexes: glium::VertexBuffer::new(display, &corners).unwrap(),
+            box_indexes: glium::IndexBuffer::new(display, PrimitiveType::LinesList, &edges).unwrap(),
+        }
+    }
+
+    /// Program replacing the one of `item`, `None` to keep it.
+    pub(crate) fn shading_program(&self, item: &DrawItem) -> Option<&Program> {
+        match self.settings.shading {
+            DebugShading::Default => None,
+            DebugShading::UvChecker if has_attribute(&item.vertexes, "tex_coords") => Some(self.uv_checker_program.get(item)),
+            // unlit draws such as the light bulbs keep their own shader
+            DebugShading::LightingOnly if has_attribute(&item.vertexes, "normal") && item.program.get_uniform("dirLight.direction").is_some() => {
+                Some(self.lighting_program.get(item))
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn replaces_shading(&self) -> bool {
+        self.settings.shading != DebugShading::Default
+    }
+
+    pub(crate) fn add_uniforms<'a>(&self, item: &mut DrawItem<'a>) {
+        item.uniforms.add("checkerCount", UniformValue::Float(self.settings.checker_count));
+    }
+
+    pub(crate) fn draw_overlays<S: Surface>(&self, target: &mut S, item: &DrawItem, render_states: &mut RenderStateCache) {
+        let settings = &self.settings;
+        if !settings.has_overlay() {
+            return;
+        }
+        let lines = RenderState {
+            cull: CullMode::None,
+            depth: DepthState {
+                test: DepthFunction::LessOrEqual,
+                write: false,
+            },
+            ..RenderState::default()
+        };
+        if settings.wireframe {
+            let mut uniforms = item.uniforms.clone();
+            uniforms.add("debugColor", UniformValue::Vec4([0.9, 0.9, 0.9, 1.0]));
+            let state = RenderState {
+                polygon_mode: FillMode::Line,
+                ..lines
+            };
+            draw(target, item, item.vertexes.clone(), item.indexes.clone(), &self.solid_program, &uniforms, render_states.get(&state));
+        }
+        let vectors = [
+            (settings.normals, "normal", &self.normals_program, [0.2, 0.4, 1.0, 1.0]),
+            (settings.tangents, "tangent", &self.tangents_program, [1.0, 0.2, 0.2, 1.0]),
+        ];
+        for (enabled, attribute, program, color) in vectors.iter() {
+            if !*enabled || !has_attribute(&item.vertexes, attribute) {
+                continue;
+            }
+            let mut uniforms = item.uniforms.clone();
+            uniforms.add("debugColor", UniformValue::Vec4(*color));
+            uniforms.add("vectorLength", UniformValue::Float(settings.vector_length));
+            draw(target, item, item.vertexes.clone(), NoIndices(PrimitiveType::Points), program, &uniforms, render_states.get(&lines));
+        }
+        if let (true, Some(bounds)) = (settings.bounding_boxes, item.bounds) {
+            let mut uniforms = item.uniforms.clone();
+            uniforms.add("debugColor", UniformValue::Vec4([1.0, 0.9, 0.1, 1.0]));
+            uniforms.add("boxMin", UniformValue::Vec3(bounds.min.into()));
+            uniforms.add("boxMax", UniformValue::Vec3(bounds.max.into()));
+            draw(target, item, (&self.box_vertexes).into(), &self.box_indexes, &self.box_program, &uniforms, render_states.get(&lines));
+        }
+    }
+}
+
+// draws `vertexes` once per instance of `item` when it has some, with the matching program
+fn draw<'b, S: Surface, I: Into<IndicesSource<'b>>>(
+    target: &mut S, item: &DrawItem<'b>, vertexes: VerticesSource<'b>, indexes: I, program: &Variants, uniforms: &UniformStorage, params: &DrawParameters,
+) {
+    let program = program.get(item);
+    match &item.instances {
+        Some(instances) => target.draw((vertexes, instances.clone()), indexes, program, uniforms, params),
+        None => target.draw(vertexes, indexes, program, uniforms, params),
+    }.unwrap();
+}
+
+fn has_attribute(vertexes: &VerticesSource, name: &str) -> bool {
+    match vertexes {
+        VerticesSource::VertexBuffer(_, format, _) => format.iter().any(|(attribute, _, _, _)| attribute == name),
+        VerticesSource::Marker { .. } => false,
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+
+use glium::{Api, Display, Version};
+use glium::texture::DepthStencilFormat;
+
+use crate::render_state::DepthFunction;
+
+const GL_LOWER_LEFT: u32 = 0x8CA1;
+const GL_NEGATIVE_ONE_TO_ONE: u32 = 0x935E;
+const GL_ZERO_TO_ONE: u32 = 0x935F;
+
+type ClipControl = extern "system" fn(origin: u32, depth: u32);
+
+/// How depth values are stored and compared.
+///
+/// With `ReversedZ` the near plane is at a depth of 1 and the far plane at 0: paired with a
+/// floating point depth buffer and a `[0, 1]` clip range the precision of the floats balances
+/// the one lost by the perspective division, so far surfaces stop fighting.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum DepthMode {
+    #[default]
+    Standard,
+    ReversedZ,
+}
+
+impl DepthMode {
+    pub const ALL: [DepthMode; 2] = [DepthMode::Standard, DepthMode::ReversedZ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DepthMode::Standard => "Standard",
+            DepthMode::ReversedZ => "Reversed-Z",
+        }
+    }
+
+    pub fn is_reversed(&self) -> bool {
+        *self == DepthMode::ReversedZ
+    }
+
+    /// Value the depth buffer is cleared with, the farthest depth.
+    pub fn clear_depth(&self) -> f32 {
+        match self {
+            DepthMode::Standard => 1.0,
+            DepthMode::ReversedZ => 0.0,
+        }
+    }
+
+    // 8 stencil bits are always attached so materials can use `RenderState::stencil`
+    pub fn depth_stencil_format(&self) -> DepthStencilFormat {
+        match self {
+            DepthMode::Standard => DepthStencilFormat::I24I8,
+            DepthMode::ReversedZ => DepthStencilFormat::F32I8,
+        }
+    }
+
+    /// `function` written for the standard convention, flipped when depths are reversed.
+    pub fn depth_test(&self, function: DepthFunction) -> DepthFunction {
+        match self {
+            DepthMode::Standard => function,
+            DepthMode::ReversedZ => function.reversed(),
+        }
+    }
+
+    /// Depths of the near and far planes in normalized device coordinates once `apply` succeeded.
+    /// The far plane of an infinite reversed projection is at 0, where nothing can be unprojected.
+    pub fn ndc_depth_range(&self) -> (f32, f32) {
+        match self {
+            DepthMode::Standard => (-1.0, 1.0),
+            DepthMode::ReversedZ => (1.0, 0.0),
+        }
+    }
+
+    /// Sets the clip depth range to `[0, 1]` for reversed depths and back to `[-1, 1]` otherwise,
+    /// through `glClipControl` which glium does not expose. Returns `false` when the mode cannot
+    /// be used: reversed depths need OpenGL 4.5, the standard range being the context default.
+    #[must_use]
+    pub fn apply(&self, display: &Display) -> bool {
+        let address = if *display.get_opengl_version() < Version(Api::Gl, 4, 5) {
+            std::ptr::null()
+        } else {
+            display.gl_window().get_proc_address("glClipControl")
+        };
+        if address.is_null() {
+            return !self.is_reversed();
+        }
+        let depth = match self {
+            DepthMode::Standard => GL_NEGATIVE_ONE_TO_ONE,
+            DepthMode::ReversedZ => GL_ZERO_TO_ONE,
+        };
+        // glium flushes its own state on draw, the clip control is not part of it
+        let clip_control: ClipControl = unsafe { std::mem::transmute(address) };
+        clip_control(GL_LOWER_LEFT, depth);
+        true
+    }
+}
+
+impl fmt::Display for DepthMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use rusttype::{point, PositionedGlyph, Scale};
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(std::io::Error),
+    // the data is neither a TrueType nor an OpenType font
+    InvalidFont,
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontError::Io(err) => write!(f, "cannot read font: {}", err),
+            FontError::InvalidFont => write!(f, "invalid TTF/OTF font data"),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+impl From<std::io::Error> for FontError {
+    fn from(err: std::io::Error) -> Self {
+        FontError::Io(err)
+    }
+}
+
+pub struct Font(rusttype::Font<'static>);
+
+impl Font {
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, FontError> {
+        rusttype::Font::try_from_vec(data)
+            .map(Font)
+            .ok_or(FontError::InvalidFont)
+    }
+
+    pub fn inner(&self) -> &rusttype::Font<'static> {
+        &self.0
+    }
+
+    /// Distance between two baselines at `size` pixels.
+    pub fn line_height(&self, size: f32) -> f32 {
+        let metrics = self.0.v_metrics(Scale::uniform(size));
+        metrics.ascent - metrics.descent + metrics.line_gap
+    }
+}
+
+pub fn load_font<P: AsRef<Path>>(path: P) -> Result<Font, FontError> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+    Font::from_bytes(data)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// How a string is laid out, sizes are in pixels.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextStyle {
+    pub size: f32,
+    pub color: [f32; 4],
+    // lines are aligned on the origin of the text
+    pub align: TextAlign,
+    // words going past this width start a new line
+    pub max_width: Option<f32>,
+    // multiplier of the line height of the font
+    pub line_spacing: f32,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            size: 16.0,
+            color: [1.0, 1.0, 1.0, 1.0],
+            align: TextAlign::Left,
+            max_width: None,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+/// Glyphs placed with the origin of the text at `(0, 0)` and y going down,
+/// the first baseline being one ascent below the origin.
+pub struct TextLayout {
+    pub glyphs: Vec<PositionedGlyph<'static>>,
+    pub width: f32,
+    pub height: f32,
+}
+
+pub fn layout_text(font: &Font, text: &str, style: &TextStyle) -> TextLayout {
+    let font = font.inner();
+    let scale = Scale::uniform(style.size);
+    let metrics = font.v_metrics(scale);
+    let line_height = (metrics.ascent - metrics.descent + metrics.line_gap) * style.line_spacing;
+
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        lines.extend(wrap_line(font, scale, paragraph, style.max_width));
+    }
+
+    let width = lines.iter().map(|(_, width)| *width).fold(0.0, f32::max);
+    let mut glyphs = Vec::new();
+    for (i, (line, line_width)) in lines.iter().enumerate() {
+        let start = match style.align {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => -line_width / 2.0,
+            TextAlign::Right => -line_width,
+        };
+        let baseline = metrics.ascent + i as f32 * line_height;
+        glyphs.extend(line.iter().map(|(glyph, x)| glyph.clone().positioned(point(start + x, baseline))));
+    }
+    TextLayout {
+        glyphs,
+        width,
+        height: lines.len() as f32 * line_height,
+    }
+}
+
+type LineGlyphs = Vec<(rusttype::ScaledGlyph<'static>, f32)>;
+
+// splits a line without line feed at the last space before `max_width`
+fn wrap_line(font: &rusttype::Font<'static>, scale: Scale, line: &str, max_width: Option<f32>) -> Vec<(LineGlyphs, f32)> {
+    let mut lines = Vec::new();
+    let mut current: LineGlyphs = Vec::new();
+    let mut caret = 0.0;
+    let mut previous = None;
+    // index of the glyph following the last space, and the width of the line before that space
+    let mut last_break: Option<(usize, f32)> = None;
+
+    for c in line.chars() {
+        let glyph = font.glyph(c).scaled(scale);
+        if let Some(previous) = previous {
+            caret += font.pair_kerning(scale, previous, glyph.id());
+        }
+        let advance = glyph.h_metrics().advance_width;
+        previous = Some(glyph.id());
+
+        if let (Some(max_width), Some((index, line_width))) = (max_width, last_break) {
+            if !c.is_whitespace() && caret + advance > max_width {
+                let rest = current.split_off(index);
+                let shift = rest.first().map(|(_, x)| *x).unwrap_or(caret);
+                lines.push((current, line_width));
+                current = rest.into_iter().map(|(glyph, x)| (glyph, x - shift)).collect();
+                caret -= shift;
+                last_break = None;
+            }
+        }
+        if c.is_whitespace() {
+            last_break = Some((current.len() + 1, caret));
+        }
+        current.push((glyph, caret));
+        caret += advance;
+    }
+    lines.push((current, caret));
+    lines
+}
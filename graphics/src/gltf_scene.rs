@@ -0,0 +1,522 @@
+use std::fmt;
+use std::path::Path;
+
+use glium::Display;
+use glium::index::PrimitiveType;
+use glium::texture::{RawImage2d, Texture2d};
+use gltf::camera::Projection as GltfProjection;
+use gltf::image::Format;
+use gltf::khr_lights_punctual::Kind;
+use gltf::material::AlphaMode;
+use gltf::mesh::Mode;
+use math::{Aabb, glm, InfiniteReversedPerspective, Orthographic, Perspective, Projection};
+
+use crate::{DirectionalLight, GVec3, PbrMaterial, PointLight, SpotLight};
+use crate::render_state::{BlendMode, CullMode, DepthState, RenderState};
+use crate::tangent::{generate_tangents, smooth_normals};
+use crate::vertex::{VertexNorm, VertexNormTan};
+
+#[derive(Debug)]
+pub enum GltfError {
+    Import(gltf::Error),
+    // a triangle primitive without the mandatory POSITION attribute
+    MissingPositions(usize),
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GltfError::Import(err) => write!(f, "cannot import glTF: {}", err),
+            GltfError::MissingPositions(mesh) => write!(f, "a primitive of mesh {} has no positions", mesh),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+impl From<gltf::Error> for GltfError {
+    fn from(err: gltf::Error) -> Self {
+        GltfError::Import(err)
+    }
+}
+
+/// Triangle list of a mesh, strips and fans being unrolled.
+pub struct GltfPrimitive {
+    pub vertexes: Vec<VertexNormTan>,
+    pub indexes: Vec<u32>,
+    // index in `GltfScene::materials`
+    pub material: Option<usize>,
+    pub bounds: Option<Aabb>,
+}
+
+impl GltfPrimitive {
+    pub fn buffers(&self, display: &Display) -> (glium::VertexBuffer<VertexNormTan>, glium::IndexBuffer<u32>) {
+        (
+            glium::VertexBuffer::new(display, &self.vertexes).unwrap(),
+            glium::IndexBuffer::new(display, PrimitiveType::TrianglesList, &self.indexes).unwrap(),
+        )
+    }
+}
+
+pub struct GltfMesh {
+    pub name: Option<String>,
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+pub struct GltfCamera {
+    pub name: Option<String>,
+    // cameras without far plane become infinite reversed-Z perspectives
+    pub projection: Projection,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GltfLightKind {
+    Directional,
+    Point,
+    // cone angles in radians
+    Spot { inner: f32, outer: f32 },
+}
+
+/// `KHR_lights_punctual` light, placed in the scene through the nodes using it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct GltfLight {
+    pub name: Option<String>,
+    pub kind: GltfLightKind,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: Option<f32>,
+}
+
+/// Light of a node converted to the engine lights, in world space.
+#[derive(Debug)]
+pub enum SceneLight {
+    Directional(DirectionalLight),
+    Point(PointLight),
+    Spot(SpotLight),
+}
+
+pub struct GltfNode {
+    pub name: Option<String>,
+    // relative to the parent node
+    pub transform: glm::Mat4,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub camera: Option<usize>,
+    pub light: Option<usize>,
+}
+
+/// glTF material before its textures are uploaded.
+#[derive(Clone, PartialEq, Debug)]
+pub struct GltfMaterial {
+    pub name: Option<String>,
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: [f32; 3],
+    // indexes in `GltfDocument::images`, metallic in blue and roughness in green
+    pub base_color_image: Option<usize>,
+    pub metallic_roughness_image: Option<usize>,
+    pub occlusion_image: Option<usize>,
+    pub emissive_image: Option<usize>,
+    pub normal_image: Option<usize>,
+    // set for masked materials, whose fragments under the cutoff are discarded
+    pub alpha_cutoff: Option<f32>,
+    pub render_state: RenderState,
+}
+
+/// Content of a `.gltf` or `.glb` file, every reference being an index in the matching list.
+/// Materials are `PbrMaterial`s once uploaded, `GltfMaterial`s in a `GltfDocument`.
+pub struct GltfScene<M = PbrMaterial> {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<M>,
+    pub cameras: Vec<GltfCamera>,
+    pub lights: Vec<GltfLight>,
+    pub nodes: Vec<GltfNode>,
+    // nodes of the default scene, or of the first one
+    pub roots: Vec<usize>,
+}
+
+impl<M> GltfScene<M> {
+    /// World transform of every node, indexed like `nodes`. Nodes outside the
+    /// hierarchy of `roots` keep their local transform.
+    pub fn world_transforms(&self) -> Vec<glm::Mat4> {
+        let mut transforms = self.nodes.iter().map(|node| node.transform).collect::<Vec<_>>();
+        let mut stack = self.roots.iter().map(|root| (*root, glm::Mat4::identity())).collect::<Vec<_>>();
+        while let Some((index, parent)) = stack.pop() {
+            let world = parent * self.nodes[index].transform;
+            transforms[index] = world;
+            stack.extend(self.nodes[index].children.iter().map(|child| (*child, world)));
+        }
+        transforms
+    }
+
+    /// Lights of the nodes moved by their world transform. glTF intensities are
+    /// physical units, the color is scaled by the intensity clamped to 1.
+    pub fn scene_lights(&self) -> Vec<SceneLight> {
+        let transforms = self.world_transforms();
+        self.nodes.iter()
+            .zip(transforms.iter())
+            .filter_map(|(node, transform)| node.light.map(|light| (&self.lights[light], transform)))
+            .map(|(light, transform)| {
+                let position = (transform * glm::vec4(0.0, 0.0, 0.0, 1.0)).xyz();
+                // lights shine down their local -z axis
+                let direction = glm::normalize(&(transform * glm::vec4(0.0, 0.0, -1.0, 0.0)).xyz());
+                let color = glm::Vec3::from(light.color) * light.intensity.min(1.0);
+                let ambient = gvec3(&(color * 0.05));
+                let (diffuse, specular) = (gvec3(&color), gvec3(&color));
+                // the attenuation reaches 1/256 at the range, inverse square falloff otherwise
+                let quadratic = light.range.map_or(1.0, |range| 255.0 / (range * range));
+                match light.kind {
+                    GltfLightKind::Directional => SceneLight::Directional(DirectionalLight::new(gvec3(&direction), ambient, diffuse, specular)),
+                    GltfLightKind::Point => SceneLight::Point(PointLight::new(gvec3(&position), ambient, diffuse, specular, 1.0, 0.0, quadratic)),
+                    GltfLightKind::Spot { inner, outer } => SceneLight::Spot(SpotLight::new(
+                        gvec3(&position), gvec3(&direction), ambient, diffuse, specular, 1.0, 0.0, quadratic, inner.cos(), outer.cos(),
+                    )),
+                }
+            })
+            .collect()
+    }
+}
+
+fn gvec3(v: &glm::Vec3) -> GVec3 {
+    GVec3::new(v.x, v.y, v.z)
+}
+
+/// glTF file read without a display, its images waiting to be uploaded with the materials.
+pub struct GltfDocument {
+    pub scene: GltfScene<GltfMaterial>,
+    pub images: Vec<gltf::image::Data>,
+}
+
+impl GltfDocument {
+    pub fn upload(self, display: &Display) -> GltfScene {
+        let images = self.images;
+        let texture = |image: Option<usize>, channel: Option<usize>| image.map(|image| upload_image(display, &images[image], channel));
+        let materials = self.scene.materials.iter()
+            .map(|material| {
+                let [r, g, b, a] = material.base_color;
+                let [er, eg, eb] = material.emissive;
+                let mut result = PbrMaterial::new(GVec3::new(r, g, b), material.metallic, material.roughness)
+                    .with_alpha(a, material.alpha_cutoff)
+                    .with_emissive(GVec3::new(er, eg, eb), texture(material.emissive_image, None));
+                result.albedo_map = texture(material.base_color_image, None);
+                result.metallic_map = texture(material.metallic_roughness_image, Some(2));
+                result.roughness_map = texture(material.metallic_roughness_image, Some(1));
+                result.ao_map = texture(material.occlusion_image, None);
+                result.normal_map = texture(material.normal_image, None);
+                result.with_render_state(material.render_state)
+            })
+            .collect();
+        let GltfScene { meshes, cameras, lights, nodes, roots, .. } = self.scene;
+        GltfScene {
+            meshes,
+            materials,
+            cameras,
+            lights,
+            nodes,
+            roots,
+        }
+    }
+}
+
+/// Imports a glTF 2.0 file with its external or embedded buffers and images.
+pub fn load_gltf<P: AsRef<Path>>(display: &Display, path: P) -> Result<GltfScene, GltfError> {
+    parse_gltf(path).map(|document| document.upload(display))
+}
+
+/// Reads a glTF 2.0 file and its buffers and images, leaving the upload to `GltfDocument::upload`.
+pub fn parse_gltf<P: AsRef<Path>>(path: P) -> Result<GltfDocument, GltfError> {
+    let (document, buffers, images) = gltf::import(path)?;
+
+    let mut meshes = Vec::new();
+    for mesh in document.meshes() {
+        let mut primitives = Vec::new();
+        for primitive in mesh.primitives() {
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let positions = reader.read_positions().ok_or(GltfError::MissingPositions(mesh.index()))?.collect::<Vec<_>>();
+            let indexes = match reader.read_indices() {
+                Some(indexes) => indexes.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            let indexes = match triangle_list(primitive.mode(), indexes) {
+                Some(indexes) => indexes,
+                // points and lines are not supported
+                None => continue,
+            };
+            let normals = match reader.read_normals() {
+                Some(normals) => normals.collect(),
+                None => smooth_normals(&positions, &indexes),
+            };
+            let tex_coords = reader.read_tex_coords(0)
+                .map(|tex_coords| tex_coords.into_f32().collect())
+                .unwrap_or_else(|| vec![[0.0, 0.0]; positions.len()]);
+            let vertexes = match reader.read_tangents() {
+                Some(tangents) => positions.iter().zip(normals.iter()).zip(tex_coords.iter()).zip(tangents)
+                    .map(|(((p, n), t), tangent)| VertexNormTan::new(p[0], p[1], p[2], *n, *t, tangent))
+                    .collect(),
+                None => {
+                    let vertexes = positions.iter().zip(normals.iter()).zip(tex_coords.iter())
+                        .map(|((p, n), t)| VertexNorm::new(p[0], p[1], p[2], *n, *t))
+                        .collect::<Vec<_>>();
+                    generate_tangents(&vertexes, &indexes)
+                }
+            };
+            primitives.push(GltfPrimitive {
+                bounds: Aabb::from_points(positions.iter().map(|p| glm::Vec3::from(*p))),
+                vertexes,
+                indexes,
+                material: primitive.material().index(),
+            });
+        }
+        meshes.push(GltfMesh {
+            name: mesh.name().map(str::to_string),
+            primitives,
+        });
+    }
+
+    let image = |texture: Option<gltf::texture::Texture>| texture.map(|texture| texture.source().index());
+    let materials = document.materials()
+        .map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            let mut render_state = RenderState::default();
+            if material.double_sided() {
+                render_state.cull = CullMode::None;
+            }
+            let mut base_color = pbr.base_color_factor();
+            let mut alpha_cutoff = None;
+            match material.alpha_mode() {
+                AlphaMode::Opaque => base_color[3] = 1.0,
+                // the cutoff defaults to 0.5 in the specification
+                AlphaMode::Mask => alpha_cutoff = Some(material.alpha_cutoff().unwrap_or(0.5)),
+                AlphaMode::Blend => {
+                    render_state.blend = BlendMode::Alpha;
+                    render_state.depth = DepthState { write: false, ..DepthState::default() };
+                }
+            }
+            GltfMaterial {
+                name: material.name().map(str::to_string),
+                base_color,
+                metallic: pbr.metallic_factor(),
+                roughness: pbr.roughness_factor(),
+                emissive: material.emissive_factor(),
+                base_color_image: image(pbr.base_color_texture().map(|info| info.texture())),
+                metallic_roughness_image: image(pbr.metallic_roughness_texture().map(|info| info.texture())),
+                occlusion_image: image(material.occlusion_texture().map(|occlusion| occlusion.texture())),
+                emissive_image: image(material.emissive_texture().map(|info| info.texture())),
+                normal_image: image(material.normal_texture().map(|normal| normal.texture())),
+                alpha_cutoff,
+                render_state,
+            }
+        })
+        .collect();
+
+    let cameras = document.cameras()
+        .map(|camera| {
+            let projection = match camera.projection() {
+                GltfProjection::Perspective(perspective) => {
+                    let aspect = perspective.aspect_ratio().unwrap_or_else(|| Perspective::default().aspect);
+                    match perspective.zfar() {
+                        Some(far) => Perspective { aspect, fov: perspective.yfov(), near: perspective.znear(), far }.into(),
+                        None => InfiniteReversedPerspective { aspect, fov: perspective.yfov(), near: perspective.znear() }.into(),
+                    }
+                }
+                GltfProjection::Orthographic(orthographic) => Orthographic {
+                    aspect: orthographic.xmag() / orthographic.ymag(),
+                    height: orthographic.ymag() * 2.0,
+                    near: orthographic.znear(),
+                    far: orthographic.zfar(),
+                }.into(),
+            };
+            GltfCamera {
+                name: camera.name().map(str::to_string),
+                projection,
+            }
+        })
+        .collect();
+
+    let lights = document.lights()
+        .map(|lights| lights.map(|light| GltfLight {
+            name: light.name().map(str::to_string),
+            kind: match light.kind() {
+                Kind::Directional => GltfLightKind::Directional,
+                Kind::Point => GltfLightKind::Point,
+                Kind::Spot { inner_cone_angle, outer_cone_angle } => GltfLightKind::Spot { inner: inner_cone_angle, outer: outer_cone_angle },
+            },
+            color: light.color(),
+            intensity: light.intensity(),
+            range: light.range(),
+        }).collect())
+        .unwrap_or_default();
+
+    let nodes = document.nodes()
+        .map(|node| GltfNode {
+            name: node.name().map(str::to_string),
+            transform: glm::Mat4::from(node.transform().matrix()),
+            children: node.children().map(|child| child.index()).collect(),
+            mesh: node.mesh().map(|mesh| mesh.index()),
+            camera: node.camera().map(|camera| camera.index()),
+            light: node.light().map(|light| light.index()),
+        })
+        .collect();
+    let roots = document.default_scene()
+        .or_else(|| document.scenes().next())
+        .map(|scene| scene.nodes().map(|node| node.index()).collect())
+        .unwrap_or_default();
+
+    Ok(GltfDocument {
+        scene: GltfScene {
+            meshes,
+            materials,
+            cameras,
+            lights,
+            nodes,
+            roots,
+        },
+        images,
+    })
+}
+
+fn triangle_list(mode: Mode, indexes: Vec<u32>) -> Option<Vec<u32>> {
+    match mode {
+        Mode::Triangles => Some(indexes),
+        // every other triangle of a strip is flipped to keep the winding
+        Mode::TriangleStrip => Some((0..indexes.len().saturating_sub(2))
+            .flat_map(|i| if i % 2 == 0 {
+                [indexes[i], indexes[i + 1], indexes[i + 2]]
+            } else {
+                [indexes[i + 1], indexes[i], indexes[i + 2]]
+            }.to_vec())
+            .collect()),
+        Mode::TriangleFan => Some((1..indexes.len().saturating_sub(1))
+            .flat_map(|i| [indexes[0], indexes[i], indexes[i + 1]].to_vec())
+            .collect()),
+        _ => None,
+    }
+}
+
+// RGBA8 texture of the image, or of a single channel spread to the red one; glTF images
+// start with their top row like the texture coordinates expect
+fn upload_image(display: &Display, image: &gltf::image::Data, channel: Option<usize>) -> Texture2d {
+    let (components, bytes, bgr) = match image.format {
+        Format::R8 => (1, 1, false),
+        Format::R8G8 => (2, 1, false),
+        Format::R8G8B8 => (3, 1, false),
+        Format::R8G8B8A8 => (4, 1, false),
+        Format::B8G8R8 => (3, 1, true),
+        Format::B8G8R8A8 => (4, 1, true),
+        Format::R16 => (1, 2, false),
+        Format::R16G16 => (2, 2, false),
+        Format::R16G16B16 => (3, 2, false),
+        Format::R16G16B16A16 => (4, 2, false),
+    };
+    // keeps the most significant byte of little endian 16 bit components
+    let component = |pixel: &[u8], i: usize| pixel[i * bytes + bytes - 1];
+    let mut rgba = Vec::with_capacity((image.width * image.height * 4) as usize);
+    for pixel in image.pixels.chunks(components * bytes) {
+        let mut color = [0, 0, 0, 255];
+        for (i, value) in color.iter_mut().enumerate().take(components) {
+            let source = if bgr && i < 3 { 2 - i } else { i };
+            *value = component(pixel, source);
+        }
+        if let Some(channel) = channel {
+            color = [color[channel], 0, 0, 255];
+        }
+        rgba.extend_from_slice(&color);
+    }
+    Texture2d::new(display, RawImage2d::from_raw_rgba(rgba, (image.width, image.height))).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: &glm::Vec3, b: &glm::Vec3) {
+        assert!(glm::distance(a, b) < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn parses_the_sample_scene() {
+        let document = parse_gltf(concat!(env!("CARGO_MANIFEST_DIR"), "/../resources/models/sample_scene.gltf")).unwrap();
+        let scene = &document.scene;
+
+        // the embedded base64 buffer holds a flat quad, two corners raised by a sparse accessor
+        let primitive = &scene.meshes[0].primitives[0];
+        let positions = primitive.vertexes.iter().map(|vertex| vertex.position()).collect::<Vec<_>>();
+        assert_eq!(positions, vec![[-0.5, 0.0, -0.5], [0.5, 0.25, -0.5], [0.5, 0.25, 0.5], [-0.5, 0.0, 0.5]]);
+        let tex_coords = primitive.vertexes.iter().map(|vertex| vertex.tex_coords()).collect::<Vec<_>>();
+        assert_eq!(tex_coords, vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+        assert_eq!(primitive.indexes, vec![0, 3, 2, 2, 1, 0]);
+        assert_eq!(primitive.material, Some(0));
+        let bounds = primitive.bounds.unwrap();
+        assert_eq!((bounds.min, bounds.max), (glm::vec3(-0.5, 0.0, -0.5), glm::vec3(0.5, 0.25, 0.5)));
+
+        let material = &scene.materials[0];
+        assert_eq!(material.name.as_deref(), Some("copper"));
+        assert_eq!(material.base_color, [0.95, 0.64, 0.54, 1.0]);
+        assert_eq!((material.metallic, material.roughness), (1.0, 0.35));
+        assert_eq!(material.render_state.cull, CullMode::None);
+        assert_eq!(material.render_state.blend, BlendMode::Opaque);
+        assert_eq!(material.alpha_cutoff, None);
+
+        assert_eq!(scene.roots, vec![0]);
+        assert_eq!(scene.nodes[0].children, vec![1, 2, 3]);
+        let names = scene.nodes.iter().map(|node| node.name.as_deref().unwrap()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["root", "plate", "lamp", "viewer"]);
+        assert_eq!((scene.nodes[1].mesh, scene.nodes[2].light, scene.nodes[3].camera), (Some(0), Some(0), Some(0)));
+        let world = scene.world_transforms();
+        assert_near(&(world[3] * glm::vec4(0.0, 0.0, 0.0, 1.0)).xyz(), &glm::vec3(0.0, 1.5, 5.0));
+        // the plate is turned by 45 degrees around y and scaled by 1.5 on x and z
+        let corner = (world[1] * glm::vec4(0.5, 0.0, 0.5, 1.0)).xyz();
+        assert_near(&corner, &glm::vec3(1.5 * 2f32.sqrt() / 2.0, 0.5, 2.0));
+
+        // without far plane the camera is an infinite perspective
+        match scene.cameras[0].projection {
+            Projection::InfiniteReversed(perspective) => {
+                assert_eq!((perspective.aspect, perspective.fov, perspective.near), (1.7778, 0.8, 0.1));
+            }
+            other => panic!("unexpected projection {:?}", other),
+        }
+
+        let light = &scene.lights[0];
+        assert_eq!(light.name.as_deref(), Some("lamp"));
+        assert_eq!(light.kind, GltfLightKind::Point);
+        assert_eq!((light.color, light.intensity, light.range), ([1.0, 0.9, 0.7], 1.0, Some(6.0)));
+        match &scene.scene_lights()[..] {
+            [SceneLight::Point(point)] => {
+                assert_near(&point.position.data, &glm::vec3(0.0, 1.5, 2.0));
+            }
+            other => panic!("unexpected lights {:?}", other),
+        }
+    }
+
+    #[test]
+    fn maps_alpha_modes() {
+        let path = std::env::temp_dir().join(format!("gltf_alpha_modes_{}.gltf", std::process::id()));
+        std::fs::write(&path, r#"{
+            "asset": { "version": "2.0" },
+            "materials": [
+                { "pbrMetallicRoughness": { "baseColorFactor": [1, 1, 1, 0.5] } },
+                { "alphaMode": "MASK", "alphaCutoff": 0.3, "pbrMetallicRoughness": { "baseColorFactor": [1, 1, 1, 0.5] } },
+                { "alphaMode": "MASK" },
+                { "alphaMode": "BLEND", "pbrMetallicRoughness": { "baseColorFactor": [1, 1, 1, 0.5] } }
+            ]
+        }"#).unwrap();
+        let document = parse_gltf(&path);
+        std::fs::remove_file(&path).unwrap();
+        let materials = document.unwrap().scene.materials;
+
+        // opaque materials ignore the alpha of their base color
+        assert_eq!(materials[0].base_color[3], 1.0);
+        assert_eq!(materials[0].alpha_cutoff, None);
+        assert_eq!(materials[0].render_state, RenderState::default());
+
+        assert_eq!(materials[1].base_color[3], 0.5);
+        assert_eq!(materials[1].alpha_cutoff, Some(0.3));
+        assert_eq!(materials[1].render_state.blend, BlendMode::Opaque);
+        assert_eq!(materials[2].alpha_cutoff, Some(0.5));
+
+        assert_eq!(materials[3].alpha_cutoff, None);
+        assert_eq!(materials[3].render_state.blend, BlendMode::Alpha);
+        assert!(!materials[3].render_state.depth.write);
+    }
+}
@@ -0,0 +1,792 @@
+use std::borrow::Cow;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use glium::{BlitTarget, Display, Surface};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{ClientFormat, CubeLayer, Cubemap, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerBehavior, SamplerWrapFunction, UniformValue};
+use image::codecs::hdr::HdrDecoder;
+use math::glm;
+use math::glm::{vec3, Vec3};
+
+use crate::uniform::{StructToUniform, UniformStorage};
+
+const CACHE_MAGIC: &[u8; 4] = b"IBL1";
+
+const CUBE_LAYERS: [CubeLayer; 6] = [
+    CubeLayer::PositiveX,
+    CubeLayer::NegativeX,
+    CubeLayer::PositiveY,
+    CubeLayer::NegativeY,
+    CubeLayer::PositiveZ,
+    CubeLayer::NegativeZ,
+];
+
+#[derive(Debug)]
+pub enum IblError {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    InvalidEnvironment(String),
+    InvalidCache(String),
+}
+
+impl From<std::io::Error> for IblError {
+    fn from(err: std::io::Error) -> Self {
+        IblError::Io(err)
+    }
+}
+
+impl From<image::ImageError> for IblError {
+    fn from(err: image::ImageError) -> Self {
+        IblError::Image(err)
+    }
+}
+
+/// Environment the image based lighting is computed from.
+#[derive(Debug, Clone)]
+pub enum EnvironmentSource {
+    /// Latitude/longitude panorama, `.hdr` files are read as linear radiance, other formats as sRGB.
+    Equirectangular(String),
+    /// Cubemap faces in +X, -X, +Y, -Y, +Z, -Z order.
+    Cubemap([String; 6]),
+}
+
+impl EnvironmentSource {
+    // identifies the source files, their version and the settings in the cache so a cache built
+    // from another environment, an edited file or other settings is not reused
+    fn cache_key(&self, settings: &IblSettings) -> String {
+        let stamped = |path: &String| format!("{}@{}", path, file_stamp(path));
+        let source = match self {
+            EnvironmentSource::Equirectangular(path) => format!("equirectangular:{}", stamped(path)),
+            EnvironmentSource::Cubemap(faces) => format!("cubemap:{}", faces.iter().map(stamped).collect::<Vec<_>>().join("|")),
+        };
+        format!("{};{:?}", source, settings)
+    }
+}
+
+// modification time and length of a file, changing whenever it is edited
+fn file_stamp(path: &str) -> String {
+    match std::fs::metadata(path) {
+        Ok(metadata) => {
+            let modified = metadata.modified().ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_nanos());
+            format!("{}:{}", modified, metadata.len())
+        }
+        Err(_) => "missing".to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IblSettings {
+    // size of the cubemap the environment is resampled into before filtering
+    pub environment_size: u32,
+    pub irradiance_size: u32,
+    pub prefilter_size: u32,
+    pub prefilter_mips: u32,
+    pub prefilter_samples: u32,
+    pub brdf_lut_size: u32,
+    pub brdf_samples: u32,
+}
+
+impl IblSettings {
+    /// Number of prefiltered mips actually computed, `prefilter_mips` limited to the mip chain of
+    /// `prefilter_size` and at least 1.
+    pub fn prefilter_mip_count(&self) -> u32 {
+        let chain = 32 - self.prefilter_size.max(1).leading_zeros();
+        self.prefilter_mips.clamp(1, chain)
+    }
+
+    fn prefilter_mip_size(&self, mip: u32) -> u32 {
+        (self.prefilter_size >> mip).max(1)
+    }
+
+    // byte length of a cache file written with these settings and a key of `key_len` bytes
+    fn cache_len(&self, key_len: usize) -> u64 {
+        let cube = |size: u32| 4 + 6 * (size as u64) * (size as u64) * 12;
+        let prefiltered = (0..self.prefilter_mip_count()).map(|mip| cube(self.prefilter_mip_size(mip))).sum::<u64>();
+        let lut = (self.brdf_lut_size as u64) * (self.brdf_lut_size as u64) * 8;
+        (4 + 4 + key_len as u64) + cube(self.irradiance_size) + 4 + prefiltered + 4 + lut
+    }
+}
+
+impl Default for IblSettings {
+    fn default() -> Self {
+        Self {
+            environment_size: 128,
+            irradiance_size: 32,
+            prefilter_size: 128,
+            prefilter_mips: 5,
+            prefilter_samples: 64,
+            brdf_lut_size: 128,
+            brdf_samples: 256,
+        }
+    }
+}
+
+/// Square RGB float image per cube face, rows go from the top of the face to the bottom.
+#[derive(Debug, Clone)]
+pub struct CubeData {
+    pub size: u32,
+    pub faces: Vec<Vec<[f32; 3]>>,
+}
+
+impl CubeData {
+    fn from_fn<F: Fn(&Vec3) -> [f32; 3]>(size: u32, f: F) -> Self {
+        let faces = (0..6)
+            .map(|face| {
+                let mut texels = Vec::with_capacity((size * size) as usize);
+                for y in 0..size {
+                    for x in 0..size {
+                        texels.push(f(&texel_direction(face, x, y, size)));
+                    }
+                }
+                texels
+            })
+            .collect();
+        Self { size, faces }
+    }
+
+    fn downsample(&self) -> Self {
+        let size = (self.size / 2).max(1);
+        let faces = self.faces.iter()
+            .map(|face| {
+                let mut texels = Vec::with_capacity((size * size) as usize);
+                for y in 0..size {
+                    for x in 0..size {
+                        let mut sum = [0.0; 3];
+                        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+                            let sx = (x * 2 + dx).min(self.size - 1);
+                            let sy = (y * 2 + dy).min(self.size - 1);
+                            let texel = face[(sy * self.size + sx) as usize];
+                            for c in 0..3 {
+                                sum[c] += texel[c] * 0.25;
+                            }
+                        }
+                        texels.push(sum);
+                    }
+                }
+                texels
+            })
+            .collect();
+        Self { size, faces }
+    }
+
+    fn sample(&self, dir: &Vec3) -> Vec3 {
+        let (face, s, t) = direction_to_face(dir);
+        let x = ((s * self.size as f32) as u32).min(self.size - 1);
+        let y = ((t * self.size as f32) as u32).min(self.size - 1);
+        let texel = self.faces[face][(y * self.size + x) as usize];
+        vec3(texel[0], texel[1], texel[2])
+    }
+
+    fn to_raw(&self, face: usize) -> RawImage2d<'static, f32> {
+        let data = self.faces[face].iter().flat_map(|texel| texel.iter().copied()).collect::<Vec<_>>();
+        RawImage2d::from_raw_rgb(data, (self.size, self.size))
+    }
+}
+
+/// Environment loaded on the CPU, sampled by direction.
+enum Environment {
+    Equirectangular {
+        width: u32,
+        height: u32,
+        texels: Vec<[f32; 3]>,
+    },
+    Cubemap(CubeData),
+}
+
+impl Environment {
+    fn load(source: &EnvironmentSource) -> Result<Self, IblError> {
+        match source {
+            EnvironmentSource::Equirectangular(path) => {
+                let (width, height, texels) = load_linear_image(path)?;
+                Ok(Environment::Equirectangular { width, height, texels })
+            }
+            EnvironmentSource::Cubemap(paths) => {
+                let mut size = 0;
+                let mut faces = vec![];
+                for path in paths.iter() {
+                    let (width, height, texels) = load_linear_image(path)?;
+                    if width != height || (size != 0 && width != size) {
+                        return Err(IblError::InvalidEnvironment(format!("cubemap face {} is not a square of the same size as the other faces", path)));
+                    }
+                    size = width;
+                    faces.push(texels);
+                }
+                Ok(Environment::Cubemap(CubeData { size, faces }))
+            }
+        }
+    }
+
+    fn sample(&self, dir: &Vec3) -> [f32; 3] {
+        match self {
+            Environment::Equirectangular { width, height, texels } => {
+                let dir = dir.normalize();
+                let u = dir.z.atan2(dir.x) / (2.0 * PI) + 0.5;
+                let v = dir.y.clamp(-1.0, 1.0).acos() / PI;
+                let x = ((u * *width as f32) as u32).min(width - 1);
+                let y = ((v * *height as f32) as u32).min(height - 1);
+                texels[(y * width + x) as usize]
+            }
+            Environment::Cubemap(cube) => {
+                let c = cube.sample(dir);
+                [c.x, c.y, c.z]
+            }
+        }
+    }
+}
+
+fn load_linear_image(path: &str) -> Result<(u32, u32, Vec<[f32; 3]>), IblError> {
+    if path.to_lowercase().ends_with(".hdr") {
+        let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+        let meta = decoder.metadata();
+        let texels = decoder.read_image_hdr()?
+            .into_iter()
+            .map(|p| p.0)
+            .collect();
+        Ok((meta.width, meta.height, texels))
+    } else {
+        let image = image::open(path)?.to_rgb8();
+        let (width, height) = image.dimensions();
+        let texels = image.pixels()
+            .map(|p| [srgb_to_linear(p.0[0]), srgb_to_linear(p.0[1]), srgb_to_linear(p.0[2])])
+            .collect();
+        Ok((width, height, texels))
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    (value as f32 / 255.).powf(2.2)
+}
+
+// OpenGL cubemap convention, see the cube map selection table of the GL specification
+fn texel_direction(face: usize, x: u32, y: u32, size: u32) -> Vec3 {
+    let sc = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+    let tc = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+    let dir = match face {
+        0 => vec3(1.0, -tc, -sc),
+        1 => vec3(-1.0, -tc, sc),
+        2 => vec3(sc, 1.0, tc),
+        3 => vec3(sc, -1.0, -tc),
+        4 => vec3(sc, -tc, 1.0),
+        _ => vec3(-sc, -tc, -1.0),
+    };
+    dir.normalize()
+}
+
+fn direction_to_face(dir: &Vec3) -> (usize, f32, f32) {
+    let (ax, ay, az) = (dir.x.abs(), dir.y.abs(), dir.z.abs());
+    let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+        if dir.x > 0.0 { (0, -dir.z, -dir.y, ax) } else { (1, dir.z, -dir.y, ax) }
+    } else if ay >= az {
+        if dir.y > 0.0 { (2, dir.x, dir.z, ay) } else { (3, dir.x, -dir.z, ay) }
+    } else if dir.z > 0.0 {
+        (4, dir.x, -dir.y, az)
+    } else {
+        (5, -dir.x, -dir.y, az)
+    };
+    (face, (sc / ma + 1.0) * 0.5, (tc / ma + 1.0) * 0.5)
+}
+
+fn hammersley(i: u32, count: u32) -> (f32, f32) {
+    (i as f32 / count as f32, (i.reverse_bits() as f64 * 2.328_306_436_538_696_3e-10) as f32)
+}
+
+fn importance_sample_ggx(xi: (f32, f32), normal: &Vec3, roughness: f32) -> Vec3 {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.0;
+    let cos_theta = ((1.0 - xi.1) / (1.0 + (a * a - 1.0) * xi.1)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+    let h = vec3(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta);
+    let (tangent, bitangent) = tangent_frame(normal);
+    (tangent * h.x + bitangent * h.y + normal * h.z).normalize()
+}
+
+fn tangent_frame(normal: &Vec3) -> (Vec3, Vec3) {
+    let up = if normal.z.abs() < 0.999 { vec3(0.0, 0.0, 1.0) } else { vec3(1.0, 0.0, 0.0) };
+    let tangent = up.cross(normal).normalize();
+    let bitangent = normal.cross(&tangent);
+    (tangent, bitangent)
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a2 = roughness.powi(4);
+    let denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * denom * denom)
+}
+
+fn geometry_smith_ibl(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    let g = |n_dot_x: f32| n_dot_x / (n_dot_x * (1.0 - k) + k);
+    g(n_dot_v) * g(n_dot_l)
+}
+
+/// Source cubemap with its box filtered mip chain, sampled trilinearly by level of detail.
+struct CubeChain(Vec<CubeData>);
+
+impl CubeChain {
+    fn new(base: CubeData) -> Self {
+        let mut levels = vec![base];
+        while levels.last().unwrap().size > 1 {
+            let next = levels.last().unwrap().downsample();
+            levels.push(next);
+        }
+        Self(levels)
+    }
+
+    fn sample_lod(&self, dir: &Vec3, lod: f32) -> Vec3 {
+        let lod = lod.clamp(0.0, (self.0.len() - 1) as f32);
+        let low = lod.floor() as usize;
+        let high = (low + 1).min(self.0.len() - 1);
+        let t = lod - low as f32;
+        glm::lerp(&self.0[low].sample(dir), &self.0[high].sample(dir), t)
+    }
+}
+
+/// Precomputed image based lighting data, kept on the CPU so it can be cached to disk.
+#[derive(Debug, Clone)]
+pub struct IblMaps {
+    pub irradiance: CubeData,
+    // mip 0 is the mirror reflection, the last mip a roughness of 1
+    pub prefiltered: Vec<CubeData>,
+    pub brdf_lut_size: u32,
+    // (scale, bias) applied to F0 for each (n_dot_v, roughness)
+    pub brdf_lut: Vec<[f32; 2]>,
+}
+
+impl IblMaps {
+    pub fn compute(source: &EnvironmentSource, settings: &IblSettings) -> Result<Self, IblError> {
+        let environment = Environment::load(source)?;
+        let chain = CubeChain::new(CubeData::from_fn(settings.environment_size, |dir| environment.sample(dir)));
+        Ok(Self {
+            irradiance: compute_irradiance(&chain, settings.irradiance_size),
+            prefiltered: compute_prefiltered(&chain, settings),
+            brdf_lut_size: settings.brdf_lut_size,
+            brdf_lut: compute_brdf_lut(settings.brdf_lut_size, settings.brdf_samples),
+        })
+    }
+
+    pub fn load_or_compute<P: AsRef<Path>>(source: &EnvironmentSource, settings: &IblSettings, cache_path: P) -> Result<Self, IblError> {
+        let key = source.cache_key(settings);
+        if let Ok(maps) = Self::load(&cache_path, &key, settings) {
+            return Ok(maps);
+        }
+        let maps = Self::compute(source, settings)?;
+        if let Some(dir) = cache_path.as_ref().parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        maps.save(&cache_path, &key)?;
+        Ok(maps)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P, key: &str) -> Result<(), IblError> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(CACHE_MAGIC)?;
+        write_u32(&mut out, key.len() as u32)?;
+        out.write_all(key.as_bytes())?;
+        write_cube(&mut out, &self.irradiance)?;
+        write_u32(&mut out, self.prefiltered.len() as u32)?;
+        for mip in self.prefiltered.iter() {
+            write_cube(&mut out, mip)?;
+        }
+        write_u32(&mut out, self.brdf_lut_size)?;
+        for texel in self.brdf_lut.iter() {
+            write_f32s(&mut out, texel)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Reads a cache written by `save` with the same key and settings. Every size is checked
+    /// against the settings before anything is allocated, a mismatch being `InvalidCache`.
+    pub fn load<P: AsRef<Path>>(path: P, key: &str, settings: &IblSettings) -> Result<Self, IblError> {
+        let file = File::open(path)?;
+        if file.metadata()?.len() != settings.cache_len(key.len()) {
+            return Err(IblError::InvalidCache("cache size does not match the settings".to_string()));
+        }
+        let mut input = BufReader::new(file);
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != CACHE_MAGIC {
+            return Err(IblError::InvalidCache("not an IBL cache file".to_string()));
+        }
+        if read_u32(&mut input)? as usize != key.len() {
+            return Err(IblError::InvalidCache("cache was built from another environment".to_string()));
+        }
+        let mut cached_key = vec![0u8; key.len()];
+        input.read_exact(&mut cached_key)?;
+        if cached_key != key.as_bytes() {
+            return Err(IblError::InvalidCache("cache was built from another environment".to_string()));
+        }
+        let irradiance = read_cube(&mut input, settings.irradiance_size)?;
+        if read_u32(&mut input)? != settings.prefilter_mip_count() {
+            return Err(IblError::InvalidCache("unexpected number of prefiltered mips".to_string()));
+        }
+        let prefiltered = (0..settings.prefilter_mip_count())
+            .map(|mip| read_cube(&mut input, settings.prefilter_mip_size(mip)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let brdf_lut_size = read_u32(&mut input)?;
+        if brdf_lut_size != settings.brdf_lut_size {
+            return Err(IblError::InvalidCache(format!("BRDF LUT of {} texels, {} expected", brdf_lut_size, settings.brdf_lut_size)));
+        }
+        let texels = brdf_lut_size as usize * brdf_lut_size as usize;
+        let mut brdf_lut = Vec::with_capacity(texels);
+        for _ in 0..texels {
+            let mut texel = [0.0; 2];
+            read_f32s(&mut input, &mut texel)?;
+            brdf_lut.push(texel);
+        }
+        Ok(Self {
+            irradiance,
+            prefiltered,
+            brdf_lut_size,
+            brdf_lut,
+        })
+    }
+}
+
+fn compute_irradiance(chain: &CubeChain, size: u32) -> CubeData {
+    // convolving a small version of the environment is enough for such a low frequency signal
+    let lod = (chain.0[0].size as f32 / 16.0).log2().max(0.0);
+    let sample_delta = 0.05;
+    CubeData::from_fn(size, |normal| {
+        let (tangent, bitangent) = tangent_frame(normal);
+        let mut irradiance = Vec3::zeros();
+        let mut samples = 0.0;
+        let mut phi = 0.0;
+        while phi < 2.0 * PI {
+            let mut theta = 0.0;
+            while theta < 0.5 * PI {
+                let local = vec3(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos());
+                let dir = tangent * local.x + bitangent * local.y + normal * local.z;
+                irradiance += chain.sample_lod(&dir, lod) * theta.cos() * theta.sin();
+                samples += 1.0;
+                theta += sample_delta;
+            }
+            phi += sample_delta;
+        }
+        let irradiance = irradiance * PI / samples;
+        [irradiance.x, irradiance.y, irradiance.z]
+    })
+}
+
+fn compute_prefiltered(chain: &CubeChain, settings: &IblSettings) -> Vec<CubeData> {
+    let source_size = chain.0[0].size as f32;
+    let texel_solid_angle = 4.0 * PI / (6.0 * source_size * source_size);
+    let mips = settings.prefilter_mip_count();
+    (0..mips)
+        .map(|mip| {
+            let size = settings.prefilter_mip_size(mip);
+            let roughness = mip as f32 / (mips - 1).max(1) as f32;
+            CubeData::from_fn(size, |normal| {
+                if roughness == 0.0 {
+                    let c = chain.sample_lod(normal, 0.0);
+                    return [c.x, c.y, c.z];
+                }
+                let mut color = Vec3::zeros();
+                let mut total_weight = 0.0;
+                for i in 0..settings.prefilter_samples {
+                    let h = importance_sample_ggx(hammersley(i, settings.prefilter_samples), normal, roughness);
+                    let l = (h * 2.0 * normal.dot(&h) - normal).normalize();
+                    let n_dot_l = normal.dot(&l);
+                    if n_dot_l > 0.0 {
+                        // pick the source level matching the solid angle covered by the sample
+                        let n_dot_h = normal.dot(&h).max(0.0);
+                        let pdf = distribution_ggx(n_dot_h, roughness) * 0.25 + 0.0001;
+                        let sample_solid_angle = 1.0 / (settings.prefilter_samples as f32 * pdf);
+                        let lod = 0.5 * (sample_solid_angle / texel_solid_angle).log2();
+                        color += chain.sample_lod(&l, lod) * n_dot_l;
+                        total_weight += n_dot_l;
+                    }
+                }
+                let color = color / total_weight.max(0.0001);
+                [color.x, color.y, color.z]
+            })
+        })
+        .collect()
+}
+
+fn compute_brdf_lut(size: u32, samples: u32) -> Vec<[f32; 2]> {
+    let normal = vec3(0.0, 0.0, 1.0);
+    let mut lut = Vec::with_capacity((size * size) as usize);
+    for y in 0..size {
+        let roughness = (y as f32 + 0.5) / size as f32;
+        for x in 0..size {
+            let n_dot_v = (x as f32 + 0.5) / size as f32;
+            let v = vec3((1.0 - n_dot_v * n_dot_v).sqrt(), 0.0, n_dot_v);
+            let (mut scale, mut bias) = (0.0, 0.0);
+            for i in 0..samples {
+                let h = importance_sample_ggx(hammersley(i, samples), &normal, roughness);
+                let l = (h * 2.0 * v.dot(&h) - v).normalize();
+                let n_dot_l = l.z.max(0.0);
+                let n_dot_h = h.z.max(0.0);
+                let v_dot_h = v.dot(&h).max(0.0);
+                if n_dot_l > 0.0 {
+                    let g = geometry_smith_ibl(n_dot_v, n_dot_l, roughness);
+                    let g_vis = (g * v_dot_h) / (n_dot_h * n_dot_v);
+                    let fc = (1.0 - v_dot_h).powi(5);
+                    scale += (1.0 - fc) * g_vis;
+                    bias += fc * g_vis;
+                }
+            }
+            lut.push([scale / samples as f32, bias / samples as f32]);
+        }
+    }
+    lut
+}
+
+fn write_u32<W: Write>(out: &mut W, value: u32) -> std::io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+fn read_u32<R: Read>(input: &mut R) -> std::io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn write_f32s<W: Write>(out: &mut W, values: &[f32]) -> std::io::Result<()> {
+    for value in values {
+        out.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_f32s<R: Read>(input: &mut R, values: &mut [f32]) -> std::io::Result<()> {
+    let mut bytes = [0u8; 4];
+    for value in values.iter_mut() {
+        input.read_exact(&mut bytes)?;
+        *value = f32::from_le_bytes(bytes);
+    }
+    Ok(())
+}
+
+fn write_cube<W: Write>(out: &mut W, cube: &CubeData) -> std::io::Result<()> {
+    write_u32(out, cube.size)?;
+    for face in cube.faces.iter() {
+        for texel in face.iter() {
+            write_f32s(out, texel)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_cube<R: Read>(input: &mut R, expected_size: u32) -> Result<CubeData, IblError> {
+    let size = read_u32(input)?;
+    if size != expected_size {
+        return Err(IblError::InvalidCache(format!("cube face of {} texels, {} expected", size, expected_size)));
+    }
+    let texels = size as usize * size as usize;
+    let mut faces = vec![];
+    for _ in 0..6 {
+        let mut face = Vec::with_capacity(texels);
+        for _ in 0..texels {
+            let mut texel = [0.0; 3];
+            read_f32s(input, &mut texel)?;
+            face.push(texel);
+        }
+        faces.push(face);
+    }
+    Ok(CubeData { size, faces })
+}
+
+/// Image based lighting textures uploaded to the GPU, bound to the `ibl` struct of `pbr.fs.glsl`.
+pub struct Ibl {
+    pub irradiance: Cubemap,
+    pub prefiltered: Cubemap,
+    pub brdf_lut: Texture2d,
+    prefiltered_mips: u32,
+}
+
+impl Ibl {
+    pub fn new(display: &Display, maps: &IblMaps) -> Self {
+        let irradiance = upload_cubemap(display, std::slice::from_ref(&maps.irradiance));
+        let prefiltered = upload_cubemap(display, &maps.prefiltered);
+        let lut_data = maps.brdf_lut.iter().flat_map(|texel| texel.iter().copied()).collect::<Vec<_>>();
+        let lut_image = RawImage2d {
+            data: Cow::Owned(lut_data),
+            width: maps.brdf_lut_size,
+            height: maps.brdf_lut_size,
+            format: ClientFormat::F32F32,
+        };
+        let brdf_lut = Texture2d::with_format(display, lut_image, UncompressedFloatFormat::F16F16, MipmapsOption::NoMipmap).unwrap();
+        Self {
+            prefiltered_mips: prefiltered.get_mipmap_levels(),
+            irradiance,
+            prefiltered,
+            brdf_lut,
+        }
+    }
+
+    /// Black 1x1 maps, bound in place of a missing environment so the samplers of the `ibl`
+    /// struct never fall back to the texture unit of another sampler type.
+    pub fn placeholder(display: &Display) -> Self {
+        let black = CubeData {
+            size: 1,
+            faces: vec![vec![[0.0; 3]]; 6],
+        };
+        Self::new(display, &IblMaps {
+            irradiance: black.clone(),
+            prefiltered: vec![black],
+            brdf_lut_size: 1,
+            brdf_lut: vec![[0.0; 2]],
+        })
+    }
+
+    pub fn load_or_compute<P: AsRef<Path>>(display: &Display, source: &EnvironmentSource, settings: &IblSettings, cache_path: P) -> Result<Self, IblError> {
+        let maps = IblMaps::load_or_compute(source, settings, cache_path)?;
+        Ok(Self::new(display, &maps))
+    }
+}
+
+// each level of `levels` is uploaded into the matching mipmap of the cubemap, levels past the
+// smallest mipmap being ignored
+fn upload_cubemap(display: &Display, levels: &[CubeData]) -> Cubemap {
+    let size = levels[0].size;
+    let mipmaps = if levels.len() > 1 {
+        MipmapsOption::EmptyMipmapsMax(levels.len() as u32 - 1)
+    } else {
+        MipmapsOption::NoMipmap
+    };
+    let cubemap = Cubemap::empty_with_format(display, UncompressedFloatFormat::F16F16F16, mipmaps, size).unwrap();
+    for (level, cube) in levels.iter().enumerate() {
+        let mipmap = match cubemap.mipmap(level as u32) {
+            Some(mipmap) => mipmap,
+            None => break,
+        };
+        for (face, layer) in CUBE_LAYERS.iter().enumerate() {
+            let texture = Texture2d::with_format(display, cube.to_raw(face), UncompressedFloatFormat::F16F16F16, MipmapsOption::NoMipmap).unwrap();
+            let target = SimpleFrameBuffer::new(display, mipmap.image(*layer)).unwrap();
+            let rect = BlitTarget {
+                left: 0,
+                bottom: 0,
+                width: cube.size as i32,
+                height: cube.size as i32,
+            };
+            texture.as_surface().blit_whole_color_to(&target, &rect, MagnifySamplerFilter::Nearest);
+        }
+    }
+    cubemap
+}
+
+impl StructToUniform for Ibl {
+    fn as_uniform<'a>(&'a self, struct_name: &str, storage: &mut UniformStorage<'a>) {
+        let clamped = SamplerBehavior {
+            wrap_function: (SamplerWrapFunction::Clamp, SamplerWrapFunction::Clamp, SamplerWrapFunction::Clamp),
+            minify_filter: MinifySamplerFilter::Linear,
+            magnify_filter: MagnifySamplerFilter::Linear,
+            ..Default::default()
+        };
+        let trilinear = SamplerBehavior {
+            minify_filter: MinifySamplerFilter::LinearMipmapLinear,
+            ..clamped
+        };
+        storage.add(&*format!("{}.irradiance", struct_name), UniformValue::Cubemap(&self.irradiance, Some(clamped)));
+        storage.add(&*format!("{}.prefiltered", struct_name), UniformValue::Cubemap(&self.prefiltered, Some(trilinear)));
+        storage.add(&*format!("{}.brdfLut", struct_name), UniformValue::Texture2d(&self.brdf_lut, Some(clamped)));
+        storage.add(&*format!("{}.maxLod", struct_name), UniformValue::Float((self.prefiltered_mips - 1) as f32));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> IblSettings {
+        IblSettings {
+            environment_size: 4,
+            irradiance_size: 2,
+            prefilter_size: 4,
+            // more than the 3 mips of a 4 texel cube
+            prefilter_mips: 5,
+            prefilter_samples: 4,
+            brdf_lut_size: 2,
+            brdf_samples: 4,
+        }
+    }
+
+    fn maps(settings: &IblSettings) -> IblMaps {
+        let cube = |size: u32| CubeData { size, faces: vec![vec![[0.5; 3]; (size * size) as usize]; 6] };
+        IblMaps {
+            irradiance: cube(settings.irradiance_size),
+            prefiltered: (0..settings.prefilter_mip_count()).map(|mip| cube(settings.prefilter_mip_size(mip))).collect(),
+            brdf_lut_size: settings.brdf_lut_size,
+            brdf_lut: vec![[0.25, 0.75]; (settings.brdf_lut_size * settings.brdf_lut_size) as usize],
+        }
+    }
+
+    fn cache_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("rust_opengl_ibl_{}_{}.ibl", name, std::process::id()))
+    }
+
+    #[test]
+    fn mip_count_is_clamped_to_the_chain() {
+        assert_eq!(settings().prefilter_mip_count(), 3);
+        assert_eq!(IblSettings { prefilter_mips: 0, ..settings() }.prefilter_mip_count(), 1);
+        assert_eq!(IblSettings::default().prefilter_mip_count(), 5);
+    }
+
+    #[test]
+    fn cache_round_trips() {
+        let settings = settings();
+        let path = cache_path("round_trip");
+        maps(&settings).save(&path, "key").unwrap();
+        let loaded = IblMaps::load(&path, "key", &settings).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.prefiltered.len(), 3);
+        assert_eq!(loaded.prefiltered[2].size, 1);
+        assert_eq!(loaded.irradiance.faces[5][3], [0.5; 3]);
+        assert_eq!(loaded.brdf_lut, vec![[0.25, 0.75]; 4]);
+    }
+
+    #[test]
+    fn mismatching_caches_are_invalid() {
+        let settings = settings();
+        let path = cache_path("mismatch");
+        maps(&settings).save(&path, "key").unwrap();
+        let other_key = IblMaps::load(&path, "kez", &settings);
+        let other_settings = IblMaps::load(&path, "key", &IblSettings { irradiance_size: 3, ..settings });
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(other_key, Err(IblError::InvalidCache(_))));
+        assert!(matches!(other_settings, Err(IblError::InvalidCache(_))));
+    }
+
+    #[test]
+    fn corrupted_sizes_are_invalid() {
+        let settings = settings();
+        let path = cache_path("corrupted");
+        maps(&settings).save(&path, "key").unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        let valid = bytes.clone();
+        // huge key length
+        bytes[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        let huge_key = IblMaps::load(&path, "key", &settings);
+        // irradiance size whose square overflows a u32
+        let mut bytes = valid;
+        bytes[11..15].copy_from_slice(&0x1_0000u32.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        let huge_cube = IblMaps::load(&path, "key", &settings);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(huge_key, Err(IblError::InvalidCache(_))));
+        assert!(matches!(huge_cube, Err(IblError::InvalidCache(_))));
+    }
+
+    #[test]
+    fn cache_key_follows_settings_and_file() {
+        let path = cache_path("source");
+        std::fs::write(&path, b"first").unwrap();
+        let source = EnvironmentSource::Equirectangular(path.to_string_lossy().into_owned());
+        let key = source.cache_key(&settings());
+        assert_ne!(key, source.cache_key(&IblSettings { prefilter_samples: 8, ..settings() }));
+        std::fs::write(&path, b"edited file").unwrap();
+        let edited = source.cache_key(&settings());
+        std::fs::remove_file(&path).unwrap();
+        assert_ne!(key, edited);
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+
+use glium::Display;
+use glium::vertex::{BufferCreationError, PerInstance, VertexBuffer};
+use math::glm;
+
+/// Attributes of one copy of an instanced draw, read by shaders compiled with `INSTANCED`.
+#[derive(Copy, Clone, Debug)]
+pub struct Instance {
+    instance_model: [[f32; 4]; 4],
+    // inverse transpose of the model, computed once here rather than per vertex
+    instance_normal: [[f32; 3]; 3],
+    instance_tint: [f32; 4],
+}
+
+impl Instance {
+    pub fn new(model: &glm::Mat4) -> Self {
+        let normal = glm::transpose(&glm::inverse(&glm::mat4_to_mat3(model)));
+        Self {
+            instance_model: (*model).into(),
+            instance_normal: normal.into(),
+            instance_tint: [1.0; 4],
+        }
+    }
+
+    pub fn with_tint(mut self, tint: [f32; 4]) -> Self {
+        self.instance_tint = tint;
+        self
+    }
+
+    pub fn position(&self) -> glm::Vec3 {
+        let [x, y, z, _] = self.instance_model[3];
+        glm::vec3(x, y, z)
+    }
+}
+
+glium::implement_vertex!(Instance, instance_model, instance_normal, instance_tint);
+
+#[derive(Debug)]
+pub enum InstancingError {
+    Buffer(BufferCreationError),
+    NotSupported,
+}
+
+impl fmt::Display for InstancingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstancingError::Buffer(err) => write!(f, "cannot create the instance buffer: {}", err),
+            InstancingError::NotSupported => write!(f, "instancing is not supported by the context"),
+        }
+    }
+}
+
+impl std::error::Error for InstancingError {}
+
+/// Dynamic vertex buffer of `Instance`s, drawn alongside the vertexes of a mesh through
+/// `DrawItem::with_instances`. Updates with the same number of instances rewrite the buffer in
+/// place, other counts reallocate it.
+pub struct InstanceBuffer {
+    // `None` without instances, glium refusing empty buffers
+    buffer: Option<VertexBuffer<Instance>>,
+}
+
+impl InstanceBuffer {
+    pub fn new(display: &Display, instances: &[Instance]) -> Result<Self, InstancingError> {
+        let mut buffer = Self { buffer: None };
+        buffer.update(display, instances)?;
+        Ok(buffer)
+    }
+
+    pub fn update(&mut self, display: &Display, instances: &[Instance]) -> Result<(), InstancingError> {
+        match &self.buffer {
+            _ if instances.is_empty() => self.buffer = None,
+            Some(buffer) if buffer.len() == instances.len() => buffer.write(instances),
+            _ => {
+                let buffer = VertexBuffer::dynamic(display, instances).map_err(InstancingError::Buffer)?;
+                buffer.per_instance().map_err(|_| InstancingError::NotSupported)?;
+                self.buffer = Some(buffer);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.as_ref().map_or(0, |buffer| buffer.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_none()
+    }
+
+    /// Instances to pass to `DrawItem::with_instances`, `None` when empty.
+    pub fn per_instance(&self) -> Option<PerInstance<'_>> {
+        // support was checked when the buffer was created
+        self.buffer.as_ref().and_then(|buffer| buffer.per_instance().ok())
+    }
+}
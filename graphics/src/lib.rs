@@ -4,6 +4,27 @@ mod texture;
 mod material;
 mod light;
 mod math_data;
+mod antialiasing;
+mod tangent;
+mod pbr;
+mod ibl;
+mod material_library;
+mod material_file;
+mod render_state;
+mod depth_mode;
+mod renderer;
+mod debug_view;
+mod debug_draw;
+mod font;
+mod text;
+mod sprite;
+mod mesh;
+mod lod;
+mod instancing;
+mod obj;
+mod ply;
+mod stl;
+mod gltf_scene;
 pub mod uniform;
 
 pub use colors::Colors;
@@ -13,6 +34,27 @@ pub use texture::*;
 pub use material::*;
 pub use light::*;
 pub use math_data::*;
+pub use antialiasing::*;
+pub use tangent::*;
+pub use pbr::*;
+pub use ibl::*;
+pub use material_library::*;
+pub use material_file::*;
+pub use render_state::*;
+pub use depth_mode::*;
+pub use renderer::*;
+pub use debug_view::*;
+pub use debug_draw::*;
+pub use font::*;
+pub use text::*;
+pub use sprite::*;
+pub use mesh::*;
+pub use lod::*;
+pub use instancing::*;
+pub use obj::*;
+pub use ply::*;
+pub use stl::*;
+pub use gltf_scene::*;
 
 use glium::DrawParameters;
 use crate::vertex::VertexNorm;
@@ -30,16 +72,20 @@ pub fn load_glsl(path: &str) -> String {
     nice_shader
 }
 
-pub fn draw_params() -> DrawParameters<'static> {
-    use glium::{Depth, DepthTest, BackfaceCullingMode};
-    DrawParameters {
-        depth: Depth {
-            test: DepthTest::IfLess,
-            write: true,
-            ..Depth::default()
-        },
-        backface_culling: BackfaceCullingMode::CullClockwise,
-        ..DrawParameters::default()
-    }
+/// Variant of a shader source with `#define` lines inserted after its `#version` directive.
+pub fn shader_variant(source: &str, defines: &[&str]) -> String {
+    let defines: String = defines.iter().map(|define| format!("#define {}\n", define)).collect();
+    let split = if source.trim_start().starts_with("#version") {
+        source.find('\n').map_or(source.len(), |end| end + 1)
+    } else {
+        0
+    };
+    let (version, body) = source.split_at(split);
+    let newline = if version.is_empty() || version.ends_with('\n') { "" } else { "\n" };
+    format!("{}{}{}{}", version, newline, defines, body)
+}
+
+pub fn draw_params(depth_mode: DepthMode) -> DrawParameters<'static> {
+    RenderState::default().to_draw_parameters_with(depth_mode)
 }
 
@@ -28,6 +28,15 @@ impl PointLight {
             quadratic
         }
     }
+
+    /// Distance where the attenuation falls under `threshold`, e.g. `1. / 256.` for 8 bit colors.
+    pub fn attenuation_radius(&self, threshold: f32) -> f32 {
+        let (c, l, q) = (self.constant - 1. / threshold, self.linear, self.quadratic);
+        if q.abs() <= f32::EPSILON {
+            return -c / l;
+        }
+        (-l + (l * l - 4. * q * c).sqrt()) / (2. * q)
+    }
 }
 
 impl StructToUniform for PointLight {
@@ -0,0 +1,42 @@
+/// Levels of detail of one object, from the most detailed to the coarsest, switched on the
+/// object's screen size as a fraction of the viewport height.
+#[derive(Debug, Clone)]
+pub struct LodChain<T> {
+    // each level with the screen size under which it replaces the previous one
+    levels: Vec<(T, f32)>,
+}
+
+impl<T> LodChain<T> {
+    pub fn new(item: T) -> Self {
+        Self { levels: vec![(item, f32::INFINITY)] }
+    }
+
+    /// Adds a coarser level drawn once the screen size falls under `screen_size`. Levels are
+    /// expected by decreasing screen size.
+    pub fn with_level(mut self, item: T, screen_size: f32) -> Self {
+        self.levels.push((item, screen_size));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    pub fn level(&self, index: usize) -> Option<&T> {
+        self.levels.get(index).map(|(item, _)| item)
+    }
+
+    pub fn select_index(&self, screen_size: f32) -> usize {
+        self.levels.iter()
+            .rposition(|(_, below)| screen_size < *below)
+            .unwrap_or(0)
+    }
+
+    pub fn select(&self, screen_size: f32) -> &T {
+        &self.levels[self.select_index(screen_size)].0
+    }
+}
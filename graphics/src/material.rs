@@ -1,28 +1,139 @@
 use crate::GVec3;
+use crate::render_state::RenderState;
 use glium::implement_uniform_block;
 use crate::uniform::{StructToUniform, UniformStorage};
-use glium::uniforms::UniformValue;
+use glium::uniforms::{AsUniformValue, UniformValue};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ParallaxMode {
+    // single offset along the view direction
+    Simple,
+    // ray marching through depth layers, stops at the first layer under the surface
+    Steep,
+    // steep parallax plus interpolation between the last two layers
+    Occlusion,
+}
+
+impl ParallaxMode {
+    pub(crate) fn as_shader_id(&self) -> i32 {
+        match self {
+            ParallaxMode::Simple => 0,
+            ParallaxMode::Steep => 1,
+            ParallaxMode::Occlusion => 2,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ParallaxSettings {
+    pub mode: ParallaxMode,
+    pub height_scale: f32,
+    // layer count is interpolated between min and max depending on the view angle
+    pub min_layers: f32,
+    pub max_layers: f32,
+}
+
+impl Default for ParallaxSettings {
+    fn default() -> Self {
+        Self {
+            mode: ParallaxMode::Occlusion,
+            height_scale: 0.05,
+            min_layers: 8.,
+            max_layers: 32.,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Material {
     pub diffuse: glium::texture::Texture2d,
     pub specular: glium::texture::Texture2d,
+    pub normal: Option<glium::texture::Texture2d>,
+    pub height: Option<glium::texture::Texture2d>,
+    pub parallax: ParallaxSettings,
     pub shininess: f32,
+    pub render_state: RenderState,
 }
 impl Material {
     pub fn new(diffuse:  glium::texture::Texture2d, specular:  glium::texture::Texture2d, shininess: f32) -> Self {
         Self {
             diffuse,
             specular,
+            normal: None,
+            height: None,
+            parallax: ParallaxSettings::default(),
             shininess: shininess * 128.,
+            render_state: RenderState::default(),
         }
     }
+
+    pub fn with_render_state(mut self, render_state: RenderState) -> Self {
+        self.render_state = render_state;
+        self
+    }
+
+    pub fn with_normal_map(mut self, normal: glium::texture::Texture2d) -> Self {
+        self.normal = Some(normal);
+        self
+    }
+
+    // height map is white for the highest points of the surface
+    pub fn with_height_map(mut self, height: glium::texture::Texture2d, parallax: ParallaxSettings) -> Self {
+        self.height = Some(height);
+        self.parallax = parallax;
+        self
+    }
 }
 impl StructToUniform for Material{
     fn as_uniform<'a>(&'a self, struct_name: &str, storage: &mut UniformStorage<'a>) {
         storage.add(&*format!("{}.diffuse", struct_name), UniformValue::Texture2d(&self.diffuse, None));
         storage.add(&*format!("{}.specular", struct_name), UniformValue::Texture2d(&self.specular, None));
         storage.add(&*format!("{}.shininess", struct_name), UniformValue::Float(self.shininess));
+        storage.add(&*format!("{}.hasNormalMap", struct_name), UniformValue::Bool(self.normal.is_some()));
+        if let Some(normal) = &self.normal {
+            storage.add(&*format!("{}.normal", struct_name), UniformValue::Texture2d(normal, None));
+        }
+        storage.add(&*format!("{}.hasHeightMap", struct_name), UniformValue::Bool(self.height.is_some()));
+        if let Some(height) = &self.height {
+            storage.add(&*format!("{}.height", struct_name), UniformValue::Texture2d(height, None));
+            storage.add(&*format!("{}.parallaxMode", struct_name), UniformValue::SignedInt(self.parallax.mode.as_shader_id()));
+            storage.add(&*format!("{}.heightScale", struct_name), UniformValue::Float(self.parallax.height_scale));
+            storage.add(&*format!("{}.minLayers", struct_name), UniformValue::Float(self.parallax.min_layers));
+            storage.add(&*format!("{}.maxLayers", struct_name), UniformValue::Float(self.parallax.max_layers));
+        }
     }
 }
 
+/// Color only Blinn-Phong material, see `resources/material_info_datas.csv` for classic values.
+#[derive(Debug, Clone, Copy)]
+pub struct PhongMaterial {
+    pub ambient: GVec3,
+    pub diffuse: GVec3,
+    pub specular: GVec3,
+    pub shininess: f32,
+    pub render_state: RenderState,
+}
+impl PhongMaterial {
+    pub fn new(ambient: GVec3, diffuse: GVec3, specular: GVec3, shininess: f32) -> Self {
+        Self {
+            ambient,
+            diffuse,
+            specular,
+            shininess: shininess * 128.,
+            render_state: RenderState::default(),
+        }
+    }
+
+    pub fn with_render_state(mut self, render_state: RenderState) -> Self {
+        self.render_state = render_state;
+        self
+    }
+}
+impl StructToUniform for PhongMaterial {
+    fn as_uniform<'a>(&'a self, struct_name: &str, storage: &mut UniformStorage<'a>) {
+        storage.add(&*format!("{}.ambient", struct_name), self.ambient.as_uniform_value());
+        storage.add(&*format!("{}.diffuse", struct_name), self.diffuse.as_uniform_value());
+        storage.add(&*format!("{}.specular", struct_name), self.specular.as_uniform_value());
+        storage.add(&*format!("{}.shininess", struct_name), UniformValue::Float(self.shininess));
+    }
+}
@@ -0,0 +1,261 @@
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use glium::{Display, Program};
+use glium::texture::Texture2d;
+use glium::uniforms::{UniformType, UniformValue};
+use serde::Deserialize;
+
+use crate::{load_texture_file, shader_variant};
+use crate::render_state::RenderState;
+use crate::uniform::UniformStorage;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShaderDescription {
+    pub vertex: String,
+    pub fragment: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum ParameterValue {
+    Float(f32),
+    Int(i32),
+    Bool(bool),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+}
+
+impl ParameterValue {
+    fn as_uniform_value(&self) -> UniformValue<'static> {
+        match *self {
+            ParameterValue::Float(v) => UniformValue::Float(v),
+            ParameterValue::Int(v) => UniformValue::SignedInt(v),
+            ParameterValue::Bool(v) => UniformValue::Bool(v),
+            ParameterValue::Vec2(v) => UniformValue::Vec2(v),
+            ParameterValue::Vec3(v) => UniformValue::Vec3(v),
+            ParameterValue::Vec4(v) => UniformValue::Vec4(v),
+        }
+    }
+
+    fn matches(&self, ty: UniformType) -> bool {
+        matches!(
+            (self, ty),
+            (ParameterValue::Float(_), UniformType::Float)
+                | (ParameterValue::Int(_), UniformType::Int)
+                | (ParameterValue::Bool(_), UniformType::Bool)
+                | (ParameterValue::Vec2(_), UniformType::FloatVec2)
+                | (ParameterValue::Vec3(_), UniformType::FloatVec3)
+                | (ParameterValue::Vec4(_), UniformType::FloatVec4)
+        )
+    }
+}
+
+/// Content of a `.ron` or `.toml` material file. Texture and parameter keys are the full
+/// uniform names (`material.diffuse`), paths are relative to the material file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MaterialDescription {
+    pub name: String,
+    pub shader: ShaderDescription,
+    #[serde(default)]
+    pub textures: BTreeMap<String, String>,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, ParameterValue>,
+    #[serde(default)]
+    pub render_state: RenderState,
+}
+
+impl MaterialDescription {
+    pub fn from_ron(source: &str) -> Result<Self, MaterialFileError> {
+        ron::de::from_str(source).map_err(|err| MaterialFileError::Parse(err.to_string()))
+    }
+
+    pub fn from_toml(source: &str) -> Result<Self, MaterialFileError> {
+        toml::from_str(source).map_err(|err| MaterialFileError::Parse(err.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub enum MaterialFileError {
+    Io(PathBuf, std::io::Error),
+    UnsupportedFormat(PathBuf),
+    Parse(String),
+    Texture(PathBuf, image::ImageError),
+    Shader(String),
+    // a texture or parameter does not match any active uniform of the shader
+    Validation(String),
+}
+
+impl fmt::Display for MaterialFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaterialFileError::Io(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            MaterialFileError::UnsupportedFormat(path) => write!(f, "{} is neither a .ron nor a .toml file", path.display()),
+            MaterialFileError::Parse(err) => write!(f, "invalid material description: {}", err),
+            MaterialFileError::Texture(path, err) => write!(f, "cannot load texture {}: {}", path.display(), err),
+            MaterialFileError::Shader(err) => write!(f, "cannot compile shader: {}", err),
+            MaterialFileError::Validation(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for MaterialFileError {}
+
+/// Material built from a `MaterialDescription`, owning its program and textures.
+pub struct DataMaterial {
+    pub name: String,
+    pub program: Program,
+    pub textures: Vec<(String, Texture2d)>,
+    pub parameters: Vec<(String, ParameterValue)>,
+    pub render_state: RenderState,
+    vertex_source: String,
+    fragment_source: String,
+    // compiled by the first call to `instanced_program`
+    instanced_program: OnceCell<Program>,
+}
+
+impl DataMaterial {
+    pub fn new<P: AsRef<Path>>(display: &Display, description: MaterialDescription, base_dir: P) -> Result<Self, MaterialFileError> {
+        let base_dir = base_dir.as_ref();
+        let vertex = read_source(&base_dir.join(&description.shader.vertex))?;
+        let fragment = read_source(&base_dir.join(&description.shader.fragment))?;
+        let program = Program::from_source(display, &vertex, &fragment, None)
+            .map_err(|err| MaterialFileError::Shader(format!("{:?}", err)))?;
+        validate(&description, &program)?;
+
+        let textures = description.textures.iter()
+            .map(|(uniform, path)| {
+                let path = base_dir.join(path);
+                load_texture_file(&path, display)
+                    .map(|texture| (uniform.clone(), texture))
+                    .map_err(|err| MaterialFileError::Texture(path, err))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            name: description.name,
+            program,
+            textures,
+            parameters: description.parameters.into_iter().collect(),
+            render_state: description.render_state,
+            vertex_source: vertex,
+            fragment_source: fragment,
+            instanced_program: OnceCell::new(),
+        })
+    }
+
+    /// Program of the material compiled with `INSTANCED` defined, for draws taking the model
+    /// from an `InstanceBuffer`. It is compiled once, later calls return the same program.
+    pub fn instanced_program(&self, display: &Display) -> Result<&Program, MaterialFileError> {
+        if let Some(program) = self.instanced_program.get() {
+            return Ok(program);
+        }
+        let vertex = shader_variant(&self.vertex_source, &["INSTANCED"]);
+        let program = Program::from_source(display, &vertex, &self.fragment_source, None)
+            .map_err(|err| MaterialFileError::Shader(format!("{:?}", err)))?;
+        if program.get_attribute("instance_model").is_none() {
+            return Err(MaterialFileError::Validation(format!(
+                "material '{}': vertex shader has no instanced variant", self.name)));
+        }
+        Ok(self.instanced_program.get_or_init(|| program))
+    }
+
+    pub fn add_uniforms<'a>(&'a self, storage: &mut UniformStorage<'a>) {
+        for (uniform, texture) in self.textures.iter() {
+            storage.add(uniform, UniformValue::Texture2d(texture, None));
+        }
+        for (uniform, value) in self.parameters.iter() {
+            storage.add(uniform, value.as_uniform_value());
+        }
+    }
+}
+
+fn read_source(path: &Path) -> Result<String, MaterialFileError> {
+    let mut source = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut source))
+        .map_err(|err| MaterialFileError::Io(path.to_path_buf(), err))?;
+    Ok(source)
+}
+
+fn validate(description: &MaterialDescription, program: &Program) -> Result<(), MaterialFileError> {
+    for uniform in description.textures.keys() {
+        match program.get_uniform(uniform) {
+            Some(u) if u.ty == UniformType::Sampler2d => {}
+            Some(u) => return Err(MaterialFileError::Validation(format!(
+                "material '{}': texture '{}' is bound to a {:?} uniform", description.name, uniform, u.ty))),
+            None => return Err(MaterialFileError::Validation(format!(
+                "material '{}': shader has no active uniform '{}'", description.name, uniform))),
+        }
+    }
+    for (uniform, value) in description.parameters.iter() {
+        match program.get_uniform(uniform) {
+            Some(u) if value.matches(u.ty) => {}
+            Some(u) => return Err(MaterialFileError::Validation(format!(
+                "material '{}': parameter '{}' is a {:?} but the shader expects {:?}", description.name, uniform, value, u.ty))),
+            None => return Err(MaterialFileError::Validation(format!(
+                "material '{}': shader has no active uniform '{}'", description.name, uniform))),
+        }
+    }
+    Ok(())
+}
+
+pub fn load_material_file<P: AsRef<Path>>(display: &Display, path: P) -> Result<DataMaterial, MaterialFileError> {
+    let path = path.as_ref();
+    let source = read_source(path)?;
+    let description = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("ron") => MaterialDescription::from_ron(&source)?,
+        Some("toml") => MaterialDescription::from_toml(&source)?,
+        _ => return Err(MaterialFileError::UnsupportedFormat(path.to_path_buf())),
+    };
+    DataMaterial::new(display, description, path.parent().unwrap_or_else(|| Path::new(".")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_state::{BlendMode, CullMode, DepthFunction, DepthState};
+
+    #[test]
+    fn parses_the_toml_material() {
+        let description = MaterialDescription::from_toml(include_str!("../../resources/materials/bricks.toml")).unwrap();
+        assert_eq!(description.name, "bricks");
+        assert_eq!(description.shader.vertex, "../shaders/sample_tex.vs.glsl");
+        assert_eq!(description.shader.fragment, "../shaders/sample_tex.fs.glsl");
+        assert_eq!(description.textures.get("tex").map(String::as_str), Some("../textures/bricks.png"));
+        assert!(description.parameters.is_empty());
+        let expected = RenderState {
+            blend: BlendMode::Opaque,
+            cull: CullMode::None,
+            depth: DepthState { test: DepthFunction::LessOrEqual, write: true },
+            ..RenderState::default()
+        };
+        assert_eq!(description.render_state, expected);
+    }
+
+    #[test]
+    fn parses_the_ron_material() {
+        let description = MaterialDescription::from_ron(include_str!("../../resources/materials/crate.ron")).unwrap();
+        assert_eq!(description.name, "crate");
+        assert_eq!(description.textures.len(), 2);
+        assert_eq!(description.parameters.get("material.shininess"), Some(&ParameterValue::Float(76.8)));
+        assert_eq!(description.parameters.get("material.hasNormalMap"), Some(&ParameterValue::Bool(false)));
+        assert_eq!(description.render_state.cull, CullMode::Clockwise);
+    }
+
+    #[test]
+    fn rejects_invalid_descriptions() {
+        assert!(matches!(MaterialDescription::from_toml("name = \"missing shader\""), Err(MaterialFileError::Parse(_))));
+        assert!(matches!(MaterialDescription::from_ron("(name: \"crate\")"), Err(MaterialFileError::Parse(_))));
+    }
+
+    #[test]
+    fn parameters_match_their_uniform_type() {
+        assert!(ParameterValue::Float(1.0).matches(UniformType::Float));
+        assert!(ParameterValue::Vec3([0.0; 3]).matches(UniformType::FloatVec3));
+        assert!(!ParameterValue::Int(1).matches(UniformType::Float));
+    }
+}
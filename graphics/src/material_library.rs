@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::GVec3;
+use crate::material::PhongMaterial;
+
+#[derive(Debug)]
+pub enum MaterialParseError {
+    Io(std::io::Error),
+    // line numbers start at 1, the header being the first line
+    Syntax { line: usize, message: String },
+}
+
+impl fmt::Display for MaterialParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaterialParseError::Io(err) => write!(f, "cannot read material table: {}", err),
+            MaterialParseError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for MaterialParseError {}
+
+impl From<std::io::Error> for MaterialParseError {
+    fn from(err: std::io::Error) -> Self {
+        MaterialParseError::Io(err)
+    }
+}
+
+/// Named `PhongMaterial`s parsed from a material table, with columns separated by `;`
+/// and color components by `,`:
+/// `Name;Ambient;Diffuse;Specular;Shininess`.
+#[derive(Debug, Default)]
+pub struct MaterialLibrary {
+    materials: HashMap<String, PhongMaterial>,
+    // keeps the order of the file for listings
+    names: Vec<String>,
+}
+
+impl MaterialLibrary {
+    pub fn parse(source: &str) -> Result<Self, MaterialParseError> {
+        let mut library = Self::default();
+        let mut lines = source.lines().enumerate().map(|(i, line)| (i + 1, line.trim()));
+        match lines.next() {
+            Some((_, header)) if header.eq_ignore_ascii_case("Name;Ambient;Diffuse;Specular;Shininess") => {}
+            Some((line, _)) => return Err(syntax(line, "expected header 'Name;Ambient;Diffuse;Specular;Shininess'")),
+            None => return Err(syntax(1, "empty material table")),
+        }
+        for (line, content) in lines {
+            if content.is_empty() {
+                continue;
+            }
+            let columns = content.split(';').map(str::trim).collect::<Vec<_>>();
+            if columns.len() != 5 {
+                return Err(syntax(line, &format!("expected 5 columns, found {}", columns.len())));
+            }
+            let name = columns[0];
+            if name.is_empty() {
+                return Err(syntax(line, "material name is empty"));
+            }
+            if library.materials.contains_key(name) {
+                return Err(syntax(line, &format!("material '{}' is already defined", name)));
+            }
+            let ambient = parse_color(line, "ambient", columns[1])?;
+            let diffuse = parse_color(line, "diffuse", columns[2])?;
+            let specular = parse_color(line, "specular", columns[3])?;
+            let shininess = parse_float(line, "shininess", columns[4])?;
+            library.names.push(name.to_string());
+            library.materials.insert(name.to_string(), PhongMaterial::new(ambient, diffuse, specular, shininess));
+        }
+        Ok(library)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PhongMaterial> {
+        self.materials.get(name)
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+pub fn load_material_library<P: AsRef<Path>>(path: P) -> Result<MaterialLibrary, MaterialParseError> {
+    let mut source = String::new();
+    File::open(path)?.read_to_string(&mut source)?;
+    MaterialLibrary::parse(&source)
+}
+
+fn syntax(line: usize, message: &str) -> MaterialParseError {
+    MaterialParseError::Syntax {
+        line,
+        message: message.to_string(),
+    }
+}
+
+fn parse_float(line: usize, column: &str, value: &str) -> Result<f32, MaterialParseError> {
+    value.trim()
+        .parse::<f32>()
+        .map_err(|_| syntax(line, &format!("invalid {} value '{}'", column, value)))
+}
+
+fn parse_color(line: usize, column: &str, value: &str) -> Result<GVec3, MaterialParseError> {
+    let components = value.split(',')
+        .map(|component| parse_float(line, column, component))
+        .collect::<Result<Vec<_>, _>>()?;
+    match components.as_slice() {
+        [r, g, b] => Ok(GVec3::new(*r, *g, *b)),
+        _ => Err(syntax(line, &format!("{} color needs 3 components, found {}", column, components.len()))),
+    }
+}
@@ -0,0 +1,452 @@
+use std::borrow::Cow;
+use std::convert::TryInto;
+use std::fmt;
+use std::ops::Range;
+
+use glium::{Display, IndexBuffer, VertexBuffer};
+use glium::index::{IndexType, IndicesSource, PrimitiveType};
+use glium::vertex::{AttributeType, VertexBufferAny, VertexFormat};
+use math::{Aabb, Sphere};
+
+use crate::vertex::{VertexNorm, VertexNormTan};
+
+/// Attribute stream of a `Mesh`, uploaded under the name the shaders declare it with.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum VertexAttribute {
+    Position,
+    Normal,
+    TexCoords0,
+    TexCoords1,
+    Tangent,
+    Color,
+    Joints,
+    Weights,
+}
+
+impl VertexAttribute {
+    pub const ALL: [VertexAttribute; 8] = [
+        VertexAttribute::Position,
+        VertexAttribute::Normal,
+        VertexAttribute::TexCoords0,
+        VertexAttribute::TexCoords1,
+        VertexAttribute::Tangent,
+        VertexAttribute::Color,
+        VertexAttribute::Joints,
+        VertexAttribute::Weights,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VertexAttribute::Position => "position",
+            VertexAttribute::Normal => "normal",
+            VertexAttribute::TexCoords0 => "tex_coords",
+            VertexAttribute::TexCoords1 => "tex_coords1",
+            VertexAttribute::Tangent => "tangent",
+            VertexAttribute::Color => "color",
+            VertexAttribute::Joints => "joints",
+            VertexAttribute::Weights => "weights",
+        }
+    }
+
+    fn attribute_type(&self) -> AttributeType {
+        match self {
+            VertexAttribute::Position | VertexAttribute::Normal => AttributeType::F32F32F32,
+            VertexAttribute::TexCoords0 | VertexAttribute::TexCoords1 => AttributeType::F32F32,
+            VertexAttribute::Tangent | VertexAttribute::Color | VertexAttribute::Weights => AttributeType::F32F32F32F32,
+            VertexAttribute::Joints => AttributeType::U16U16U16U16,
+        }
+    }
+
+    // size in 32 bit words of the attribute in an interleaved vertex
+    fn words(&self) -> usize {
+        match self {
+            VertexAttribute::Position | VertexAttribute::Normal => 3,
+            VertexAttribute::TexCoords0 | VertexAttribute::TexCoords1 | VertexAttribute::Joints => 2,
+            VertexAttribute::Tangent | VertexAttribute::Color | VertexAttribute::Weights => 4,
+        }
+    }
+}
+
+impl fmt::Display for VertexAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug)]
+pub enum MeshError {
+    // a stream whose length differs from the number of positions
+    AttributeLength { attribute: VertexAttribute, expected: usize, found: usize },
+    IndexOutOfRange(u32),
+    // a submesh reaching past the end of the index list
+    SubmeshOutOfRange(usize),
+    VertexBuffer(glium::vertex::BufferCreationError),
+    IndexBuffer(glium::index::BufferCreationError),
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshError::AttributeLength { attribute, expected, found } =>
+                write!(f, "{} stream has {} values for {} vertexes", attribute, found, expected),
+            MeshError::IndexOutOfRange(index) => write!(f, "vertex index {} out of range", index),
+            MeshError::SubmeshOutOfRange(submesh) => write!(f, "submesh {} reaches past the indexes", submesh),
+            MeshError::VertexBuffer(err) => write!(f, "cannot create the vertex buffer: {}", err),
+            MeshError::IndexBuffer(err) => write!(f, "cannot create the index buffer: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+/// Triangles of a `Mesh` drawn with one material.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Submesh {
+    // range of the mesh index list
+    pub indexes: Range<usize>,
+    // slot in the material list of whatever draws the mesh
+    pub material: usize,
+}
+
+/// CPU side triangle mesh made of optional attribute streams, each holding one value per
+/// position, and of submeshes sharing the vertexes.
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    pub tex_coords0: Option<Vec<[f32; 2]>>,
+    pub tex_coords1: Option<Vec<[f32; 2]>>,
+    pub tangents: Option<Vec<[f32; 4]>>,
+    pub colors: Option<Vec<[f32; 4]>>,
+    pub joints: Option<Vec<[u16; 4]>>,
+    pub weights: Option<Vec<[f32; 4]>>,
+    pub indexes: Vec<u32>,
+    pub submeshes: Vec<Submesh>,
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<[f32; 3]>) -> Self {
+        Self {
+            positions,
+            ..Self::default()
+        }
+    }
+
+    /// Mesh of a single submesh using the material slot 0.
+    pub fn from_vertexes(vertexes: &[VertexNorm], indexes: &[u32]) -> Self {
+        Self::new(vertexes.iter().map(|vertex| vertex.position()).collect())
+            .with_normals(vertexes.iter().map(|vertex| vertex.normal()).collect())
+            .with_tex_coords0(vertexes.iter().map(|vertex| vertex.tex_coords()).collect())
+            .with_submesh(indexes, 0)
+    }
+
+    /// Mesh of a single submesh using the material slot 0.
+    pub fn from_tangent_vertexes(vertexes: &[VertexNormTan], indexes: &[u32]) -> Self {
+        Self::new(vertexes.iter().map(|vertex| vertex.position()).collect())
+            .with_normals(vertexes.iter().map(|vertex| vertex.normal()).collect())
+            .with_tex_coords0(vertexes.iter().map(|vertex| vertex.tex_coords()).collect())
+            .with_tangents(vertexes.iter().map(|vertex| vertex.tangent()).collect())
+            .with_submesh(indexes, 0)
+    }
+
+    pub fn with_normals(mut self, normals: Vec<[f32; 3]>) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_tex_coords0(mut self, tex_coords: Vec<[f32; 2]>) -> Self {
+        self.tex_coords0 = Some(tex_coords);
+        self
+    }
+
+    pub fn with_tex_coords1(mut self, tex_coords: Vec<[f32; 2]>) -> Self {
+        self.tex_coords1 = Some(tex_coords);
+        self
+    }
+
+    pub fn with_tangents(mut self, tangents: Vec<[f32; 4]>) -> Self {
+        self.tangents = Some(tangents);
+        self
+    }
+
+    pub fn with_colors(mut self, colors: Vec<[f32; 4]>) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    pub fn with_skin(mut self, joints: Vec<[u16; 4]>, weights: Vec<[f32; 4]>) -> Self {
+        self.joints = Some(joints);
+        self.weights = Some(weights);
+        self
+    }
+
+    pub fn with_submesh(mut self, indexes: &[u32], material: usize) -> Self {
+        self.push_submesh(indexes, material);
+        self
+    }
+
+    /// Appends triangles drawn with the material `material`, returning the submesh index.
+    pub fn push_submesh(&mut self, indexes: &[u32], material: usize) -> usize {
+        let start = self.indexes.len();
+        self.indexes.extend_from_slice(indexes);
+        self.submeshes.push(Submesh { indexes: start..self.indexes.len(), material });
+        self.submeshes.len() - 1
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn has(&self, attribute: VertexAttribute) -> bool {
+        self.stream_len(attribute).is_some()
+    }
+
+    /// Streams present in the mesh, in the order they are interleaved.
+    pub fn attributes(&self) -> Vec<VertexAttribute> {
+        VertexAttribute::ALL.iter().copied().filter(|attribute| self.has(*attribute)).collect()
+    }
+
+    fn stream_len(&self, attribute: VertexAttribute) -> Option<usize> {
+        match attribute {
+            VertexAttribute::Position => Some(self.positions.len()),
+            VertexAttribute::Normal => self.normals.as_ref().map(Vec::len),
+            VertexAttribute::TexCoords0 => self.tex_coords0.as_ref().map(Vec::len),
+            VertexAttribute::TexCoords1 => self.tex_coords1.as_ref().map(Vec::len),
+            VertexAttribute::Tangent => self.tangents.as_ref().map(Vec::len),
+            VertexAttribute::Color => self.colors.as_ref().map(Vec::len),
+            VertexAttribute::Joints => self.joints.as_ref().map(Vec::len),
+            VertexAttribute::Weights => self.weights.as_ref().map(Vec::len),
+        }
+    }
+
+    /// Index width able to address every vertex, 16 bits whenever possible.
+    pub fn index_type(&self) -> IndexType {
+        if self.vertex_count() <= u16::MAX as usize + 1 { IndexType::U16 } else { IndexType::U32 }
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(self.positions.iter().map(|position| (*position).into()))
+    }
+
+    pub fn bounding_sphere(&self) -> Option<Sphere> {
+        Sphere::from_points(self.positions.iter().map(|position| (*position).into()))
+    }
+
+    pub fn validate(&self) -> Result<(), MeshError> {
+        let expected = self.vertex_count();
+        for attribute in VertexAttribute::ALL.iter() {
+            match self.stream_len(*attribute) {
+                Some(found) if found != expected => {
+                    return Err(MeshError::AttributeLength { attribute: *attribute, expected, found });
+                }
+                _ => {}
+            }
+        }
+        if let Some(index) = self.indexes.iter().find(|index| **index as usize >= expected) {
+            return Err(MeshError::IndexOutOfRange(*index));
+        }
+        if let Some(submesh) = self.submeshes.iter().position(|submesh| submesh.indexes.end > self.indexes.len()) {
+            return Err(MeshError::SubmeshOutOfRange(submesh));
+        }
+        Ok(())
+    }
+
+    // attribute values of a vertex as 32 bit words, joints being packed two per word
+    fn push_words(&self, vertex: usize, attribute: VertexAttribute, words: &mut Vec<u32>) {
+        let floats = |values: &[f32], words: &mut Vec<u32>| words.extend(values.iter().map(|value| value.to_bits()));
+        match attribute {
+            VertexAttribute::Position => floats(&self.positions[vertex], words),
+            VertexAttribute::Normal => floats(&self.normals.as_ref().unwrap()[vertex], words),
+            VertexAttribute::TexCoords0 => floats(&self.tex_coords0.as_ref().unwrap()[vertex], words),
+            VertexAttribute::TexCoords1 => floats(&self.tex_coords1.as_ref().unwrap()[vertex], words),
+            VertexAttribute::Tangent => floats(&self.tangents.as_ref().unwrap()[vertex], words),
+            VertexAttribute::Color => floats(&self.colors.as_ref().unwrap()[vertex], words),
+            VertexAttribute::Weights => floats(&self.weights.as_ref().unwrap()[vertex], words),
+            VertexAttribute::Joints => {
+                let [a, b, c, d] = self.joints.as_ref().unwrap()[vertex];
+                words.extend_from_slice(&[pack_u16(a, b), pack_u16(c, d)]);
+            }
+        }
+    }
+
+    // vertexes as 32 bit words, their stride in words and the layout of the attributes
+    fn interleave(&self, attributes: &[VertexAttribute]) -> (Vec<u32>, usize, VertexFormat) {
+        let mut format = Vec::with_capacity(attributes.len());
+        let mut stride = 0;
+        for attribute in attributes.iter() {
+            format.push((Cow::Borrowed(attribute.name()), stride * 4, attribute.attribute_type(), false));
+            stride += attribute.words();
+        }
+        let mut words = Vec::with_capacity(stride * self.vertex_count());
+        for vertex in 0..self.vertex_count() {
+            for attribute in attributes.iter() {
+                self.push_words(vertex, *attribute, &mut words);
+            }
+        }
+        (words, stride, Cow::Owned(format))
+    }
+
+    /// Interleaves the streams in one vertex buffer and uploads the indexes with the
+    /// narrowest type they fit in.
+    pub fn upload(&self, display: &Display) -> Result<GpuMesh, MeshError> {
+        self.validate()?;
+        let attributes = self.attributes();
+        let (words, stride, format) = self.interleave(&attributes);
+        let vertexes = upload_vertexes(display, &words, stride, format)?;
+
+        let indexes = match self.index_type() {
+            IndexType::U32 => GpuIndexes::U32(IndexBuffer::new(display, PrimitiveType::TrianglesList, &self.indexes)
+                .map_err(MeshError::IndexBuffer)?),
+            _ => {
+                let indexes = self.indexes.iter().map(|index| *index as u16).collect::<Vec<_>>();
+                GpuIndexes::U16(IndexBuffer::new(display, PrimitiveType::TrianglesList, &indexes).map_err(MeshError::IndexBuffer)?)
+            }
+        };
+        Ok(GpuMesh { vertexes, indexes, attributes, submeshes: self.submeshes.clone() })
+    }
+}
+
+// two u16 laid out in memory like `[low, high]`
+fn pack_u16(low: u16, high: u16) -> u32 {
+    let ([l0, l1], [h0, h1]) = (low.to_ne_bytes(), high.to_ne_bytes());
+    u32::from_ne_bytes([l0, l1, h0, h1])
+}
+
+// vertex buffers take their stride from the size of their element type, one array size
+// per possible stride gives them the layout built at runtime
+fn upload_stride<const N: usize>(display: &Display, words: &[u32], format: VertexFormat) -> Result<VertexBufferAny, MeshError> {
+    let vertexes = words.chunks_exact(N).map(|vertex| vertex.try_into().unwrap()).collect::<Vec<[u32; N]>>();
+    // the format only describes offsets within the N words of each vertex
+    let buffer = unsafe { VertexBuffer::new_raw(display, &vertexes, format, N * 4) };
+    buffer.map(VertexBufferAny::from).map_err(MeshError::VertexBuffer)
+}
+
+macro_rules! upload_strides {
+    ($display:expr, $words:expr, $stride:expr, $format:expr, $($n:literal)*) => {
+        match $stride {
+            $($n => upload_stride::<$n>($display, $words, $format),)*
+            _ => unreachable!("vertexes of {} words", $stride),
+        }
+    };
+}
+
+fn upload_vertexes(display: &Display, words: &[u32], stride: usize, format: VertexFormat) -> Result<VertexBufferAny, MeshError> {
+    // from positions alone to every attribute
+    upload_strides!(display, words, stride, format, 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24)
+}
+
+enum GpuIndexes {
+    U16(IndexBuffer<u16>),
+    U32(IndexBuffer<u32>),
+}
+
+/// `Mesh` uploaded to the GPU, drawn whole or one submesh at a time.
+pub struct GpuMesh {
+    vertexes: VertexBufferAny,
+    indexes: GpuIndexes,
+    attributes: Vec<VertexAttribute>,
+    submeshes: Vec<Submesh>,
+}
+
+impl GpuMesh {
+    pub fn vertexes(&self) -> &VertexBufferAny {
+        &self.vertexes
+    }
+
+    pub fn indexes(&self) -> IndicesSource<'_> {
+        match &self.indexes {
+            GpuIndexes::U16(indexes) => indexes.into(),
+            GpuIndexes::U32(indexes) => indexes.into(),
+        }
+    }
+
+    pub fn index_type(&self) -> IndexType {
+        match &self.indexes {
+            GpuIndexes::U16(_) => IndexType::U16,
+            GpuIndexes::U32(_) => IndexType::U32,
+        }
+    }
+
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    pub fn submeshes(&self) -> &[Submesh] {
+        &self.submeshes
+    }
+
+    /// Indexes of one submesh, `None` when out of range.
+    pub fn submesh_indexes(&self, submesh: usize) -> Option<IndicesSource<'_>> {
+        let range = self.submeshes.get(submesh)?.indexes.clone();
+        match &self.indexes {
+            GpuIndexes::U16(indexes) => indexes.slice(range).map(Into::into),
+            GpuIndexes::U32(indexes) => indexes.slice(range).map(Into::into),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> Mesh {
+        Mesh::new(vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]).with_submesh(&[0, 1, 2], 0)
+    }
+
+    #[test]
+    fn index_type_switches_past_65536_vertexes() {
+        assert_eq!(Mesh::new(vec![[0.0; 3]; 65536]).index_type(), IndexType::U16);
+        assert_eq!(Mesh::new(vec![[0.0; 3]; 65537]).index_type(), IndexType::U32);
+    }
+
+    #[test]
+    fn validate_checks_streams_indexes_and_submeshes() {
+        assert!(triangle().validate().is_ok());
+
+        let mesh = triangle().with_tex_coords0(vec![[0.0; 2]; 2]);
+        match mesh.validate() {
+            Err(MeshError::AttributeLength { attribute, expected, found }) => {
+                assert_eq!((attribute, expected, found), (VertexAttribute::TexCoords0, 3, 2));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let mesh = triangle().with_submesh(&[0, 1, 3], 1);
+        assert!(matches!(mesh.validate(), Err(MeshError::IndexOutOfRange(3))));
+
+        let mut mesh = triangle().with_submesh(&[2, 1, 0], 1);
+        mesh.submeshes[1].indexes.end += 3;
+        assert!(matches!(mesh.validate(), Err(MeshError::SubmeshOutOfRange(1))));
+    }
+
+    #[test]
+    fn pack_u16_keeps_the_memory_order() {
+        let packed = pack_u16(0x0102, 0x0304).to_ne_bytes();
+        assert_eq!(packed[..2], 0x0102u16.to_ne_bytes());
+        assert_eq!(packed[2..], 0x0304u16.to_ne_bytes());
+    }
+
+    #[test]
+    fn interleaves_attributes_in_declaration_order() {
+        let mesh = triangle()
+            .with_skin(vec![[1, 2, 3, 4]; 3], vec![[0.25; 4]; 3])
+            .with_tex_coords0(vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        let attributes = mesh.attributes();
+        assert_eq!(attributes, vec![VertexAttribute::Position, VertexAttribute::TexCoords0, VertexAttribute::Joints, VertexAttribute::Weights]);
+        let (words, stride, format) = mesh.interleave(&attributes);
+        // 3 position, 2 texture coordinate, 2 packed joint and 4 weight words
+        assert_eq!(stride, 11);
+        assert_eq!(words.len(), stride * 3);
+        let offsets = format.iter().map(|(name, offset, ty, _)| (name.as_ref(), *offset, *ty)).collect::<Vec<_>>();
+        assert_eq!(offsets, vec![
+            ("position", 0, AttributeType::F32F32F32),
+            ("tex_coords", 12, AttributeType::F32F32),
+            ("joints", 20, AttributeType::U16U16U16U16),
+            ("weights", 28, AttributeType::F32F32F32F32),
+        ]);
+        let second = &words[stride..stride * 2];
+        assert_eq!(second[..5], [1.0f32, 0.0, 0.0, 1.0, 0.0].map(f32::to_bits));
+        assert_eq!(second[5..7], [pack_u16(1, 2), pack_u16(3, 4)]);
+        assert_eq!(second[7..], [0.25f32.to_bits(); 4]);
+    }
+}
@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use glium::Display;
+use glium::index::PrimitiveType;
+use glium::texture::{RawImage2d, Texture2d};
+
+use crate::{GVec3, load_texture_file, Material, PhongMaterial};
+use crate::render_state::{BlendMode, DepthState, RenderState};
+use crate::tangent::smooth_normals;
+use crate::vertex::VertexNorm;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(PathBuf, std::io::Error),
+    Parse { path: PathBuf, line: usize, message: String },
+    Texture(PathBuf, image::ImageError),
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            ObjError::Parse { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+            ObjError::Texture(path, err) => write!(f, "cannot load texture {}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+/// Triangles of one object and group sharing a material.
+#[derive(Debug, Clone)]
+pub struct ObjMesh {
+    pub object: String,
+    pub group: String,
+    pub material: Option<String>,
+    pub vertexes: Vec<VertexNorm>,
+    pub indexes: Vec<u32>,
+}
+
+impl ObjMesh {
+    pub fn buffers(&self, display: &Display) -> (glium::VertexBuffer<VertexNorm>, glium::IndexBuffer<u32>) {
+        (
+            glium::VertexBuffer::new(display, &self.vertexes).unwrap(),
+            glium::IndexBuffer::new(display, PrimitiveType::TrianglesList, &self.indexes).unwrap(),
+        )
+    }
+}
+
+/// Material of a `.mtl` file, texture paths being resolved relative to the MTL file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    // `Ns` exponent, from 0 to 1000
+    pub shininess: f32,
+    // 1 for an opaque material
+    pub dissolve: f32,
+    pub diffuse_texture: Option<PathBuf>,
+    pub specular_texture: Option<PathBuf>,
+    // `map_Bump`, `bump` or `norm`, expected to be a tangent space normal map
+    pub normal_texture: Option<PathBuf>,
+}
+
+impl MtlMaterial {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ambient: [0.2, 0.2, 0.2],
+            diffuse: [0.8, 0.8, 0.8],
+            specular: [0.0, 0.0, 0.0],
+            shininess: 32.0,
+            dissolve: 1.0,
+            diffuse_texture: None,
+            specular_texture: None,
+            normal_texture: None,
+        }
+    }
+
+    fn render_state(&self) -> RenderState {
+        if self.dissolve >= 1.0 {
+            return RenderState::default();
+        }
+        RenderState {
+            blend: BlendMode::Alpha,
+            depth: DepthState {
+                write: false,
+                ..DepthState::default()
+            },
+            ..RenderState::default()
+        }
+    }
+
+    pub fn to_phong(&self) -> PhongMaterial {
+        let [ar, ag, ab] = self.ambient;
+        let [dr, dg, db] = self.diffuse;
+        let [sr, sg, sb] = self.specular;
+        PhongMaterial::new(GVec3::new(ar, ag, ab), GVec3::new(dr, dg, db), GVec3::new(sr, sg, sb), self.shininess / 128.)
+            .with_render_state(self.render_state())
+    }
+
+    /// Textured material, `None` without diffuse texture. A missing specular texture is
+    /// replaced by a single pixel of the specular color.
+    pub fn to_material(&self, display: &Display) -> Result<Option<Material>, ObjError> {
+        let diffuse_path = match &self.diffuse_texture {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let load = |path: &PathBuf| load_texture_file(path, display).map_err(|err| ObjError::Texture(path.clone(), err));
+        let diffuse = load(diffuse_path)?;
+        let specular = match &self.specular_texture {
+            Some(path) => load(path)?,
+            None => {
+                let [r, g, b] = self.specular;
+                let pixel = vec![(r * 255.) as u8, (g * 255.) as u8, (b * 255.) as u8, 255];
+                Texture2d::new(display, RawImage2d::from_raw_rgba(pixel, (1, 1))).unwrap()
+            }
+        };
+        let mut material = Material::new(diffuse, specular, self.shininess / 128.).with_render_state(self.render_state());
+        if let Some(path) = &self.normal_texture {
+            material = material.with_normal_map(load(path)?);
+        }
+        Ok(Some(material))
+    }
+}
+
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    pub materials: HashMap<String, MtlMaterial>,
+}
+
+impl ObjModel {
+    pub fn material(&self, mesh: &ObjMesh) -> Option<&MtlMaterial> {
+        mesh.material.as_ref().and_then(|name| self.materials.get(name))
+    }
+}
+
+// position, texture coordinates and normal indexes of a face corner, all 0 based
+type Corner = (usize, Option<usize>, Option<usize>);
+
+struct MeshBuilder {
+    object: String,
+    group: String,
+    material: Option<String>,
+    corners: Vec<Corner>,
+}
+
+impl MeshBuilder {
+    fn new(object: &str, group: &str, material: Option<String>) -> Self {
+        Self {
+            object: object.to_string(),
+            group: group.to_string(),
+            material,
+            corners: Vec::new(),
+        }
+    }
+
+    // vertexes are shared by identical corners, normals missing from the file are smoothed
+    // over the faces using each position
+    fn build(self, positions: &[[f32; 3]], tex_coords: &[[f32; 2]], normals: &[[f32; 3]]) -> ObjMesh {
+        let smooth = if self.corners.iter().any(|(_, _, normal)| normal.is_none()) {
+            let indexes = self.corners.iter().map(|(position, _, _)| *position as u32).collect::<Vec<_>>();
+            smooth_normals(positions, &indexes)
+        } else {
+            Vec::new()
+        };
+
+        let mut unique = HashMap::new();
+        let mut vertexes = Vec::new();
+        let mut indexes = Vec::with_capacity(self.corners.len());
+        for corner in self.corners.iter() {
+            let index = *unique.entry(*corner).or_insert_with(|| {
+                let (position, tex_coord, normal) = *corner;
+                let [x, y, z] = positions[position];
+                let normal = normal.map_or(smooth.get(position).copied().unwrap_or([0.0, 1.0, 0.0]), |i| normals[i]);
+                let tex_coords = tex_coord.map_or([0.0, 0.0], |i| tex_coords[i]);
+                vertexes.push(VertexNorm::new(x, y, z, normal, tex_coords));
+                vertexes.len() as u32 - 1
+            });
+            indexes.push(index);
+        }
+        ObjMesh {
+            object: self.object,
+            group: self.group,
+            material: self.material,
+            vertexes,
+            indexes,
+        }
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    let mut source = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut source))
+        .map_err(|err| ObjError::Io(path.to_path_buf(), err))?;
+    Ok(source)
+}
+
+fn parse_floats<const N: usize>(values: &[&str], defaults: [f32; N]) -> Result<[f32; N], String> {
+    if values.len() < N.min(1) {
+        return Err("missing values".to_string());
+    }
+    let mut result = defaults;
+    for (i, value) in values.iter().take(N).enumerate() {
+        result[i] = value.parse().map_err(|_| format!("invalid number '{}'", value))?;
+    }
+    Ok(result)
+}
+
+// OBJ indexes start at 1, negative ones count back from the last element read
+fn parse_index(value: &str, count: usize) -> Result<usize, String> {
+    let index: i64 = value.parse().map_err(|_| format!("invalid index '{}'", value))?;
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("index {} out of range", index));
+    }
+    Ok(resolved as usize)
+}
+
+fn parse_corner(value: &str, counts: (usize, usize, usize)) -> Result<Corner, String> {
+    let mut parts = value.split('/');
+    let position = parse_index(parts.next().unwrap_or(""), counts.0)?;
+    let tex_coord = match parts.next() {
+        Some(part) if !part.is_empty() => Some(parse_index(part, counts.1)?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(part) if !part.is_empty() => Some(parse_index(part, counts.2)?),
+        _ => None,
+    };
+    Ok((position, tex_coord, normal))
+}
+
+/// Reads an OBJ file and the MTL libraries it references. One mesh is made per object,
+/// group and material, polygons being triangulated as fans so they must be convex.
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<ObjModel, ObjError> {
+    let path = path.as_ref();
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let source = read_file(path)?;
+
+    let mut positions = Vec::new();
+    let mut tex_coords = Vec::new();
+    let mut normals = Vec::new();
+    let mut materials = HashMap::new();
+    let mut builders: Vec<MeshBuilder> = Vec::new();
+    let mut current = MeshBuilder::new("default", "default", None);
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| ObjError::Parse { path: path.to_path_buf(), line: number + 1, message };
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let values = tokens.collect::<Vec<_>>();
+        let name = values.join(" ");
+        match keyword {
+            "v" => positions.push(parse_floats(&values, [0.0; 3]).map_err(error)?),
+            "vt" => tex_coords.push(parse_floats(&values, [0.0; 2]).map_err(error)?),
+            "vn" => normals.push(parse_floats(&values, [0.0; 3]).map_err(error)?),
+            "f" => {
+                let counts = (positions.len(), tex_coords.len(), normals.len());
+                let corners = values.iter()
+                    .map(|value| parse_corner(value, counts))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+                if corners.len() < 3 {
+                    return Err(error("face with less than 3 vertexes".to_string()));
+                }
+                for i in 1..corners.len() - 1 {
+                    current.corners.extend_from_slice(&[corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "o" | "g" | "usemtl" => {
+                let (object, group, material) = match keyword {
+                    "o" => (name.as_str(), "default", current.material.clone()),
+                    "g" => (current.object.as_str(), name.as_str(), current.material.clone()),
+                    _ => (current.object.as_str(), current.group.as_str(), Some(name.clone())),
+                };
+                let next = MeshBuilder::new(object, group, material);
+                builders.push(std::mem::replace(&mut current, next));
+            }
+            "mtllib" => {
+                for library in values.iter() {
+                    materials.extend(load_mtl(&base_dir.join(library))?);
+                }
+            }
+            // smoothing groups, lines and points are not supported
+            _ => {}
+        }
+    }
+    builders.push(current);
+
+    let meshes = builders.into_iter()
+        .filter(|builder| !builder.corners.is_empty())
+        .map(|builder| builder.build(&positions, &tex_coords, &normals))
+        .collect();
+    Ok(ObjModel { meshes, materials })
+}
+
+/// Reads the materials of an MTL file, texture paths being relative to the file's directory.
+pub fn load_mtl(path: &Path) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let texture_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let source = read_file(path)?;
+    let mut materials = HashMap::new();
+    let mut current: Option<MtlMaterial> = None;
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| ObjError::Parse { path: path.to_path_buf(), line: number + 1, message };
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let values = tokens.collect::<Vec<_>>();
+        if keyword == "newmtl" {
+            if let Some(material) = current.take() {
+                materials.insert(material.name.clone(), material);
+            }
+            current = Some(MtlMaterial::new(&values.join(" ")));
+            continue;
+        }
+        let material = current.as_mut().ok_or_else(|| error(format!("'{}' before any newmtl", keyword)))?;
+        // texture options such as `-bm 1.0` precede the file name
+        let texture = || values.last().map(|file| texture_dir.join(file));
+        match keyword {
+            "Ka" => material.ambient = parse_floats(&values, [0.0; 3]).map_err(error)?,
+            "Kd" => material.diffuse = parse_floats(&values, [0.0; 3]).map_err(error)?,
+            "Ks" => material.specular = parse_floats(&values, [0.0; 3]).map_err(error)?,
+            "Ns" => material.shininess = parse_floats(&values, [0.0]).map_err(error)?[0],
+            "d" => material.dissolve = parse_floats(&values, [1.0]).map_err(error)?[0],
+            "Tr" => material.dissolve = 1.0 - parse_floats(&values, [0.0]).map_err(error)?[0],
+            "map_Kd" => material.diffuse_texture = texture(),
+            "map_Ks" => material.specular_texture = texture(),
+            "map_Bump" | "map_bump" | "bump" | "norm" => material.normal_texture = texture(),
+            _ => {}
+        }
+    }
+    if let Some(material) = current {
+        materials.insert(material.name.clone(), material);
+    }
+    Ok(materials)
+}
+
+/// Writes an indexed triangle list as a single OBJ object, every vertex getting its own
+/// position, texture coordinate and normal so the mesh reads back unchanged.
+pub fn write_obj<W: Write>(writer: &mut W, vertexes: &[VertexNorm], indexes: &[u32]) -> std::io::Result<()> {
+    for vertex in vertexes.iter() {
+        let [x, y, z] = vertex.position();
+        writeln!(writer, "v {} {} {}", x, y, z)?;
+    }
+    for vertex in vertexes.iter() {
+        let [s, t] = vertex.tex_coords();
+        writeln!(writer, "vt {} {}", s, t)?;
+    }
+    for vertex in vertexes.iter() {
+        let [x, y, z] = vertex.normal();
+        writeln!(writer, "vn {} {} {}", x, y, z)?;
+    }
+    for triangle in indexes.chunks(3).filter(|triangle| triangle.len() == 3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] + 1);
+        writeln!(writer, "f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}", a, b, c)?;
+    }
+    writer.flush()
+}
+
+pub fn save_obj<P: AsRef<Path>>(path: P, vertexes: &[VertexNorm], indexes: &[u32]) -> Result<(), ObjError> {
+    let path = path.as_ref();
+    File::create(path)
+        .and_then(|file| write_obj(&mut BufWriter::new(file), vertexes, indexes))
+        .map_err(|err| ObjError::Io(path.to_path_buf(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_keeps_every_vertex() {
+        // two triangles sharing an edge, with one normal and texture coordinate per vertex
+        let vertexes = vec![
+            VertexNorm::new(0.1, 0.2, 0.3, [0.0, 1.0, 0.0], [0.1, 0.9]),
+            VertexNorm::new(1.0 / 3.0, -0.7, 0.0, [0.6, 0.0, 0.8], [0.25, 1.0 / 3.0]),
+            VertexNorm::new(-0.45, 0.0, 2.0 / 3.0, [-0.8, 0.0, 0.6], [0.7, 0.0]),
+            VertexNorm::new(0.1, 0.2, 0.3, [0.0, 0.0, -1.0], [1.0, 0.55]),
+        ];
+        let indexes = vec![0, 1, 2, 2, 1, 3];
+        let path = std::env::temp_dir().join(format!("obj_round_trip_{}.obj", std::process::id()));
+        save_obj(&path, &vertexes, &indexes).unwrap();
+        let loaded = load_obj(&path);
+        std::fs::remove_file(&path).unwrap();
+        let model = loaded.unwrap();
+
+        assert_eq!(model.meshes.len(), 1);
+        let mesh = &model.meshes[0];
+        // the last vertex shares its position with the first one but stays apart
+        assert_eq!(mesh.vertexes.len(), vertexes.len());
+        assert_eq!(mesh.indexes.len(), indexes.len());
+        for (index, loaded) in indexes.iter().zip(mesh.indexes.iter()) {
+            let (vertex, loaded) = (&vertexes[*index as usize], &mesh.vertexes[*loaded as usize]);
+            assert_eq!(loaded.position(), vertex.position());
+            assert_eq!(loaded.normal(), vertex.normal());
+            assert_eq!(loaded.tex_coords(), vertex.tex_coords());
+        }
+    }
+
+    #[test]
+    fn resolves_textures_next_to_the_mtl_file() {
+        let dir = std::env::temp_dir().join(format!("obj_mtl_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("materials")).unwrap();
+        std::fs::write(dir.join("model.obj"), "mtllib materials/model.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n").unwrap();
+        std::fs::write(dir.join("materials/model.mtl"), "newmtl red\nKd 1 0 0\nmap_Kd -bm 1.0 red.png\n").unwrap();
+        let loaded = load_obj(dir.join("model.obj"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        let model = loaded.unwrap();
+
+        let material = model.material(&model.meshes[0]).unwrap();
+        assert_eq!(material.diffuse, [1.0, 0.0, 0.0]);
+        assert_eq!(material.diffuse_texture, Some(dir.join("materials").join("red.png")));
+        // normals missing from the file are those of the face
+        assert!(model.meshes[0].vertexes.iter().all(|vertex| vertex.normal() == [0.0, 0.0, 1.0]));
+    }
+}
@@ -0,0 +1,157 @@
+use glium::texture::Texture2d;
+use glium::uniforms::{AsUniformValue, UniformValue};
+
+use crate::GVec3;
+use crate::material::ParallaxSettings;
+use crate::render_state::{BlendMode, RenderState};
+use crate::renderer::RenderBucket;
+use crate::uniform::{StructToUniform, UniformStorage};
+
+/// Metallic/roughness material for the Cook-Torrance GGX shader (`pbr.fs.glsl`).
+/// Every factor is multiplied with its map when the map is set, like glTF does.
+#[derive(Debug)]
+pub struct PbrMaterial {
+    pub albedo: GVec3,
+    pub albedo_map: Option<Texture2d>,
+    // multiplied with the alpha of the albedo map, ignored unless blended or alpha tested
+    pub alpha: f32,
+    // fragments whose alpha falls under the cutoff are discarded
+    pub alpha_cutoff: Option<f32>,
+    pub metallic: f32,
+    pub metallic_map: Option<Texture2d>,
+    pub roughness: f32,
+    pub roughness_map: Option<Texture2d>,
+    pub ao: f32,
+    pub ao_map: Option<Texture2d>,
+    pub emissive: GVec3,
+    pub emissive_map: Option<Texture2d>,
+    pub normal_map: Option<Texture2d>,
+    pub height_map: Option<Texture2d>,
+    pub parallax: ParallaxSettings,
+    pub render_state: RenderState,
+}
+
+impl PbrMaterial {
+    pub fn new(albedo: GVec3, metallic: f32, roughness: f32) -> Self {
+        Self {
+            albedo,
+            metallic,
+            roughness,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_albedo_map(mut self, map: Texture2d) -> Self {
+        self.albedo_map = Some(map);
+        self
+    }
+
+    pub fn with_alpha(mut self, alpha: f32, cutoff: Option<f32>) -> Self {
+        self.alpha = alpha;
+        self.alpha_cutoff = cutoff;
+        self
+    }
+
+    pub fn with_metallic_map(mut self, map: Texture2d) -> Self {
+        self.metallic_map = Some(map);
+        self
+    }
+
+    pub fn with_roughness_map(mut self, map: Texture2d) -> Self {
+        self.roughness_map = Some(map);
+        self
+    }
+
+    pub fn with_ao_map(mut self, map: Texture2d) -> Self {
+        self.ao_map = Some(map);
+        self
+    }
+
+    pub fn with_emissive(mut self, emissive: GVec3, map: Option<Texture2d>) -> Self {
+        self.emissive = emissive;
+        self.emissive_map = map;
+        self
+    }
+
+    pub fn with_normal_map(mut self, map: Texture2d) -> Self {
+        self.normal_map = Some(map);
+        self
+    }
+
+    pub fn with_height_map(mut self, map: Texture2d, parallax: ParallaxSettings) -> Self {
+        self.height_map = Some(map);
+        self.parallax = parallax;
+        self
+    }
+
+    pub fn with_render_state(mut self, render_state: RenderState) -> Self {
+        self.render_state = render_state;
+        self
+    }
+
+    /// Alpha tested when the material has a cutoff, opaque or transparent after its render state.
+    pub fn bucket(&self) -> RenderBucket {
+        match self.alpha_cutoff {
+            Some(_) => RenderBucket::AlphaTested,
+            None => RenderBucket::from_render_state(&self.render_state),
+        }
+    }
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        Self {
+            albedo: GVec3::new(1.0, 1.0, 1.0),
+            albedo_map: None,
+            alpha: 1.0,
+            alpha_cutoff: None,
+            metallic: 0.0,
+            metallic_map: None,
+            roughness: 1.0,
+            roughness_map: None,
+            ao: 1.0,
+            ao_map: None,
+            emissive: GVec3::new(0.0, 0.0, 0.0),
+            emissive_map: None,
+            normal_map: None,
+            height_map: None,
+            parallax: ParallaxSettings::default(),
+            render_state: RenderState::default(),
+        }
+    }
+}
+
+fn add_map<'a>(storage: &mut UniformStorage<'a>, struct_name: &str, name: &str, flag: &str, map: &'a Option<Texture2d>) {
+    storage.add(&*format!("{}.{}", struct_name, flag), UniformValue::Bool(map.is_some()));
+    if let Some(map) = map {
+        storage.add(&*format!("{}.{}", struct_name, name), UniformValue::Texture2d(map, None));
+    }
+}
+
+impl StructToUniform for PbrMaterial {
+    fn as_uniform<'a>(&'a self, struct_name: &str, storage: &mut UniformStorage<'a>) {
+        storage.add(&*format!("{}.albedo", struct_name), self.albedo.as_uniform_value());
+        storage.add(&*format!("{}.alpha", struct_name), UniformValue::Float(self.alpha));
+        let has_alpha = self.alpha_cutoff.is_some() || self.render_state.blend != BlendMode::Opaque;
+        storage.add(&*format!("{}.hasAlpha", struct_name), UniformValue::Bool(has_alpha));
+        storage.add(&*format!("{}.hasAlphaCutoff", struct_name), UniformValue::Bool(self.alpha_cutoff.is_some()));
+        storage.add(&*format!("{}.alphaCutoff", struct_name), UniformValue::Float(self.alpha_cutoff.unwrap_or(0.0)));
+        storage.add(&*format!("{}.metallic", struct_name), UniformValue::Float(self.metallic));
+        storage.add(&*format!("{}.roughness", struct_name), UniformValue::Float(self.roughness));
+        storage.add(&*format!("{}.ao", struct_name), UniformValue::Float(self.ao));
+        storage.add(&*format!("{}.emissive", struct_name), self.emissive.as_uniform_value());
+        add_map(storage, struct_name, "albedoMap", "hasAlbedoMap", &self.albedo_map);
+        add_map(storage, struct_name, "metallicMap", "hasMetallicMap", &self.metallic_map);
+        add_map(storage, struct_name, "roughnessMap", "hasRoughnessMap", &self.roughness_map);
+        add_map(storage, struct_name, "aoMap", "hasAoMap", &self.ao_map);
+        add_map(storage, struct_name, "emissiveMap", "hasEmissiveMap", &self.emissive_map);
+        add_map(storage, struct_name, "normalMap", "hasNormalMap", &self.normal_map);
+        add_map(storage, struct_name, "heightMap", "hasHeightMap", &self.height_map);
+        if self.height_map.is_some() {
+            storage.add(&*format!("{}.parallaxMode", struct_name), UniformValue::SignedInt(self.parallax.mode.as_shader_id()));
+            storage.add(&*format!("{}.heightScale", struct_name), UniformValue::Float(self.parallax.height_scale));
+            storage.add(&*format!("{}.minLayers", struct_name), UniformValue::Float(self.parallax.min_layers));
+            storage.add(&*format!("{}.maxLayers", struct_name), UniformValue::Float(self.parallax.max_layers));
+        }
+    }
+}
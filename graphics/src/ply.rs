@@ -0,0 +1,360 @@
+use std::convert::TryInto;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::tangent::smooth_normals;
+use crate::vertex::VertexNorm;
+
+#[derive(Debug)]
+pub enum PlyError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, String),
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlyError::Io(path, err) => write!(f, "cannot access {}: {}", path.display(), err),
+            PlyError::Parse(path, message) => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl std::error::Error for PlyError {}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+impl PlyFormat {
+    fn name(&self) -> &'static str {
+        match self {
+            PlyFormat::Ascii => "ascii",
+            PlyFormat::BinaryLittleEndian => "binary_little_endian",
+        }
+    }
+}
+
+/// Writes an indexed triangle list with positions, normals and texture coordinates.
+pub fn write_ply<W: Write>(writer: &mut W, format: PlyFormat, vertexes: &[VertexNorm], indexes: &[u32]) -> std::io::Result<()> {
+    let triangles = indexes.chunks(3).filter(|triangle| triangle.len() == 3);
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format {} 1.0", format.name())?;
+    writeln!(writer, "element vertex {}", vertexes.len())?;
+    for property in ["x", "y", "z", "nx", "ny", "nz", "s", "t"].iter() {
+        writeln!(writer, "property float {}", property)?;
+    }
+    writeln!(writer, "element face {}", indexes.len() / 3)?;
+    writeln!(writer, "property list uchar uint vertex_indices")?;
+    writeln!(writer, "end_header")?;
+
+    for vertex in vertexes.iter() {
+        let [x, y, z] = vertex.position();
+        let [nx, ny, nz] = vertex.normal();
+        let [s, t] = vertex.tex_coords();
+        let values = [x, y, z, nx, ny, nz, s, t];
+        match format {
+            PlyFormat::Ascii => {
+                let line = values.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(" ");
+                writeln!(writer, "{}", line)?;
+            }
+            PlyFormat::BinaryLittleEndian => {
+                for value in values.iter() {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+            }
+        }
+    }
+    for triangle in triangles {
+        match format {
+            PlyFormat::Ascii => writeln!(writer, "3 {} {} {}", triangle[0], triangle[1], triangle[2])?,
+            PlyFormat::BinaryLittleEndian => {
+                writer.write_all(&[3])?;
+                for index in triangle.iter() {
+                    writer.write_all(&index.to_le_bytes())?;
+                }
+            }
+        }
+    }
+    writer.flush()
+}
+
+pub fn save_ply<P: AsRef<Path>>(path: P, format: PlyFormat, vertexes: &[VertexNorm], indexes: &[u32]) -> Result<(), PlyError> {
+    let path = path.as_ref();
+    File::create(path)
+        .and_then(|file| write_ply(&mut BufWriter::new(file), format, vertexes, indexes))
+        .map_err(|err| PlyError::Io(path.to_path_buf(), err))
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Scalar {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Self, String> {
+        Ok(match name {
+            "char" | "int8" => Scalar::Char,
+            "uchar" | "uint8" => Scalar::UChar,
+            "short" | "int16" => Scalar::Short,
+            "ushort" | "uint16" => Scalar::UShort,
+            "int" | "int32" => Scalar::Int,
+            "uint" | "uint32" => Scalar::UInt,
+            "float" | "float32" => Scalar::Float,
+            "double" | "float64" => Scalar::Double,
+            _ => return Err(format!("unknown property type '{}'", name)),
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::Char | Scalar::UChar => 1,
+            Scalar::Short | Scalar::UShort => 2,
+            Scalar::Int | Scalar::UInt | Scalar::Float => 4,
+            Scalar::Double => 8,
+        }
+    }
+}
+
+enum Property {
+    Scalar(String, Scalar),
+    // count type, item type
+    List(String, Scalar, Scalar),
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar(name, _) | Property::List(name, _, _) => name,
+        }
+    }
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn property(&self, names: &[&str]) -> Option<usize> {
+        self.properties.iter().position(|property| names.contains(&property.name()))
+    }
+}
+
+// element data following the header, every value being widened to f64
+enum Body<'a> {
+    Ascii(std::str::SplitWhitespace<'a>),
+    Binary { bytes: &'a [u8], big_endian: bool },
+}
+
+impl Body<'_> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, String> {
+        match self {
+            Body::Ascii(tokens) => {
+                let token = tokens.next().ok_or("unexpected end of file")?;
+                token.parse().map_err(|_| format!("invalid number '{}'", token))
+            }
+            Body::Binary { bytes, big_endian } => {
+                let size = scalar.size();
+                if bytes.len() < size {
+                    return Err("unexpected end of file".to_string());
+                }
+                // decoded as little endian from a copy reversed for big endian files
+                let mut buffer = [0u8; 8];
+                buffer[..size].copy_from_slice(&bytes[..size]);
+                if *big_endian {
+                    buffer[..size].reverse();
+                }
+                *bytes = &bytes[size..];
+                Ok(match scalar {
+                    Scalar::Char => buffer[0] as i8 as f64,
+                    Scalar::UChar => buffer[0] as f64,
+                    Scalar::Short => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    Scalar::UShort => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    Scalar::Int => i32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+                    Scalar::UInt => u32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+                    Scalar::Float => f32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+                    Scalar::Double => f64::from_le_bytes(buffer),
+                })
+            }
+        }
+    }
+
+    // values of one element, lists being flattened after their length
+    fn read_element(&mut self, element: &Element) -> Result<Vec<Vec<f64>>, String> {
+        element.properties.iter()
+            .map(|property| match property {
+                Property::Scalar(_, scalar) => Ok(vec![self.read(*scalar)?]),
+                Property::List(_, count, item) => {
+                    let count = self.read(*count)? as usize;
+                    (0..count).map(|_| self.read(*item)).collect()
+                }
+            })
+            .collect()
+    }
+}
+
+fn parse_header(header: &str) -> Result<(String, Vec<Element>), String> {
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err("missing 'ply' magic number".to_string());
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+        match tokens.as_slice() {
+            ["format", name, _] => format = Some(name.to_string()),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| format!("invalid element count '{}'", count))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => elements.last_mut()
+                .ok_or("property outside of an element")?
+                .properties.push(Property::List(name.to_string(), Scalar::parse(count)?, Scalar::parse(item)?)),
+            ["property", scalar, name] => elements.last_mut()
+                .ok_or("property outside of an element")?
+                .properties.push(Property::Scalar(name.to_string(), Scalar::parse(scalar)?)),
+            // comments and obj_info
+            _ => {}
+        }
+    }
+    Ok((format.ok_or("missing format")?, elements))
+}
+
+fn parse_ply(bytes: &[u8]) -> Result<(Vec<VertexNorm>, Vec<u32>), String> {
+    const END_HEADER: &[u8] = b"end_header";
+    let end = bytes.windows(END_HEADER.len())
+        .position(|window| window == END_HEADER)
+        .ok_or("missing end_header")?;
+    let header = std::str::from_utf8(&bytes[..end]).map_err(|_| "header is not valid text")?;
+    let (format, elements) = parse_header(header)?;
+    // the body starts after the line feed ending the header
+    let data_start = bytes[end..].iter().position(|byte| *byte == b'\n').map_or(bytes.len(), |i| end + i + 1);
+    let data = &bytes[data_start..];
+    let mut body = match format.as_str() {
+        "ascii" => Body::Ascii(std::str::from_utf8(data).map_err(|_| "body is not valid text")?.split_whitespace()),
+        "binary_little_endian" => Body::Binary { bytes: data, big_endian: false },
+        "binary_big_endian" => Body::Binary { bytes: data, big_endian: true },
+        _ => return Err(format!("unknown format '{}'", format)),
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut tex_coords = Vec::new();
+    let mut indexes = Vec::new();
+    for element in elements.iter() {
+        let position = [["x"], ["y"], ["z"]].map(|names| element.property(&names));
+        let normal = [["nx"], ["ny"], ["nz"]].map(|names| element.property(&names));
+        let tex_coord = [["s", "u", "texture_u"], ["t", "v", "texture_v"]].map(|names| element.property(&names));
+        let face = element.property(&["vertex_indices", "vertex_index"]);
+        for _ in 0..element.count {
+            let values = body.read_element(element)?;
+            let get = |property: Option<usize>| property.map_or(0.0, |i| values[i][0] as f32);
+            match element.name.as_str() {
+                "vertex" => {
+                    positions.push(position.map(get));
+                    if normal.iter().all(Option::is_some) {
+                        normals.push(normal.map(get));
+                    }
+                    tex_coords.push(tex_coord.map(get));
+                }
+                "face" => {
+                    let corners = face.map(|i| values[i].as_slice()).ok_or("face without vertex indices")?;
+                    for i in 1..corners.len().saturating_sub(1) {
+                        indexes.extend_from_slice(&[corners[0] as u32, corners[i] as u32, corners[i + 1] as u32]);
+                    }
+                }
+                // other elements are read to be skipped
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(index) = indexes.iter().find(|index| **index as usize >= positions.len()) {
+        return Err(format!("vertex index {} out of range", index));
+    }
+    if normals.len() != positions.len() {
+        normals = smooth_normals(&positions, &indexes);
+    }
+    let vertexes = positions.iter().zip(normals.iter()).zip(tex_coords.iter())
+        .map(|((&[x, y, z], normal), tex_coords)| VertexNorm::new(x, y, z, *normal, *tex_coords))
+        .collect();
+    Ok((vertexes, indexes))
+}
+
+/// Reads the vertexes and faces of an ASCII or binary PLY file, polygons being triangulated
+/// as fans. Normals are smoothed over the faces when the vertexes have none.
+pub fn load_ply<P: AsRef<Path>>(path: P) -> Result<(Vec<VertexNorm>, Vec<u32>), PlyError> {
+    let path = path.as_ref();
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|err| PlyError::Io(path.to_path_buf(), err))?;
+    parse_ply(&bytes).map_err(|message| PlyError::Parse(path.to_path_buf(), message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // tetrahedron whose values are not exact in binary, to catch any rounding
+    fn tetrahedron() -> (Vec<VertexNorm>, Vec<u32>) {
+        let vertexes = vec![
+            VertexNorm::new(0.1, 0.2, 0.3, [0.0, 1.0, 0.0], [0.1, 0.9]),
+            VertexNorm::new(1.0 / 3.0, -0.7, 0.0, [0.6, 0.0, 0.8], [0.25, 1.0 / 3.0]),
+            VertexNorm::new(-0.45, 0.0, 2.0 / 3.0, [-0.8, 0.0, 0.6], [0.7, 0.0]),
+            VertexNorm::new(0.0, -0.3, -1.1, [0.0, 0.0, -1.0], [1.0, 0.55]),
+        ];
+        (vertexes, vec![0, 1, 2, 0, 2, 3, 0, 3, 1, 1, 3, 2])
+    }
+
+    fn round_trip(format: PlyFormat) {
+        let (vertexes, indexes) = tetrahedron();
+        let path = std::env::temp_dir().join(format!("ply_round_trip_{:?}_{}.ply", format, std::process::id()));
+        save_ply(&path, format, &vertexes, &indexes).unwrap();
+        let loaded = load_ply(&path);
+        std::fs::remove_file(&path).unwrap();
+        let (loaded_vertexes, loaded_indexes) = loaded.unwrap();
+
+        assert_eq!(loaded_indexes, indexes);
+        assert_eq!(loaded_vertexes.len(), vertexes.len());
+        for (loaded, vertex) in loaded_vertexes.iter().zip(vertexes.iter()) {
+            assert_eq!(loaded.position(), vertex.position());
+            assert_eq!(loaded.normal(), vertex.normal());
+            assert_eq!(loaded.tex_coords(), vertex.tex_coords());
+        }
+    }
+
+    #[test]
+    fn ascii_round_trip_is_exact() {
+        round_trip(PlyFormat::Ascii);
+    }
+
+    #[test]
+    fn binary_round_trip_is_exact() {
+        round_trip(PlyFormat::BinaryLittleEndian);
+    }
+
+    #[test]
+    fn smooths_missing_normals_and_triangulates_polygons() {
+        let source = b"ply\nformat ascii 1.0\ncomment quad\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+            element face 1\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n1 0 0\n1 1 0\n0 1 0\n4 0 1 2 3\n";
+        let (vertexes, indexes) = parse_ply(source).unwrap();
+        assert_eq!(indexes, vec![0, 1, 2, 0, 2, 3]);
+        assert!(vertexes.iter().all(|vertex| vertex.normal() == [0.0, 0.0, 1.0]));
+    }
+}
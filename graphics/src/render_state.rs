@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+
+use glium::{BackfaceCullingMode, Blend, Depth, DepthTest, DrawParameters, PolygonMode, StencilOperation, StencilTest};
+use glium::draw_parameters::Stencil;
+use serde::Deserialize;
+
+use crate::depth_mode::DepthMode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum BlendMode {
+    Opaque,
+    // classic `src * alpha + dst * (1 - alpha)`
+    Alpha,
+    // colors are already multiplied by their alpha
+    Premultiplied,
+    Additive,
+    // weighted blended OIT: colors are summed, alpha keeps the product of `1 - alpha`
+    WeightedAccumulation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum CullMode {
+    None,
+    Clockwise,
+    CounterClockwise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum DepthFunction {
+    Always,
+    Never,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+}
+
+impl DepthFunction {
+    /// Same comparison with the depth range flipped, `Less` becoming `Greater`.
+    pub fn reversed(self) -> Self {
+        match self {
+            DepthFunction::Less => DepthFunction::Greater,
+            DepthFunction::LessOrEqual => DepthFunction::GreaterOrEqual,
+            DepthFunction::Greater => DepthFunction::Less,
+            DepthFunction::GreaterOrEqual => DepthFunction::LessOrEqual,
+            other => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(default)]
+pub struct DepthState {
+    pub test: DepthFunction,
+    pub write: bool,
+}
+
+impl Default for DepthState {
+    fn default() -> Self {
+        Self {
+            test: DepthFunction::Less,
+            write: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum FillMode {
+    Fill,
+    Line,
+    Point,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    Increment,
+    IncrementWrap,
+    Decrement,
+    DecrementWrap,
+    Invert,
+}
+
+/// Stencil configuration applied to both faces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(default)]
+pub struct StencilState {
+    // the test compares `reference & read_mask` with `stencil & read_mask`
+    pub test: DepthFunction,
+    pub reference: i32,
+    pub read_mask: u32,
+    pub write_mask: u32,
+    pub fail: StencilOp,
+    pub depth_fail: StencilOp,
+    pub pass: StencilOp,
+}
+
+impl Default for StencilState {
+    fn default() -> Self {
+        Self {
+            test: DepthFunction::Always,
+            reference: 0,
+            read_mask: 0xFF,
+            write_mask: 0xFF,
+            fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Keep,
+        }
+    }
+}
+
+/// Fixed function state a draw is made with, converted to glium `DrawParameters`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(default)]
+pub struct RenderState {
+    pub blend: BlendMode,
+    pub cull: CullMode,
+    pub depth: DepthState,
+    pub polygon_mode: FillMode,
+    pub stencil: Option<StencilState>,
+    pub color_mask: [bool; 4],
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+        Self {
+            blend: BlendMode::Opaque,
+            cull: CullMode::Clockwise,
+            depth: DepthState::default(),
+            polygon_mode: FillMode::Fill,
+            stencil: None,
+            color_mask: [true; 4],
+        }
+    }
+}
+
+impl RenderState {
+    pub fn to_draw_parameters(&self) -> DrawParameters<'static> {
+        self.to_draw_parameters_with(DepthMode::Standard)
+    }
+
+    /// Parameters for a target following `depth_mode`, the depth test of the state being
+    /// written for the standard convention.
+    pub fn to_draw_parameters_with(&self, depth_mode: DepthMode) -> DrawParameters<'static> {
+        let mask = self.color_mask;
+        DrawParameters {
+            depth: Depth {
+                test: depth_mode.depth_test(self.depth.test).into(),
+                write: self.depth.write,
+                ..Depth::default()
+            },
+            backface_culling: self.cull.into(),
+            blend: self.blend.into(),
+            polygon_mode: self.polygon_mode.into(),
+            stencil: self.stencil.map(Stencil::from).unwrap_or_default(),
+            color_mask: (mask[0], mask[1], mask[2], mask[3]),
+            ..DrawParameters::default()
+        }
+    }
+
+    /// Returns this state with every field set in `pass` replaced.
+    pub fn merge(&self, pass: &RenderStateOverride) -> RenderState {
+        RenderState {
+            blend: pass.blend.unwrap_or(self.blend),
+            cull: pass.cull.unwrap_or(self.cull),
+            depth: pass.depth.unwrap_or(self.depth),
+            polygon_mode: pass.polygon_mode.unwrap_or(self.polygon_mode),
+            stencil: pass.stencil.unwrap_or(self.stencil),
+            color_mask: pass.color_mask.unwrap_or(self.color_mask),
+        }
+    }
+}
+
+/// Per pass changes applied on top of the material render state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RenderStateOverride {
+    pub blend: Option<BlendMode>,
+    pub cull: Option<CullMode>,
+    pub depth: Option<DepthState>,
+    pub polygon_mode: Option<FillMode>,
+    pub stencil: Option<Option<StencilState>>,
+    pub color_mask: Option<[bool; 4]>,
+}
+
+impl RenderStateOverride {
+    // depth pre-pass and shadow maps: only the depth buffer is written, the test being flipped
+    // by `RenderStateCache` like any other once `set_depth_mode` reverses the depths
+    pub fn depth_only() -> Self {
+        Self {
+            blend: Some(BlendMode::Opaque),
+            depth: Some(DepthState {
+                test: DepthFunction::Less,
+                write: true,
+            }),
+            color_mask: Some([false; 4]),
+            ..Self::default()
+        }
+    }
+
+    // accumulation pass of the weighted blended transparency, tested against the opaque depth
+    pub fn weighted_oit() -> Self {
+        Self {
+            blend: Some(BlendMode::WeightedAccumulation),
+            depth: Some(DepthState {
+                test: DepthFunction::Less,
+                write: false,
+            }),
+            ..Self::default()
+        }
+    }
+
+    pub fn wireframe() -> Self {
+        Self {
+            polygon_mode: Some(FillMode::Line),
+            cull: Some(CullMode::None),
+            ..Self::default()
+        }
+    }
+}
+
+/// `DrawParameters` built once per distinct `RenderState` for the current `DepthMode`.
+#[derive(Default)]
+pub struct RenderStateCache {
+    parameters: HashMap<RenderState, DrawParameters<'static>>,
+    depth_mode: DepthMode,
+}
+
+impl RenderStateCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn depth_mode(&self) -> DepthMode {
+        self.depth_mode
+    }
+
+    /// Every pass drawn with the cache follows `depth_mode` from now on.
+    pub fn set_depth_mode(&mut self, depth_mode: DepthMode) {
+        if self.depth_mode != depth_mode {
+            self.depth_mode = depth_mode;
+            self.parameters.clear();
+        }
+    }
+
+    pub fn get(&mut self, state: &RenderState) -> &DrawParameters<'static> {
+        let depth_mode = self.depth_mode;
+        self.parameters
+            .entry(*state)
+            .or_insert_with(|| state.to_draw_parameters_with(depth_mode))
+    }
+
+    pub fn get_with_override(&mut self, state: &RenderState, pass: &RenderStateOverride) -> &DrawParameters<'static> {
+        self.get(&state.merge(pass))
+    }
+}
+
+impl From<BlendMode> for Blend {
+    fn from(mode: BlendMode) -> Self {
+        use glium::{BlendingFunction, LinearBlendingFactor};
+        match mode {
+            BlendMode::Opaque => Blend::default(),
+            BlendMode::Alpha => Blend::alpha_blending(),
+            BlendMode::Premultiplied => {
+                let function = BlendingFunction::Addition {
+                    source: LinearBlendingFactor::One,
+                    destination: LinearBlendingFactor::OneMinusSourceAlpha,
+                };
+                Blend {
+                    color: function,
+                    alpha: function,
+                    constant_value: (0.0, 0.0, 0.0, 0.0),
+                }
+            }
+            BlendMode::Additive => {
+                let function = BlendingFunction::Addition {
+                    source: LinearBlendingFactor::SourceAlpha,
+                    destination: LinearBlendingFactor::One,
+                };
+                Blend {
+                    color: function,
+                    alpha: function,
+                    constant_value: (0.0, 0.0, 0.0, 0.0),
+                }
+            }
+            BlendMode::WeightedAccumulation => Blend {
+                color: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::One,
+                    destination: LinearBlendingFactor::One,
+                },
+                alpha: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::Zero,
+                    destination: LinearBlendingFactor::OneMinusSourceAlpha,
+                },
+                constant_value: (0.0, 0.0, 0.0, 0.0),
+            },
+        }
+    }
+}
+
+impl From<CullMode> for BackfaceCullingMode {
+    fn from(mode: CullMode) -> Self {
+        match mode {
+            CullMode::None => BackfaceCullingMode::CullingDisabled,
+            CullMode::Clockwise => BackfaceCullingMode::CullClockwise,
+            CullMode::CounterClockwise => BackfaceCullingMode::CullCounterClockwise,
+        }
+    }
+}
+
+impl From<DepthFunction> for DepthTest {
+    fn from(function: DepthFunction) -> Self {
+        match function {
+            DepthFunction::Always => DepthTest::Overwrite,
+            DepthFunction::Never => DepthTest::Ignore,
+            DepthFunction::Less => DepthTest::IfLess,
+            DepthFunction::LessOrEqual => DepthTest::IfLessOrEqual,
+            DepthFunction::Greater => DepthTest::IfMore,
+            DepthFunction::GreaterOrEqual => DepthTest::IfMoreOrEqual,
+            DepthFunction::Equal => DepthTest::IfEqual,
+        }
+    }
+}
+
+impl From<FillMode> for PolygonMode {
+    fn from(mode: FillMode) -> Self {
+        match mode {
+            FillMode::Fill => PolygonMode::Fill,
+            FillMode::Line => PolygonMode::Line,
+            FillMode::Point => PolygonMode::Point,
+        }
+    }
+}
+
+impl From<StencilOp> for StencilOperation {
+    fn from(op: StencilOp) -> Self {
+        match op {
+            StencilOp::Keep => StencilOperation::Keep,
+            StencilOp::Zero => StencilOperation::Zero,
+            StencilOp::Replace => StencilOperation::Replace,
+            StencilOp::Increment => StencilOperation::Increment,
+            StencilOp::IncrementWrap => StencilOperation::IncrementWrap,
+            StencilOp::Decrement => StencilOperation::Decrement,
+            StencilOp::DecrementWrap => StencilOperation::DecrementWrap,
+            StencilOp::Invert => StencilOperation::Invert,
+        }
+    }
+}
+
+impl From<StencilState> for Stencil {
+    fn from(state: StencilState) -> Self {
+        let mask = state.read_mask;
+        let test = match state.test {
+            DepthFunction::Always => StencilTest::AlwaysPass,
+            DepthFunction::Never => StencilTest::AlwaysFail,
+            DepthFunction::Less => StencilTest::IfLess { mask },
+            DepthFunction::LessOrEqual => StencilTest::IfLessOrEqual { mask },
+            DepthFunction::Greater => StencilTest::IfMore { mask },
+            DepthFunction::GreaterOrEqual => StencilTest::IfMoreOrEqual { mask },
+            DepthFunction::Equal => StencilTest::IfEqual { mask },
+        };
+        Stencil {
+            test_clockwise: test,
+            reference_value_clockwise: state.reference,
+            write_mask_clockwise: state.write_mask,
+            fail_operation_clockwise: state.fail.into(),
+            pass_depth_fail_operation_clockwise: state.depth_fail.into(),
+            depth_pass_operation_clockwise: state.pass.into(),
+            test_counter_clockwise: test,
+            reference_value_counter_clockwise: state.reference,
+            write_mask_counter_clockwise: state.write_mask,
+            fail_operation_counter_clockwise: state.fail.into(),
+            pass_depth_fail_operation_counter_clockwise: state.depth_fail.into(),
+            depth_pass_operation_counter_clockwise: state.pass.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_only_follows_the_depth_mode() {
+        let mut cache = RenderStateCache::new();
+        let state = RenderState::default();
+        let pass = RenderStateOverride::depth_only();
+        let parameters = cache.get_with_override(&state, &pass);
+        assert_eq!(parameters.depth.test, DepthTest::IfLess);
+        assert!(parameters.depth.write);
+        assert_eq!(parameters.color_mask, (false, false, false, false));
+
+        cache.set_depth_mode(DepthMode::ReversedZ);
+        let parameters = cache.get_with_override(&state, &pass);
+        assert_eq!(parameters.depth.test, DepthTest::IfMore);
+        assert!(parameters.depth.write);
+    }
+}
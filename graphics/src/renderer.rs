@@ -0,0 +1,333 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use glium::{Blend, Display, DrawParameters, Program, Surface};
+use glium::framebuffer::MultiOutputFrameBuffer;
+use glium::index::IndicesSource;
+use glium::texture::{DepthStencilTexture2d, MipmapsOption, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::UniformValue;
+use glium::vertex::VerticesSource;
+use math::{Aabb, Frustum, glm};
+
+use crate::antialiasing::ScreenQuad;
+use crate::debug_view::DebugView;
+use crate::render_state::{BlendMode, RenderState, RenderStateCache, RenderStateOverride};
+use crate::uniform::UniformStorage;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RenderBucket {
+    Opaque,
+    // opaque states whose shader discards fragments, drawn after the plain opaque draws
+    AlphaTested,
+    Transparent,
+}
+
+impl RenderBucket {
+    /// Blended states go to the transparent bucket, everything else is opaque.
+    pub fn from_render_state(state: &RenderState) -> Self {
+        match state.blend {
+            BlendMode::Opaque => RenderBucket::Opaque,
+            _ => RenderBucket::Transparent,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TransparencyMode {
+    // back to front sorting with regular blending
+    #[default]
+    Sorted,
+    // weighted blended order independent transparency, needs a single sampled scene
+    WeightedOit,
+}
+
+impl TransparencyMode {
+    pub const ALL: [TransparencyMode; 2] = [TransparencyMode::Sorted, TransparencyMode::WeightedOit];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransparencyMode::Sorted => "Sorted",
+            TransparencyMode::WeightedOit => "Weighted OIT",
+        }
+    }
+}
+
+impl fmt::Display for TransparencyMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A single draw call waiting in a `RenderQueue`.
+pub struct DrawItem<'a> {
+    pub vertexes: VerticesSource<'a>,
+    pub indexes: IndicesSource<'a>,
+    pub program: &'a Program,
+    pub uniforms: UniformStorage<'a>,
+    pub render_state: RenderState,
+    pub bucket: RenderBucket,
+    // world space point used for depth sorting, usually the origin of the model
+    pub position: glm::Vec3,
+    // model space bounds of the vertexes
+    pub bounds: Option<Aabb>,
+    // model matrix placing the bounds in the world, needed for culling
+    pub model: Option<glm::Mat4>,
+    // world space bounds culled instead of the placed `bounds`, covering every instance
+    pub culling_bounds: Option<Aabb>,
+    // per instance attributes, drawing one copy of the vertexes for each
+    pub instances: Option<VerticesSource<'a>>,
+}
+
+impl<'a> DrawItem<'a> {
+    pub fn new<V, I>(vertexes: V, indexes: I, program: &'a Program, uniforms: UniformStorage<'a>, render_state: RenderState, position: glm::Vec3) -> Self
+        where V: Into<VerticesSource<'a>>, I: Into<IndicesSource<'a>> {
+        Self {
+            vertexes: vertexes.into(),
+            indexes: indexes.into(),
+            program,
+            uniforms,
+            render_state,
+            bucket: RenderBucket::from_render_state(&render_state),
+            position,
+            bounds: None,
+            model: None,
+            culling_bounds: None,
+            instances: None,
+        }
+    }
+
+    pub fn with_bucket(mut self, bucket: RenderBucket) -> Self {
+        self.bucket = bucket;
+        self
+    }
+
+    pub fn with_bounds(mut self, bounds: Aabb) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    pub fn with_model(mut self, model: glm::Mat4) -> Self {
+        self.model = Some(model);
+        self
+    }
+
+    /// Culls the item with world space bounds, for instanced items whose `bounds` are placed
+    /// by each instance.
+    pub fn with_culling_bounds(mut self, bounds: Aabb) -> Self {
+        self.culling_bounds = Some(bounds);
+        self
+    }
+
+    /// World space bounds, the culling bounds when given, else `None` unless both the bounds
+    /// and the model are known.
+    pub fn world_bounds(&self) -> Option<Aabb> {
+        self.culling_bounds.or_else(|| Some(self.bounds?.transform(self.model.as_ref()?)))
+    }
+
+    /// Whether the item is entirely outside the frustum, items without world bounds never are.
+    pub fn is_culled(&self, frustum: &Frustum) -> bool {
+        self.world_bounds().is_some_and(|bounds| {
+            !frustum.intersects_sphere(&bounds.bounding_sphere()) || !frustum.intersects_aabb(&bounds)
+        })
+    }
+
+    /// Draws the item once per instance, usually the `per_instance` of an `InstanceBuffer`.
+    /// The program must read the model from the instance attributes.
+    pub fn with_instances<V: Into<VerticesSource<'a>>>(mut self, instances: V) -> Self {
+        self.instances = Some(instances.into());
+        self
+    }
+
+    fn draw<S: Surface>(&self, target: &mut S, program: &Program, params: &DrawParameters) {
+        match &self.instances {
+            Some(instances) => target.draw((self.vertexes.clone(), instances.clone()), self.indexes.clone(), program, &self.uniforms, params),
+            None => target.draw(self.vertexes.clone(), self.indexes.clone(), program, &self.uniforms, params),
+        }.unwrap();
+    }
+}
+
+fn draw_item<S: Surface>(target: &mut S, mut item: DrawItem, debug_view: Option<&DebugView>, render_states: &mut RenderStateCache) {
+    let mut program = item.program;
+    if let Some(debug_program) = debug_view.and_then(|debug_view| debug_view.shading_program(&item)) {
+        debug_view.unwrap().add_uniforms(&mut item);
+        program = debug_program;
+    }
+    let params = render_states.get(&item.render_state);
+    item.draw(target, program, params);
+    if let Some(debug_view) = debug_view {
+        debug_view.draw_overlays(target, &item, render_states);
+    }
+}
+
+/// Collects the draws of a frame and submits them bucket by bucket: opaque front to back,
+/// alpha tested, then transparent back to front or through `WeightedOit`. With a frustum, items
+/// outside of it are dropped when pushed.
+#[derive(Default)]
+pub struct RenderQueue<'a> {
+    opaque: Vec<DrawItem<'a>>,
+    alpha_tested: Vec<DrawItem<'a>>,
+    transparent: Vec<DrawItem<'a>>,
+    debug_view: Option<&'a DebugView>,
+    frustum: Option<Frustum>,
+    culled: usize,
+}
+
+impl<'a> RenderQueue<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_debug_view(mut self, debug_view: &'a DebugView) -> Self {
+        self.debug_view = Some(debug_view);
+        self
+    }
+
+    pub fn with_frustum(mut self, frustum: Frustum) -> Self {
+        self.frustum = Some(frustum);
+        self
+    }
+
+    pub fn push(&mut self, item: DrawItem<'a>) {
+        if self.frustum.is_some_and(|frustum| item.is_culled(&frustum)) {
+            self.culled += 1;
+            return;
+        }
+        match item.bucket {
+            RenderBucket::Opaque => self.opaque.push(item),
+            RenderBucket::AlphaTested => self.alpha_tested.push(item),
+            RenderBucket::Transparent => self.transparent.push(item),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.opaque.len() + self.alpha_tested.len() + self.transparent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of items dropped by the frustum since the queue was created.
+    pub fn culled(&self) -> usize {
+        self.culled
+    }
+
+    /// Draws the opaque and alpha tested buckets, front to back to make the most of early depth tests.
+    pub fn draw_opaque<S: Surface>(&mut self, target: &mut S, view: &glm::Mat4, render_states: &mut RenderStateCache) {
+        let debug_view = self.debug_view;
+        for bucket in [&mut self.opaque, &mut self.alpha_tested].iter_mut() {
+            sort_by_depth(bucket, view, true);
+            for item in bucket.drain(..) {
+                draw_item(target, item, debug_view, render_states);
+            }
+        }
+    }
+
+    /// Draws the transparent bucket back to front with the blending of each item.
+    pub fn draw_transparent<S: Surface>(&mut self, target: &mut S, view: &glm::Mat4, render_states: &mut RenderStateCache) {
+        sort_by_depth(&mut self.transparent, view, false);
+        for mut item in self.transparent.drain(..) {
+            item.uniforms.add("weightedOit", UniformValue::Bool(false));
+            draw_item(target, item, self.debug_view, render_states);
+        }
+    }
+
+    /// Accumulates the transparent bucket in `oit` against the opaque `depth`, then composites
+    /// the result over `target`. Order does not matter so nothing is sorted, except when a
+    /// debug shading replaces the transparent shaders.
+    pub fn draw_transparent_oit<S: Surface>(&mut self, display: &Display, target: &mut S, view: &glm::Mat4, oit: &WeightedOit, depth: &DepthStencilTexture2d, render_states: &mut RenderStateCache) {
+        if self.transparent.is_empty() {
+            return;
+        }
+        if self.debug_view.is_some_and(|debug_view| debug_view.replaces_shading()) {
+            return self.draw_transparent(target, view, render_states);
+        }
+        let mut items = Vec::with_capacity(self.transparent.len());
+        {
+            let mut accumulation = oit.framebuffer(display, depth);
+            // alpha starts at 1 as it holds the product of `1 - alpha` of the layers
+            accumulation.clear_color(0.0, 0.0, 0.0, 1.0);
+            let pass = RenderStateOverride::weighted_oit();
+            for mut item in self.transparent.drain(..) {
+                item.uniforms.add("weightedOit", UniformValue::Bool(true));
+                let params = render_states.get_with_override(&item.render_state, &pass);
+                item.draw(&mut accumulation, item.program, params);
+                items.push(item);
+            }
+        }
+        oit.composite(target);
+        if let Some(debug_view) = self.debug_view {
+            for item in items.iter() {
+                debug_view.draw_overlays(target, item, render_states);
+            }
+        }
+    }
+
+    /// Draws every bucket, transparent objects being sorted.
+    pub fn draw<S: Surface>(&mut self, target: &mut S, view: &glm::Mat4, render_states: &mut RenderStateCache) {
+        self.draw_opaque(target, view, render_states);
+        self.draw_transparent(target, view, render_states);
+    }
+}
+
+// view space looks down -z, a greater z is closer to the camera
+fn sort_by_depth(items: &mut [DrawItem], view: &glm::Mat4, front_to_back: bool) {
+    let depth = |item: &DrawItem| (view * glm::vec4(item.position.x, item.position.y, item.position.z, 1.0)).z;
+    items.sort_by(|a, b| {
+        let ordering = depth(a).partial_cmp(&depth(b)).unwrap_or(Ordering::Equal);
+        if front_to_back { ordering.reverse() } else { ordering }
+    });
+}
+
+/// Targets of the weighted blended order independent transparency (McGuire and Bavoil 2013).
+/// `accum` holds the weighted premultiplied colors and the revealage in alpha,
+/// `weight` the sum of the weighted alphas.
+///
+/// Transparent shaders write both outputs when `weightedOit` is set:
+/// `FragColor = vec4(color.rgb * color.a * w, color.a)` and `oitWeight = vec4(color.a * w)`.
+pub struct WeightedOit {
+    dimensions: (u32, u32),
+    accum: Texture2d,
+    weight: Texture2d,
+    program: Program,
+    quad: ScreenQuad,
+}
+
+impl WeightedOit {
+    pub fn new(display: &Display, width: u32, height: u32) -> Self {
+        let program = Program::from_source(
+            display,
+            include_str!("../../resources/shaders/screen_quad.vs.glsl"),
+            include_str!("../../resources/shaders/oit_composite.fs.glsl"),
+            None,
+        ).unwrap();
+        Self {
+            dimensions: (width, height),
+            accum: Texture2d::empty_with_format(display, UncompressedFloatFormat::F16F16F16F16, MipmapsOption::NoMipmap, width, height).unwrap(),
+            weight: Texture2d::empty_with_format(display, UncompressedFloatFormat::F16, MipmapsOption::NoMipmap, width, height).unwrap(),
+            program,
+            quad: ScreenQuad::new(display),
+        }
+    }
+
+    pub fn is_outdated(&self, dimensions: (u32, u32)) -> bool {
+        self.dimensions != dimensions
+    }
+
+    fn framebuffer<'a>(&'a self, display: &Display, depth: &'a DepthStencilTexture2d) -> MultiOutputFrameBuffer<'a> {
+        let outputs = [("FragColor", &self.accum), ("oitWeight", &self.weight)];
+        MultiOutputFrameBuffer::with_depth_stencil_buffer(display, outputs.iter().cloned(), depth).unwrap()
+    }
+
+    fn composite<S: Surface>(&self, target: &mut S) {
+        let uniforms = glium::uniform! {
+            accum: &self.accum,
+            weight: &self.weight,
+        };
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..DrawParameters::default()
+        };
+        self.quad.draw(target, &self.program, &uniforms, &params);
+    }
+}
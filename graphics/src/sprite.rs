@@ -0,0 +1,407 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use glium::{Display, GlObject, Program, Surface};
+use glium::index::PrimitiveType;
+use glium::texture::Texture2d;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+use math::glm;
+use serde::Deserialize;
+
+use crate::Colors;
+use crate::load_texture_file;
+use crate::render_state::{BlendMode, CullMode, DepthFunction, DepthState, RenderState, RenderStateCache};
+
+/// Part of a texture in normalized coordinates, `min` being the bottom left corner
+/// as textures are uploaded bottom row first.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextureRegion {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+    // size of the sprite in pixels
+    pub size: [f32; 2],
+    // the region is stored rotated 90° clockwise in the texture
+    pub rotated: bool,
+}
+
+impl TextureRegion {
+    pub fn full(texture: &Texture2d) -> Self {
+        Self {
+            min: [0.0, 0.0],
+            max: [1.0, 1.0],
+            size: [texture.width() as f32, texture.height() as f32],
+            rotated: false,
+        }
+    }
+
+    /// Region of `width` by `height` pixels at `x`, `y` from the top left corner of a
+    /// `texture_size` texture, as written by packing tools.
+    pub fn from_pixels(x: f32, y: f32, width: f32, height: f32, texture_size: [f32; 2], rotated: bool) -> Self {
+        // a rotated region covers its height horizontally in the texture
+        let (covered_width, covered_height) = if rotated { (height, width) } else { (width, height) };
+        Self {
+            min: [x / texture_size[0], 1.0 - (y + covered_height) / texture_size[1]],
+            max: [(x + covered_width) / texture_size[0], 1.0 - y / texture_size[1]],
+            size: [width, height],
+            rotated,
+        }
+    }
+
+    // texture coordinates of the top left, top right, bottom right and bottom left corners of the sprite
+    fn corners(&self) -> [[f32; 2]; 4] {
+        let (min, max) = (self.min, self.max);
+        let corners = [[min[0], max[1]], [max[0], max[1]], [max[0], min[1]], [min[0], min[1]]];
+        if self.rotated {
+            [corners[1], corners[2], corners[3], corners[0]]
+        } else {
+            corners
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Sprite<'a> {
+    pub texture: &'a Texture2d,
+    pub region: TextureRegion,
+    // pixels from the top left corner of the target, where `origin` is placed
+    pub position: [f32; 2],
+    // clockwise in radians around `origin`
+    pub rotation: f32,
+    pub scale: [f32; 2],
+    // pivot of the sprite, `[0.5, 0.5]` for its center
+    pub origin: [f32; 2],
+    pub tint: Colors,
+    // from 0 in front to 1 at the back
+    pub depth: f32,
+}
+
+impl<'a> Sprite<'a> {
+    pub fn new(texture: &'a Texture2d, region: TextureRegion) -> Self {
+        Self {
+            texture,
+            region,
+            position: [0.0, 0.0],
+            rotation: 0.0,
+            scale: [1.0, 1.0],
+            origin: [0.0, 0.0],
+            tint: Colors::WHITE,
+            depth: 0.0,
+        }
+    }
+
+    pub fn with_position(mut self, x: f32, y: f32) -> Self {
+        self.position = [x, y];
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, x: f32, y: f32) -> Self {
+        self.scale = [x, y];
+        self
+    }
+
+    pub fn with_origin(mut self, x: f32, y: f32) -> Self {
+        self.origin = [x, y];
+        self
+    }
+
+    pub fn with_tint(mut self, tint: Colors) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    fn vertexes(&self) -> [SpriteVertex; 4] {
+        let width = self.region.size[0] * self.scale[0];
+        let height = self.region.size[1] * self.scale[1];
+        let (sin, cos) = self.rotation.sin_cos();
+        let left = -self.origin[0] * width;
+        let top = -self.origin[1] * height;
+        let offsets = [[left, top], [left + width, top], [left + width, top + height], [left, top + height]];
+        let tex_coords = self.region.corners();
+        let color: [f32; 4] = self.tint.into();
+        let mut vertexes = [SpriteVertex { position: [0.0; 3], tex_coords: [0.0; 2], color }; 4];
+        for (i, vertex) in vertexes.iter_mut().enumerate() {
+            let [x, y] = offsets[i];
+            // y goes down on screen so this turns clockwise
+            vertex.position = [
+                self.position[0] + x * cos - y * sin,
+                self.position[1] + x * sin + y * cos,
+                -self.depth,
+            ];
+            vertex.tex_coords = tex_coords[i];
+        }
+        vertexes
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SpriteSortMode {
+    // back to front by depth then grouped by texture, for overlapping translucent sprites
+    #[default]
+    BackToFront,
+    // grouped by texture only, the fewest draw calls when sprites do not overlap
+    Texture,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct SpriteVertex {
+    position: [f32; 3],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+
+glium::implement_vertex!(SpriteVertex, position, tex_coords, color);
+
+/// Program and buffers shared by the `SpriteBatch` of every frame.
+pub struct SpriteRenderer {
+    program: Program,
+    vertexes: Vec<SpriteVertex>,
+    buffer: glium::VertexBuffer<SpriteVertex>,
+    indexes: glium::IndexBuffer<u32>,
+}
+
+impl SpriteRenderer {
+    pub fn new(display: &Display) -> Self {
+        let program = Program::from_source(
+            display,
+            include_str!("../../resources/shaders/sprite.vs.glsl"),
+            include_str!("../../resources/shaders/sprite.fs.glsl"),
+            None,
+        ).unwrap();
+        Self {
+            program,
+            vertexes: Vec::new(),
+            buffer: glium::VertexBuffer::empty_dynamic(display, 4 * 256).unwrap(),
+            indexes: quad_indexes(display, 256),
+        }
+    }
+
+    fn reserve(&mut self, display: &Display, sprites: usize) {
+        if sprites * 4 > self.buffer.len() {
+            let sprites = sprites.next_power_of_two();
+            self.buffer = glium::VertexBuffer::empty_dynamic(display, sprites * 4).unwrap();
+            self.indexes = quad_indexes(display, sprites);
+        }
+    }
+}
+
+fn quad_indexes(display: &Display, sprites: usize) -> glium::IndexBuffer<u32> {
+    let indexes = (0..sprites as u32)
+        .flat_map(|i| [0, 1, 2, 2, 3, 0].iter().map(move |corner| i * 4 + corner).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    glium::IndexBuffer::new(display, PrimitiveType::TrianglesList, &indexes).unwrap()
+}
+
+/// Sprites of a frame, drawn with one call per run of sprites sharing a texture.
+#[derive(Default)]
+pub struct SpriteBatch<'a> {
+    sprites: Vec<Sprite<'a>>,
+    pub sort_mode: SpriteSortMode,
+    // pixels with y going down from the top left corner of the target when `None`
+    pub projection: Option<glm::Mat4>,
+}
+
+impl<'a> SpriteBatch<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_sort_mode(mut self, sort_mode: SpriteSortMode) -> Self {
+        self.sort_mode = sort_mode;
+        self
+    }
+
+    pub fn with_projection(mut self, projection: glm::Mat4) -> Self {
+        self.projection = Some(projection);
+        self
+    }
+
+    pub fn push(&mut self, sprite: Sprite<'a>) {
+        self.sprites.push(sprite);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    /// Draws then empties the batch, returns the number of draw calls.
+    pub fn draw<S: Surface>(&mut self, display: &Display, target: &mut S, renderer: &mut SpriteRenderer, render_states: &mut RenderStateCache) -> usize {
+        if self.sprites.is_empty() {
+            return 0;
+        }
+        let depth = |sprite: &Sprite| sprite.depth;
+        match self.sort_mode {
+            SpriteSortMode::BackToFront => self.sprites.sort_by(|a, b| {
+                depth(b).partial_cmp(&depth(a)).unwrap_or(Ordering::Equal)
+                    .then(a.texture.get_id().cmp(&b.texture.get_id()))
+            }),
+            SpriteSortMode::Texture => self.sprites.sort_by_key(|sprite| sprite.texture.get_id()),
+        }
+
+        renderer.reserve(display, self.sprites.len());
+        renderer.vertexes.clear();
+        renderer.vertexes.extend(self.sprites.iter().flat_map(|sprite| sprite.vertexes().to_vec()));
+        renderer.buffer.slice(0..renderer.vertexes.len()).unwrap().write(&renderer.vertexes);
+
+        let (width, height) = target.get_dimensions();
+        let projection: [[f32; 4]; 4] = self.projection
+            .unwrap_or_else(|| glm::ortho(0.0, width as f32, height as f32, 0.0, -1.0, 1.0))
+            .into();
+        let state = RenderState {
+            blend: BlendMode::Alpha,
+            cull: CullMode::None,
+            depth: DepthState {
+                test: DepthFunction::Always,
+                write: false,
+            },
+            ..RenderState::default()
+        };
+        let params = render_states.get(&state);
+
+        let mut draw_calls = 0;
+        let mut start = 0;
+        while start < self.sprites.len() {
+            let texture = self.sprites[start].texture;
+            let count = self.sprites[start..].iter().take_while(|sprite| sprite.texture.get_id() == texture.get_id()).count();
+            let indexes = renderer.indexes.slice(start * 6..(start + count) * 6).unwrap();
+            let uniforms = glium::uniform! {
+                projection: projection,
+                sprite: texture.sampled()
+                    .magnify_filter(MagnifySamplerFilter::Linear)
+                    .minify_filter(MinifySamplerFilter::Linear),
+            };
+            target.draw(&renderer.buffer, indexes, &renderer.program, &uniforms, params).unwrap();
+            draw_calls += 1;
+            start += count;
+        }
+        self.sprites.clear();
+        draw_calls
+    }
+}
+
+#[derive(Debug)]
+pub enum TextureAtlasError {
+    Io(PathBuf, std::io::Error),
+    Parse(String),
+    Texture(PathBuf, image::ImageError),
+}
+
+impl fmt::Display for TextureAtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureAtlasError::Io(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            TextureAtlasError::Parse(err) => write!(f, "invalid atlas description: {}", err),
+            TextureAtlasError::Texture(path, err) => write!(f, "cannot load texture {}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for TextureAtlasError {}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct PackedRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PackedFrame {
+    // only set by the array layout
+    #[serde(default)]
+    filename: Option<String>,
+    frame: PackedRect,
+    #[serde(default)]
+    rotated: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum PackedFrames {
+    Hash(HashMap<String, PackedFrame>),
+    Array(Vec<PackedFrame>),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct PackedSize {
+    w: f32,
+    h: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PackedMeta {
+    image: String,
+    size: PackedSize,
+}
+
+/// JSON written by TexturePacker and compatible tools, in the hash or array layout.
+/// Trimmed frames are drawn at their trimmed size.
+#[derive(Debug, Clone, Deserialize)]
+struct PackingFile {
+    frames: PackedFrames,
+    meta: PackedMeta,
+}
+
+/// A texture and its named regions.
+pub struct TextureAtlas {
+    pub texture: Texture2d,
+    pub regions: HashMap<String, TextureRegion>,
+}
+
+impl TextureAtlas {
+    pub fn region(&self, name: &str) -> Option<TextureRegion> {
+        self.regions.get(name).copied()
+    }
+
+    pub fn sprite(&self, name: &str) -> Option<Sprite<'_>> {
+        self.region(name).map(|region| Sprite::new(&self.texture, region))
+    }
+}
+
+/// Loads the packing file at `path` and the image it names, relative to the file.
+pub fn load_texture_atlas<P: AsRef<Path>>(display: &Display, path: P) -> Result<TextureAtlas, TextureAtlasError> {
+    let path = path.as_ref();
+    let mut source = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut source))
+        .map_err(|err| TextureAtlasError::Io(path.to_path_buf(), err))?;
+    let packing: PackingFile = serde_json::from_str(&source).map_err(|err| TextureAtlasError::Parse(err.to_string()))?;
+
+    let image_path = path.parent().unwrap_or_else(|| Path::new(".")).join(&packing.meta.image);
+    let texture = load_texture_file(&image_path, display).map_err(|err| TextureAtlasError::Texture(image_path, err))?;
+
+    let texture_size = [packing.meta.size.w, packing.meta.size.h];
+    let region = |frame: &PackedFrame| {
+        let rect = frame.frame;
+        TextureRegion::from_pixels(rect.x, rect.y, rect.w, rect.h, texture_size, frame.rotated)
+    };
+    let regions = match packing.frames {
+        PackedFrames::Hash(frames) => frames.iter().map(|(name, frame)| (name.clone(), region(frame))).collect(),
+        PackedFrames::Array(frames) => frames.iter()
+            .map(|frame| {
+                frame.filename.clone()
+                    .map(|name| (name, region(frame)))
+                    .ok_or_else(|| TextureAtlasError::Parse("frame without filename".to_string()))
+            })
+            .collect::<Result<_, _>>()?,
+    };
+    Ok(TextureAtlas { texture, regions })
+}
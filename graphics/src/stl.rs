@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use math::glm;
+
+use crate::vertex::VertexNorm;
+
+const HEADER: &[u8] = b"binary STL";
+const HEADER_SIZE: usize = 80;
+// normal, 3 vertexes and the attribute byte count
+const TRIANGLE_SIZE: usize = 12 * 4 + 2;
+
+// normal and corners of a triangle as stored in the file
+type Facet = ([f32; 3], [[f32; 3]; 3]);
+
+#[derive(Debug)]
+pub enum StlError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, String),
+}
+
+impl fmt::Display for StlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StlError::Io(path, err) => write!(f, "cannot access {}: {}", path.display(), err),
+            StlError::Parse(path, message) => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl std::error::Error for StlError {}
+
+fn face_normal(positions: [glm::Vec3; 3]) -> glm::Vec3 {
+    let [a, b, c] = positions;
+    let normal = glm::cross(&(b - a), &(c - a));
+    if normal.norm() > f32::EPSILON { glm::normalize(&normal) } else { glm::Vec3::zeros() }
+}
+
+/// Writes an indexed triangle list as binary STL. The format only keeps positions, each
+/// triangle getting the normal of its face.
+pub fn write_stl<W: Write>(writer: &mut W, vertexes: &[VertexNorm], indexes: &[u32]) -> std::io::Result<()> {
+    // the header must not start with "solid", which would make readers expect ASCII
+    let mut header = [0u8; HEADER_SIZE];
+    header[..HEADER.len()].copy_from_slice(HEADER);
+    writer.write_all(&header)?;
+    let triangles = indexes.chunks(3).filter(|triangle| triangle.len() == 3);
+    writer.write_all(&(triangles.clone().count() as u32).to_le_bytes())?;
+    for triangle in triangles {
+        let positions = [0, 1, 2].map(|i| vertexes[triangle[i] as usize].position());
+        let normal = face_normal(positions.map(glm::Vec3::from));
+        for value in normal.iter().chain(positions.iter().flatten()) {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&[0, 0])?;
+    }
+    writer.flush()
+}
+
+pub fn save_stl<P: AsRef<Path>>(path: P, vertexes: &[VertexNorm], indexes: &[u32]) -> Result<(), StlError> {
+    let path = path.as_ref();
+    File::create(path)
+        .and_then(|file| write_stl(&mut BufWriter::new(file), vertexes, indexes))
+        .map_err(|err| StlError::Io(path.to_path_buf(), err))
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<Vec<Facet>, String> {
+    let count = u32::from_le_bytes(bytes[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap()) as usize;
+    let data = &bytes[HEADER_SIZE + 4..];
+    if data.len() < count * TRIANGLE_SIZE {
+        return Err(format!("{} triangles announced, {} found", count, data.len() / TRIANGLE_SIZE));
+    }
+    let triangles = data.chunks_exact(TRIANGLE_SIZE)
+        .take(count)
+        .map(|triangle| {
+            let float = |i: usize| f32::from_le_bytes(triangle[i * 4..i * 4 + 4].try_into().unwrap());
+            let vector = |i: usize| [float(i * 3), float(i * 3 + 1), float(i * 3 + 2)];
+            (vector(0), [vector(1), vector(2), vector(3)])
+        })
+        .collect();
+    Ok(triangles)
+}
+
+fn read_vector(tokens: &mut std::str::SplitWhitespace<'_>) -> Result<[f32; 3], String> {
+    let mut value = [0.0; 3];
+    for component in value.iter_mut() {
+        let token = tokens.next().ok_or("unexpected end of file")?;
+        *component = token.parse().map_err(|_| format!("invalid number '{}'", token))?;
+    }
+    Ok(value)
+}
+
+fn parse_ascii(source: &str) -> Result<Vec<Facet>, String> {
+    let mut tokens = source.split_whitespace();
+    let mut triangles = Vec::new();
+    let mut normal = [0.0; 3];
+    let mut corners = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            "normal" => normal = read_vector(&mut tokens)?,
+            "vertex" => corners.push(read_vector(&mut tokens)?),
+            // facets with more than 3 vertexes are triangulated as fans
+            "endfacet" => {
+                for i in 1..corners.len().saturating_sub(1) {
+                    triangles.push((normal, [corners[0], corners[i], corners[i + 1]]));
+                }
+                corners.clear();
+            }
+            _ => {}
+        }
+    }
+    Ok(triangles)
+}
+
+fn parse_stl(bytes: &[u8]) -> Result<(Vec<VertexNorm>, Vec<u32>), String> {
+    // ASCII files start with "solid" but so do some binary ones, the size tells them apart
+    let binary_size = |bytes: &[u8]| {
+        let count = u32::from_le_bytes(bytes[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap()) as usize;
+        HEADER_SIZE + 4 + count * TRIANGLE_SIZE
+    };
+    let triangles = if bytes.len() >= HEADER_SIZE + 4 && binary_size(bytes) == bytes.len() {
+        parse_binary(bytes)?
+    } else if bytes.starts_with(b"solid") {
+        parse_ascii(std::str::from_utf8(bytes).map_err(|_| "ASCII STL is not valid text")?)?
+    } else if bytes.len() >= HEADER_SIZE + 4 {
+        parse_binary(bytes)?
+    } else {
+        return Err("file too short".to_string());
+    };
+
+    // corners sharing a position and a normal become one vertex, so flat faces stay flat
+    let mut unique = HashMap::new();
+    let mut vertexes = Vec::new();
+    let mut indexes = Vec::with_capacity(triangles.len() * 3);
+    for (normal, positions) in triangles.iter() {
+        let normal = if glm::Vec3::from(*normal).norm() > f32::EPSILON {
+            *normal
+        } else {
+            face_normal(positions.map(glm::Vec3::from)).into()
+        };
+        for position in positions.iter() {
+            let key = (position.map(f32::to_bits), normal.map(f32::to_bits));
+            let index = *unique.entry(key).or_insert_with(|| {
+                let [x, y, z] = *position;
+                vertexes.push(VertexNorm::new(x, y, z, normal, [0.0, 0.0]));
+                vertexes.len() as u32 - 1
+            });
+            indexes.push(index);
+        }
+    }
+    Ok((vertexes, indexes))
+}
+
+/// Reads a binary or ASCII STL file. Vertexes get the normal of their facet and no texture
+/// coordinates.
+pub fn load_stl<P: AsRef<Path>>(path: P) -> Result<(Vec<VertexNorm>, Vec<u32>), StlError> {
+    let path = path.as_ref();
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|err| StlError::Io(path.to_path_buf(), err))?;
+    parse_stl(&bytes).map_err(|message| StlError::Parse(path.to_path_buf(), message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_keeps_positions_with_face_normals() {
+        // square pyramid, the base split in two coplanar triangles
+        let positions = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 0.0, 1.0], [0.0, 0.0, 1.0], [0.5, 0.7, 0.5]];
+        let vertexes = positions.iter()
+            .map(|[x, y, z]| VertexNorm::new(*x, *y, *z, [0.0, 1.0, 0.0], [0.3, 0.6]))
+            .collect::<Vec<_>>();
+        let indexes = vec![0, 1, 2, 0, 2, 3, 0, 4, 1, 1, 4, 2, 2, 4, 3, 3, 4, 0];
+        let path = std::env::temp_dir().join(format!("stl_round_trip_{}.stl", std::process::id()));
+        save_stl(&path, &vertexes, &indexes).unwrap();
+        let loaded = load_stl(&path);
+        std::fs::remove_file(&path).unwrap();
+        let (loaded_vertexes, loaded_indexes) = loaded.unwrap();
+
+        assert_eq!(loaded_indexes.len(), indexes.len());
+        for (triangle, loaded) in indexes.chunks(3).zip(loaded_indexes.chunks(3)) {
+            let expected = triangle.iter().map(|i| vertexes[*i as usize].position()).collect::<Vec<_>>();
+            let corners = loaded.iter().map(|i| &loaded_vertexes[*i as usize]).collect::<Vec<_>>();
+            assert_eq!(corners.iter().map(|vertex| vertex.position()).collect::<Vec<_>>(), expected);
+            let normal: [f32; 3] = face_normal([0, 1, 2].map(|i| glm::Vec3::from(expected[i]))).into();
+            for vertex in corners.iter() {
+                assert_eq!(vertex.normal(), normal);
+                assert_eq!(vertex.tex_coords(), [0.0, 0.0]);
+            }
+        }
+        // the base corners are shared by its two triangles, the sides each have their own
+        assert_eq!(loaded_vertexes.len(), 4 + 4 * 3);
+        assert_eq!(loaded_vertexes[0].normal(), [0.0, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn reads_ascii() {
+        let source = b"solid quad\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 1 1 0\nvertex 0 1 0\nendloop\nendfacet\nendsolid quad\n";
+        let (vertexes, indexes) = parse_stl(source).unwrap();
+        assert_eq!(indexes, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(vertexes.iter().map(|vertex| vertex.position()).collect::<Vec<_>>(), vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]);
+        assert!(vertexes.iter().all(|vertex| vertex.normal() == [0.0, 0.0, 1.0]));
+    }
+}
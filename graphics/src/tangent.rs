@@ -0,0 +1,82 @@
+use math::glm;
+use math::glm::{vec2, vec3, Vec3};
+
+use crate::vertex::{VertexNorm, VertexNormTan};
+
+/// Generates per vertex tangents for an indexed triangle list, MikkTSpace style:
+/// each triangle contributes its UV aligned tangent frame weighted by the corner angle,
+/// the accumulated tangent is then orthogonalized against the vertex normal and the
+/// bitangent handedness is stored in the tangent `w` component.
+pub fn generate_tangents<I: Copy + Into<u32>>(vertexes: &[VertexNorm], indexes: &[I]) -> Vec<VertexNormTan> {
+    let mut tangents = vec![Vec3::zeros(); vertexes.len()];
+    let mut bitangents = vec![Vec3::zeros(); vertexes.len()];
+
+    for triangle in indexes.chunks_exact(3) {
+        let ids = [triangle[0].into() as usize, triangle[1].into() as usize, triangle[2].into() as usize];
+        let p = ids.map(|i| glm::make_vec3(&vertexes[i].position()));
+        let uv = ids.map(|i| {
+            let tex = vertexes[i].tex_coords();
+            vec2(tex[0], tex[1])
+        });
+
+        let edge1 = p[1] - p[0];
+        let edge2 = p[2] - p[0];
+        let delta_uv1 = uv[1] - uv[0];
+        let delta_uv2 = uv[2] - uv[0];
+        let det = delta_uv1.x * delta_uv2.y - delta_uv2.x * delta_uv1.y;
+        if det.abs() < f32::EPSILON {
+            // degenerate UV mapping, the fallback frame is built per vertex below
+            continue;
+        }
+        let r = 1.0 / det;
+        let tangent = (edge1 * delta_uv2.y - edge2 * delta_uv1.y) * r;
+        let bitangent = (edge2 * delta_uv1.x - edge1 * delta_uv2.x) * r;
+
+        for corner in 0..3 {
+            let a = p[(corner + 1) % 3] - p[corner];
+            let b = p[(corner + 2) % 3] - p[corner];
+            let weight = if a.norm() > 0.0 && b.norm() > 0.0 {
+                glm::angle(&a, &b)
+            } else {
+                0.0
+            };
+            tangents[ids[corner]] += tangent * weight;
+            bitangents[ids[corner]] += bitangent * weight;
+        }
+    }
+
+    vertexes.iter()
+        .enumerate()
+        .map(|(i, vertex)| {
+            let normal = glm::make_vec3(&vertex.normal()).normalize();
+            // Gram-Schmidt orthogonalization against the normal
+            let mut tangent = tangents[i] - normal * normal.dot(&tangents[i]);
+            if tangent.norm() < 1e-6 {
+                tangent = any_orthogonal(&normal);
+            }
+            let tangent = tangent.normalize();
+            let sign = if normal.cross(&tangent).dot(&bitangents[i]) < 0.0 { -1.0 } else { 1.0 };
+            VertexNormTan::from_vertex(vertex, [tangent.x, tangent.y, tangent.z, sign])
+        })
+        .collect()
+}
+
+fn any_orthogonal(normal: &Vec3) -> Vec3 {
+    let axis = if normal.x.abs() < 0.9 { vec3(1.0, 0.0, 0.0) } else { vec3(0.0, 1.0, 0.0) };
+    axis - normal * normal.dot(&axis)
+}
+
+/// Area weighted average of the normals of the triangles around each vertex.
+pub(crate) fn smooth_normals(positions: &[[f32; 3]], indexes: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::zeros(); positions.len()];
+    for triangle in indexes.chunks(3).filter(|triangle| triangle.len() == 3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(positions[triangle[i] as usize]));
+        let face = glm::cross(&(b - a), &(c - a));
+        for index in triangle.iter() {
+            normals[*index as usize] += face;
+        }
+    }
+    normals.iter()
+        .map(|normal| if normal.norm() > f32::EPSILON { glm::normalize(normal).into() } else { [0.0, 1.0, 0.0] })
+        .collect()
+}
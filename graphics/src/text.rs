@@ -0,0 +1,301 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use glium::{Display, Program, Rect, Surface};
+use glium::index::{NoIndices, PrimitiveType};
+use glium::texture::{ClientFormat, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+use math::glm;
+use rusttype::{point, PositionedGlyph};
+
+use crate::font::{Font, layout_text, TextStyle};
+use crate::render_state::{BlendMode, CullMode, DepthFunction, DepthState, RenderState, RenderStateCache};
+
+const ATLAS_SIZE: u32 = 512;
+const ATLAS_MAX_SIZE: u32 = 4096;
+// glyphs are rasterized at a quarter pixel precision
+const SUBPIXEL_STEPS: f32 = 4.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct FontId(usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font: usize,
+    glyph: u16,
+    size: u32,
+    offset: (u8, u8),
+}
+
+#[derive(Clone, Copy)]
+struct AtlasGlyph {
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    // pixel bounds relative to the rounded glyph position
+    min: [i32; 2],
+    max: [i32; 2],
+}
+
+struct AtlasFull;
+
+/// Glyph bitmaps packed in rows into a single channel texture.
+struct GlyphAtlas {
+    texture: Texture2d,
+    size: u32,
+    cursor: (u32, u32),
+    row_height: u32,
+    // `None` for glyphs without pixels, like spaces
+    glyphs: HashMap<GlyphKey, Option<AtlasGlyph>>,
+}
+
+impl GlyphAtlas {
+    fn new(display: &Display, size: u32) -> Self {
+        Self {
+            texture: Texture2d::empty_with_format(display, UncompressedFloatFormat::U8, MipmapsOption::NoMipmap, size, size).unwrap(),
+            size,
+            cursor: (0, 0),
+            row_height: 0,
+            glyphs: HashMap::new(),
+        }
+    }
+
+    fn get_or_insert(&mut self, key: GlyphKey, glyph: &PositionedGlyph<'static>) -> Result<Option<AtlasGlyph>, AtlasFull> {
+        if let Some(entry) = self.glyphs.get(&key) {
+            return Ok(*entry);
+        }
+        let offset = point(key.offset.0 as f32 / SUBPIXEL_STEPS, key.offset.1 as f32 / SUBPIXEL_STEPS);
+        let glyph = glyph.unpositioned().clone().positioned(offset);
+        let bounds = match glyph.pixel_bounding_box() {
+            Some(bounds) => bounds,
+            None => {
+                self.glyphs.insert(key, None);
+                return Ok(None);
+            }
+        };
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        // one pixel of padding avoids bleeding with linear filtering
+        if self.cursor.0 + width + 1 > self.size {
+            self.cursor = (0, self.cursor.1 + self.row_height + 1);
+            self.row_height = 0;
+        }
+        if width + 1 > self.size || self.cursor.1 + height + 1 > self.size {
+            return Err(AtlasFull);
+        }
+        let mut pixels = vec![0u8; (width * height) as usize];
+        glyph.draw(|x, y, coverage| pixels[(y * width + x) as usize] = (coverage * 255.0) as u8);
+        let (left, top) = self.cursor;
+        self.texture.write(
+            Rect { left, bottom: top, width, height },
+            RawImage2d { data: Cow::Owned(pixels), width, height, format: ClientFormat::U8 },
+        );
+        self.cursor.0 += width + 1;
+        self.row_height = self.row_height.max(height);
+
+        let size = self.size as f32;
+        let entry = AtlasGlyph {
+            uv_min: [left as f32 / size, top as f32 / size],
+            uv_max: [(left + width) as f32 / size, (top + height) as f32 / size],
+            min: [bounds.min.x, bounds.min.y],
+            max: [bounds.max.x, bounds.max.y],
+        };
+        self.glyphs.insert(key, Some(entry));
+        Ok(Some(entry))
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct TextVertex {
+    position: [f32; 3],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+
+glium::implement_vertex!(TextVertex, position, tex_coords, color);
+
+struct QueuedText {
+    font: FontId,
+    text: String,
+    style: TextStyle,
+    anchor: TextAnchor,
+}
+
+#[derive(Clone, Copy)]
+enum TextAnchor {
+    // pixels from the top left corner of the target
+    Screen([f32; 2]),
+    // billboard facing the camera, `scale` world units per pixel of the style
+    World { position: glm::Vec3, scale: f32 },
+}
+
+/// Batches the strings of a frame, rasterizing their glyphs on demand in a shared atlas
+/// which grows, or is rebuilt once at its maximum size, when it runs out of space.
+pub struct TextRenderer {
+    fonts: Vec<Font>,
+    atlas: GlyphAtlas,
+    program: Program,
+    queued: Vec<QueuedText>,
+    vertexes: Vec<TextVertex>,
+    buffer: glium::VertexBuffer<TextVertex>,
+}
+
+impl TextRenderer {
+    pub fn new(display: &Display) -> Self {
+        let program = Program::from_source(
+            display,
+            include_str!("../../resources/shaders/text.vs.glsl"),
+            include_str!("../../resources/shaders/text.fs.glsl"),
+            None,
+        ).unwrap();
+        Self {
+            fonts: Vec::new(),
+            atlas: GlyphAtlas::new(display, ATLAS_SIZE),
+            program,
+            queued: Vec::new(),
+            vertexes: Vec::new(),
+            buffer: glium::VertexBuffer::empty_dynamic(display, 6 * 256).unwrap(),
+        }
+    }
+
+    pub fn add_font(&mut self, font: Font) -> FontId {
+        self.fonts.push(font);
+        FontId(self.fonts.len() - 1)
+    }
+
+    pub fn font(&self, id: FontId) -> &Font {
+        &self.fonts[id.0]
+    }
+
+    /// Queues `text` with its origin `x` and `y` pixels from the top left corner.
+    pub fn queue_screen(&mut self, font: FontId, text: &str, x: f32, y: f32, style: &TextStyle) {
+        self.queue(font, text, style, TextAnchor::Screen([x, y]));
+    }
+
+    /// Queues `text` facing the camera at `position`, a pixel of the style measuring `scale` world units.
+    pub fn queue_world(&mut self, font: FontId, text: &str, position: glm::Vec3, scale: f32, style: &TextStyle) {
+        self.queue(font, text, style, TextAnchor::World { position, scale });
+    }
+
+    /// Draws the world space texts, tested against the depth of `target`.
+    pub fn draw_world<S: Surface>(&mut self, display: &Display, target: &mut S, view: &glm::Mat4, view_projection: &glm::Mat4, render_states: &mut RenderStateCache) {
+        let right = glm::vec3(view[(0, 0)], view[(0, 1)], view[(0, 2)]);
+        let up = glm::vec3(view[(1, 0)], view[(1, 1)], view[(1, 2)]);
+        let texts = self.take_queued(|anchor| matches!(anchor, TextAnchor::World { .. }));
+        self.build(display, &texts, |anchor, x, y| match anchor {
+            TextAnchor::World { position, scale } => (position + right * (x * scale) - up * (y * scale)).into(),
+            TextAnchor::Screen(_) => unreachable!(),
+        });
+        let depth = DepthState {
+            test: DepthFunction::LessOrEqual,
+            write: false,
+        };
+        self.submit(target, view_projection, depth, render_states);
+    }
+
+    /// Draws the screen space texts over `target`.
+    pub fn draw_screen<S: Surface>(&mut self, display: &Display, target: &mut S, render_states: &mut RenderStateCache) {
+        let (width, height) = target.get_dimensions();
+        let texts = self.take_queued(|anchor| matches!(anchor, TextAnchor::Screen(_)));
+        self.build(display, &texts, |anchor, x, y| match anchor {
+            TextAnchor::Screen([left, top]) => [left + x, top + y, 0.0],
+            TextAnchor::World { .. } => unreachable!(),
+        });
+        let projection = glm::ortho(0.0, width as f32, height as f32, 0.0, -1.0, 1.0);
+        let depth = DepthState {
+            test: DepthFunction::Always,
+            write: false,
+        };
+        self.submit(target, &projection, depth, render_states);
+    }
+
+    fn queue(&mut self, font: FontId, text: &str, style: &TextStyle, anchor: TextAnchor) {
+        self.queued.push(QueuedText {
+            font,
+            text: text.to_string(),
+            style: *style,
+            anchor,
+        });
+    }
+
+    fn take_queued<F: Fn(&TextAnchor) -> bool>(&mut self, filter: F) -> Vec<QueuedText> {
+        let (taken, kept) = self.queued.drain(..).partition(|text| filter(&text.anchor));
+        self.queued = kept;
+        taken
+    }
+
+    fn build<P: Fn(TextAnchor, f32, f32) -> [f32; 3]>(&mut self, display: &Display, texts: &[QueuedText], position: P) {
+        // a full atlas is grown, or emptied once it reached its maximum size, then the batch is rebuilt
+        let mut reallocated = false;
+        loop {
+            if self.try_build(texts, &position).is_ok() {
+                return;
+            }
+            if reallocated && self.atlas.size == ATLAS_MAX_SIZE {
+                // the batch does not fit in an empty atlas of the maximum size, nothing is drawn
+                self.vertexes.clear();
+                return;
+            }
+            let size = (self.atlas.size * 2).min(ATLAS_MAX_SIZE);
+            self.atlas = GlyphAtlas::new(display, size);
+            reallocated = true;
+        }
+    }
+
+    fn try_build<P: Fn(TextAnchor, f32, f32) -> [f32; 3]>(&mut self, texts: &[QueuedText], position: &P) -> Result<(), AtlasFull> {
+        self.vertexes.clear();
+        for text in texts.iter() {
+            let layout = layout_text(&self.fonts[text.font.0], &text.text, &text.style);
+            let color = text.style.color;
+            for glyph in layout.glyphs.iter() {
+                let origin = glyph.position();
+                let (x, y) = (origin.x.floor(), origin.y.floor());
+                let key = GlyphKey {
+                    font: text.font.0,
+                    glyph: glyph.id().0,
+                    size: text.style.size.to_bits(),
+                    offset: (((origin.x - x) * SUBPIXEL_STEPS) as u8, ((origin.y - y) * SUBPIXEL_STEPS) as u8),
+                };
+                let entry = match self.atlas.get_or_insert(key, glyph)? {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+                let corner = |cx: usize, cy: usize| TextVertex {
+                    position: position(
+                        text.anchor,
+                        x + [entry.min[0], entry.max[0]][cx] as f32,
+                        y + [entry.min[1], entry.max[1]][cy] as f32,
+                    ),
+                    tex_coords: [[entry.uv_min[0], entry.uv_max[0]][cx], [entry.uv_min[1], entry.uv_max[1]][cy]],
+                    color,
+                };
+                let quad = [corner(0, 0), corner(1, 0), corner(1, 1), corner(1, 1), corner(0, 1), corner(0, 0)];
+                self.vertexes.extend_from_slice(&quad);
+            }
+        }
+        Ok(())
+    }
+
+    fn submit<S: Surface>(&mut self, target: &mut S, transform: &glm::Mat4, depth: DepthState, render_states: &mut RenderStateCache) {
+        if self.vertexes.is_empty() {
+            return;
+        }
+        if self.vertexes.len() > self.buffer.len() {
+            self.buffer = glium::VertexBuffer::empty_dynamic(self.buffer.get_context(), self.vertexes.len().next_power_of_two()).unwrap();
+        }
+        let slice = self.buffer.slice(0..self.vertexes.len()).unwrap();
+        slice.write(&self.vertexes);
+        let transform: [[f32; 4]; 4] = (*transform).into();
+        let uniforms = glium::uniform! {
+            transform: transform,
+            atlas: self.atlas.texture.sampled()
+                .magnify_filter(MagnifySamplerFilter::Linear)
+                .minify_filter(MinifySamplerFilter::Linear),
+        };
+        let state = RenderState {
+            blend: BlendMode::Alpha,
+            cull: CullMode::None,
+            depth,
+            ..RenderState::default()
+        };
+        target.draw(slice, NoIndices(PrimitiveType::TrianglesList), &self.program, &uniforms, render_states.get(&state)).unwrap();
+    }
+}
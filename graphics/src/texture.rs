@@ -48,4 +48,13 @@ pub fn load_tif_texture(
         .unwrap()
         .to_rgba8();
     load_texture(image, display)
-}
\ No newline at end of file
+}
+
+// picks the decoder from the file extension
+pub fn load_texture_file<P: AsRef<std::path::Path>>(
+    file_path: P,
+    display: &Display,
+) -> Result<glium::texture::Texture2d, image::ImageError> {
+    let image = image::open(file_path)?.to_rgba8();
+    Ok(load_texture(image, display).unwrap())
+}
@@ -51,6 +51,65 @@ impl VertexNorm {
             tex_coords,
         }
     }
+
+    pub fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    pub fn normal(&self) -> [f32; 3] {
+        self.normal
+    }
+
+    pub fn tex_coords(&self) -> [f32; 2] {
+        self.tex_coords
+    }
 }
 
 glium::implement_vertex!(VertexNorm, position, normal, tex_coords);
+
+#[derive(Copy, Clone, Debug)]
+pub struct VertexNormTan {
+    position: [f32; 3],
+    normal: [f32; 3],
+    tex_coords: [f32; 2],
+    // xyz is the tangent, w the bitangent sign so that bitangent = w * cross(normal, tangent)
+    tangent: [f32; 4],
+}
+
+impl VertexNormTan {
+    pub fn new(x: f32, y: f32, z: f32, normal: [f32; 3], tex_coords: [f32; 2], tangent: [f32; 4]) -> Self {
+        Self {
+            position: [x, y, z],
+            normal,
+            tex_coords,
+            tangent,
+        }
+    }
+
+    pub fn from_vertex(vertex: &VertexNorm, tangent: [f32; 4]) -> Self {
+        Self {
+            position: vertex.position,
+            normal: vertex.normal,
+            tex_coords: vertex.tex_coords,
+            tangent,
+        }
+    }
+
+    pub fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    pub fn normal(&self) -> [f32; 3] {
+        self.normal
+    }
+
+    pub fn tex_coords(&self) -> [f32; 2] {
+        self.tex_coords
+    }
+
+    pub fn tangent(&self) -> [f32; 4] {
+        self.tangent
+    }
+}
+
+glium::implement_vertex!(VertexNormTan, position, normal, tex_coords, tangent);
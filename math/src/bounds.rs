@@ -0,0 +1,131 @@
+use crate::glm;
+
+/// Axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: glm::Vec3, max: glm::Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Smallest box holding every point, `None` without points.
+    pub fn from_points<I: IntoIterator<Item = glm::Vec3>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Self::new(first, first), |aabb, point| Self {
+            min: glm::min2(&aabb.min, &point),
+            max: glm::max2(&aabb.max, &point),
+        }))
+    }
+
+    pub fn center(&self) -> glm::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> glm::Vec3 {
+        self.max - self.min
+    }
+
+    /// Radius of the sphere around the center holding the whole box.
+    pub fn radius(&self) -> f32 {
+        glm::length(&self.size()) * 0.5
+    }
+
+    pub fn bounding_sphere(&self) -> Sphere {
+        Sphere::new(self.center(), self.radius())
+    }
+
+    /// Smallest box holding both boxes.
+    pub fn merge(&self, other: &Aabb) -> Self {
+        Self::new(glm::min2(&self.min, &other.min), glm::max2(&self.max, &other.max))
+    }
+
+    /// Axis aligned box holding this one once transformed, usually by a `Transform`.
+    pub fn transform(&self, matrix: &glm::Mat4) -> Self {
+        // each output axis takes the smaller and the greater product of each input axis (Arvo 1990)
+        let translation = matrix.column(3).xyz();
+        let (mut min, mut max) = (translation, translation);
+        for column in 0..3 {
+            for row in 0..3 {
+                let a = matrix[(row, column)] * self.min[column];
+                let b = matrix[(row, column)] * self.max[column];
+                min[row] += a.min(b);
+                max[row] += a.max(b);
+            }
+        }
+        Self::new(min, max)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sphere {
+    pub center: glm::Vec3,
+    pub radius: f32,
+}
+
+impl Sphere {
+    pub fn new(center: glm::Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    /// Sphere around the center of the points' bounds reaching the farthest point, a bit larger
+    /// than the smallest enclosing sphere. `None` without points.
+    pub fn from_points<I>(points: I) -> Option<Self> where I: IntoIterator<Item = glm::Vec3>, I::IntoIter: Clone {
+        let points = points.into_iter();
+        let center = Aabb::from_points(points.clone())?.center();
+        let radius = points.map(|point| glm::distance(&center, &point)).fold(0.0, f32::max);
+        Some(Self::new(center, radius))
+    }
+
+    /// Sphere holding this one once transformed, the radius growing with the largest scale.
+    pub fn transform(&self, matrix: &glm::Mat4) -> Self {
+        let center = (matrix * self.center.push(1.0)).xyz();
+        let scale = (0..3).map(|i| matrix.column(i).xyz().norm()).fold(0.0, f32::max);
+        Self::new(center, self.radius * scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+
+    use super::*;
+
+    fn assert_near(a: &glm::Vec3, b: &glm::Vec3) {
+        assert!(glm::distance(a, b) < 1e-5, "{} != {}", a, b);
+    }
+
+    fn corners(aabb: &Aabb) -> Vec<glm::Vec3> {
+        (0..8)
+            .map(|i| glm::vec3(
+                if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+            ))
+            .collect()
+    }
+
+    #[test]
+    fn transform_holds_the_rotated_corners() {
+        let aabb = Aabb::new(glm::vec3(-1.0, -1.0, -1.0), glm::vec3(1.0, 1.0, 1.0));
+        let rotation = glm::rotation(FRAC_PI_4, &glm::vec3(0.0, 1.0, 0.0));
+        let rotated = aabb.transform(&rotation);
+        let half = 2f32.sqrt();
+        assert_near(&rotated.min, &glm::vec3(-half, -1.0, -half));
+        assert_near(&rotated.max, &glm::vec3(half, 1.0, half));
+
+        // the transformed box is the box of the transformed corners
+        let aabb = Aabb::new(glm::vec3(-0.5, 0.0, 1.0), glm::vec3(2.0, 1.5, 3.0));
+        let matrix = glm::translation(&glm::vec3(3.0, -2.0, 1.0))
+            * glm::rotation(0.7, &glm::normalize(&glm::vec3(1.0, 2.0, -0.5)))
+            * glm::scaling(&glm::vec3(2.0, 0.5, 1.0));
+        let expected = Aabb::from_points(corners(&aabb).iter().map(|corner| (matrix * corner.push(1.0)).xyz())).unwrap();
+        let transformed = aabb.transform(&matrix);
+        assert_near(&transformed.min, &expected.min);
+        assert_near(&transformed.max, &expected.max);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{Aabb, glm, Sphere};
+
+/// Plane of the points `p` where `normal · p + distance = 0`, the normal pointing to the
+/// positive half space.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Plane {
+    pub normal: glm::Vec3,
+    pub distance: f32,
+}
+
+impl Plane {
+    pub fn new(normal: glm::Vec3, distance: f32) -> Self {
+        Self { normal, distance }
+    }
+
+    /// Plane of the equation `ax + by + cz + d = 0`, normalized so signed distances are in world
+    /// units. Degenerate coefficients are kept as they are.
+    pub fn from_coefficients(coefficients: &glm::Vec4) -> Self {
+        let normal = coefficients.xyz();
+        let length = normal.norm();
+        if length > f32::EPSILON {
+            Self::new(normal / length, coefficients.w / length)
+        } else {
+            Self::new(normal, coefficients.w)
+        }
+    }
+
+    pub fn signed_distance(&self, point: &glm::Vec3) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+/// Volume seen by a camera, bounded by six planes facing inwards.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Frustum {
+    // left, right, bottom, top, near, far
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Planes of a view projection matrix (Gribb and Hartmann), in world space. With a reversed
+    /// infinite projection the near plane lands in the far slot and the near slot holds every
+    /// point in front of the camera.
+    pub fn from_matrix(view_projection: &glm::Mat4) -> Self {
+        let row = |i: usize| view_projection.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Self {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z].map(|plane| Plane::from_coefficients(&plane)),
+        }
+    }
+
+    pub fn contains_point(&self, point: &glm::Vec3) -> bool {
+        self.planes.iter().all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    /// Whether the sphere may be visible, spheres near the corners being kept conservatively.
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        self.planes.iter().all(|plane| plane.signed_distance(&sphere.center) >= -sphere.radius)
+    }
+
+    /// Whether the box may be visible, testing for each plane the corner farthest along its normal.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let corner = glm::vec3(
+                if plane.normal.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.normal.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.normal.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+            plane.signed_distance(&corner) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+    use crate::{InfiniteReversedPerspective, Perspective};
+
+    // camera at z = 5 looking at the origin, seeing as far sideways as ahead
+    fn view() -> glm::Mat4 {
+        glm::look_at(&glm::vec3(0.0, 0.0, 5.0), &glm::Vec3::zeros(), &glm::vec3(0.0, 1.0, 0.0))
+    }
+
+    fn frustum() -> Frustum {
+        let perspective = Perspective { aspect: 1.0, fov: FRAC_PI_2, near: 1.0, far: 10.0 };
+        Frustum::from_matrix(&(perspective.get() * view()))
+    }
+
+    fn assert_near(value: f32, expected: f32) {
+        assert!((value - expected).abs() < 1e-4, "{} != {}", value, expected);
+    }
+
+    #[test]
+    fn planes_are_normalized_and_ordered() {
+        let frustum = frustum();
+        assert!(frustum.planes.iter().all(|plane| (plane.normal.norm() - 1.0).abs() < 1e-5));
+        // near plane at z = 4 and far plane at z = -5, both facing inwards
+        assert_near(frustum.planes[4].signed_distance(&glm::vec3(0.0, 0.0, 4.5)), -0.5);
+        assert_near(frustum.planes[5].signed_distance(&glm::vec3(0.0, 0.0, -4.0)), 1.0);
+        assert_near(frustum.planes[0].signed_distance(&glm::vec3(-5.0, 0.0, 0.0)), 0.0);
+        assert_near(frustum.planes[3].signed_distance(&glm::vec3(0.0, 5.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn points() {
+        let frustum = frustum();
+        assert!(frustum.contains_point(&glm::Vec3::zeros()));
+        assert!(frustum.contains_point(&glm::vec3(4.9, -4.9, 0.0)));
+        assert!(!frustum.contains_point(&glm::vec3(5.1, 0.0, 0.0)));
+        // behind the camera, between it and the near plane, beyond the far plane
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, 6.0)));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, 4.5)));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, -5.5)));
+    }
+
+    #[test]
+    fn spheres() {
+        let frustum = frustum();
+        assert!(frustum.intersects_sphere(&Sphere::new(glm::Vec3::zeros(), 1.0)));
+        // 1 / sqrt(2) away from the right plane
+        assert!(!frustum.intersects_sphere(&Sphere::new(glm::vec3(6.0, 0.0, 0.0), 0.5)));
+        assert!(frustum.intersects_sphere(&Sphere::new(glm::vec3(6.0, 0.0, 0.0), 1.0)));
+        assert!(frustum.intersects_sphere(&Sphere::new(glm::vec3(0.0, 0.0, -5.5), 1.0)));
+        assert!(!frustum.intersects_sphere(&Sphere::new(glm::vec3(0.0, 0.0, 7.0), 1.0)));
+    }
+
+    #[test]
+    fn boxes() {
+        let frustum = frustum();
+        assert!(frustum.intersects_aabb(&Aabb::new(glm::vec3(-1.0, -1.0, -1.0), glm::vec3(1.0, 1.0, 1.0))));
+        // straddling the right plane and the far plane
+        assert!(frustum.intersects_aabb(&Aabb::new(glm::vec3(4.0, -1.0, -1.0), glm::vec3(6.0, 1.0, 1.0))));
+        assert!(frustum.intersects_aabb(&Aabb::new(glm::vec3(-1.0, -1.0, -7.0), glm::vec3(1.0, 1.0, -4.0))));
+        // enclosing the whole frustum
+        assert!(frustum.intersects_aabb(&Aabb::new(glm::vec3(-20.0, -20.0, -20.0), glm::vec3(20.0, 20.0, 20.0))));
+        assert!(!frustum.intersects_aabb(&Aabb::new(glm::vec3(10.0, -1.0, -1.0), glm::vec3(11.0, 1.0, 1.0))));
+        assert!(!frustum.intersects_aabb(&Aabb::new(glm::vec3(-1.0, -1.0, 6.0), glm::vec3(1.0, 1.0, 7.0))));
+    }
+
+    #[test]
+    fn infinite_reversed_projection_swaps_near_and_far() {
+        let perspective = InfiniteReversedPerspective { aspect: 1.0, fov: FRAC_PI_2, near: 1.0 };
+        let frustum = Frustum::from_matrix(&(perspective.get() * view()));
+        let (near_slot, far_slot) = (&frustum.planes[4], &frustum.planes[5]);
+        // the near slot holds every point in front of the camera, however far
+        for z in [4.5, 0.0, -1000.0].iter() {
+            assert!(near_slot.signed_distance(&glm::vec3(0.0, 0.0, *z)) >= 0.0);
+        }
+        assert!(near_slot.signed_distance(&glm::vec3(0.0, 0.0, 7.0)) < 0.0);
+        // the far slot is the real near plane, at z = 4
+        assert_near(far_slot.signed_distance(&glm::vec3(0.0, 0.0, 3.0)), 1.0);
+        assert_near(far_slot.signed_distance(&glm::vec3(0.0, 0.0, 4.5)), -0.5);
+
+        assert!(frustum.contains_point(&glm::vec3(0.0, 0.0, -1000.0)));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, 4.5)));
+        assert!(frustum.intersects_sphere(&Sphere::new(glm::vec3(0.0, 0.0, -1e4), 1.0)));
+        assert!(!frustum.intersects_aabb(&Aabb::new(glm::vec3(-1.0, -1.0, 6.0), glm::vec3(1.0, 1.0, 7.0))));
+    }
+}
@@ -1,8 +1,19 @@
 pub use nalgebra_glm as glm;
 pub use rand;
 
+mod bounds;
+mod projection;
+mod frustum;
+mod scene_graph;
+
+pub use bounds::*;
+pub use projection::*;
+pub use frustum::*;
+pub use scene_graph::*;
+
 pub type RawMat4 = [[f32; 4]; 4];
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Perspective{
     pub aspect: f32,
     pub fov: f32,
@@ -37,6 +48,16 @@ impl CameraSystem {
     pub fn view(&self) -> glm::Mat4 {
         glm::look_at(&self.pos, &(&self.pos + &self.front), &self.up)
     }
+
+    /// Projected height of a sphere as a fraction of the viewport height, 1 or more once the
+    /// camera gets inside it.
+    pub fn screen_size(&self, perspective: &Perspective, center: &glm::Vec3, radius: f32) -> f32 {
+        let distance = glm::distance(&self.pos, center);
+        if distance <= radius {
+            return 1.0;
+        }
+        radius / (distance * (perspective.fov / 2.0).tan())
+    }
 }
 
 impl Default for CameraSystem {
@@ -84,6 +105,10 @@ impl Transform {
         self.transform = glm::rotate(&self.transform, angle, axis);
     }
 
+    pub fn position(&self) -> glm::Vec3 {
+        glm::vec3(self.transform.m14, self.transform.m24, self.transform.m34)
+    }
+
     pub fn get(&self) -> &glm::Mat4 {
         &self.transform
     }
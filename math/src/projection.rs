@@ -0,0 +1,273 @@
+use crate::{glm, Perspective};
+
+/// Half line starting at `origin`, `direction` being normalized.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Ray {
+    pub origin: glm::Vec3,
+    pub direction: glm::Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: glm::Vec3, direction: glm::Vec3) -> Self {
+        Self {
+            origin,
+            direction: glm::normalize(&direction),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> glm::Vec3 {
+        self.origin + self.direction * distance
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Orthographic {
+    pub aspect: f32,
+    // visible height in world units, the width follows the aspect ratio
+    pub height: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Orthographic {
+    pub fn get(&self) -> glm::Mat4 {
+        let (half_width, half_height) = (self.height * self.aspect / 2.0, self.height / 2.0);
+        glm::ortho(-half_width, half_width, -half_height, half_height, self.near, self.far)
+    }
+}
+
+impl Default for Orthographic {
+    fn default() -> Self {
+        Self {
+            aspect: 1024. / 768.,
+            height: 10.0,
+            near: 0.1,
+            far: 100.0,
+        }
+    }
+}
+
+/// Perspective without far plane mapping the near plane to a depth of 1 and the infinity to 0,
+/// meant for a `[0, 1]` clip depth range and a floating point depth buffer.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct InfiniteReversedPerspective {
+    pub aspect: f32,
+    pub fov: f32,
+    pub near: f32,
+}
+
+impl InfiniteReversedPerspective {
+    pub fn get(&self) -> glm::Mat4 {
+        glm::reversed_infinite_perspective_rh_zo(self.aspect, self.fov, self.near)
+    }
+}
+
+impl Default for InfiniteReversedPerspective {
+    fn default() -> Self {
+        Self {
+            aspect: 1024. / 768.,
+            fov: std::f64::consts::FRAC_PI_4 as f32,
+            near: 0.1,
+        }
+    }
+}
+
+impl From<&Perspective> for InfiniteReversedPerspective {
+    fn from(perspective: &Perspective) -> Self {
+        Self {
+            aspect: perspective.aspect,
+            fov: perspective.fov,
+            near: perspective.near,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Projection {
+    Perspective(Perspective),
+    Orthographic(Orthographic),
+    InfiniteReversed(InfiniteReversedPerspective),
+}
+
+impl Projection {
+    pub fn get(&self) -> glm::Mat4 {
+        match self {
+            Projection::Perspective(perspective) => perspective.get(),
+            Projection::Orthographic(orthographic) => orthographic.get(),
+            Projection::InfiniteReversed(perspective) => perspective.get(),
+        }
+    }
+
+    pub fn inverse(&self) -> glm::Mat4 {
+        self.get().try_inverse().unwrap_or_else(glm::identity)
+    }
+
+    pub fn aspect(&self) -> f32 {
+        match self {
+            Projection::Perspective(perspective) => perspective.aspect,
+            Projection::Orthographic(orthographic) => orthographic.aspect,
+            Projection::InfiniteReversed(perspective) => perspective.aspect,
+        }
+    }
+
+    pub fn set_aspect(&mut self, aspect: f32) {
+        match self {
+            Projection::Perspective(perspective) => perspective.aspect = aspect,
+            Projection::Orthographic(orthographic) => orthographic.aspect = aspect,
+            Projection::InfiniteReversed(perspective) => perspective.aspect = aspect,
+        }
+    }
+
+    /// Follows the aspect ratio of a viewport of `width` by `height` pixels, ignoring empty ones
+    /// such as a minimized window.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.set_aspect(width as f32 / height as f32);
+        }
+    }
+
+    pub fn near(&self) -> f32 {
+        match self {
+            Projection::Perspective(perspective) => perspective.near,
+            Projection::Orthographic(orthographic) => orthographic.near,
+            Projection::InfiniteReversed(perspective) => perspective.near,
+        }
+    }
+
+    /// `f32::INFINITY` for the infinite projection.
+    pub fn far(&self) -> f32 {
+        match self {
+            Projection::Perspective(perspective) => perspective.far,
+            Projection::Orthographic(orthographic) => orthographic.far,
+            Projection::InfiniteReversed(_) => f32::INFINITY,
+        }
+    }
+
+    /// Vertical field of view in radians, `None` for the orthographic projection.
+    pub fn fov(&self) -> Option<f32> {
+        match self {
+            Projection::Perspective(perspective) => Some(perspective.fov),
+            Projection::Orthographic(_) => None,
+            Projection::InfiniteReversed(perspective) => Some(perspective.fov),
+        }
+    }
+
+    pub fn set_fov(&mut self, fov: f32) {
+        match self {
+            Projection::Perspective(perspective) => perspective.fov = fov,
+            Projection::Orthographic(_) => {}
+            Projection::InfiniteReversed(perspective) => perspective.fov = fov,
+        }
+    }
+
+    pub fn is_reversed_z(&self) -> bool {
+        matches!(self, Projection::InfiniteReversed(_))
+    }
+
+    /// Normalized device depth of the near plane and of a farther point, the far plane
+    /// of the infinite projection being out of reach.
+    fn ndc_depths(&self) -> (f32, f32) {
+        if self.is_reversed_z() { (1.0, 0.5) } else { (-1.0, 1.0) }
+    }
+
+    /// World position of a point in normalized device coordinates.
+    pub fn unproject(&self, ndc: &glm::Vec3, view: &glm::Mat4) -> glm::Vec3 {
+        let inverse = view.try_inverse().unwrap_or_else(glm::identity) * self.inverse();
+        let world = inverse * glm::vec4(ndc.x, ndc.y, ndc.z, 1.0);
+        world.xyz() / world.w
+    }
+
+    /// Ray through a point `x`, `y` pixels from the top left corner of a viewport of `dimensions`,
+    /// starting on the near plane.
+    pub fn screen_ray(&self, x: f32, y: f32, dimensions: (u32, u32), view: &glm::Mat4) -> Ray {
+        let ndc_x = x / dimensions.0 as f32 * 2.0 - 1.0;
+        let ndc_y = 1.0 - y / dimensions.1 as f32 * 2.0;
+        let (near, farther) = self.ndc_depths();
+        let origin = self.unproject(&glm::vec3(ndc_x, ndc_y, near), view);
+        let target = self.unproject(&glm::vec3(ndc_x, ndc_y, farther), view);
+        Ray::new(origin, target - origin)
+    }
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection::Perspective(Perspective::default())
+    }
+}
+
+impl From<Perspective> for Projection {
+    fn from(perspective: Perspective) -> Self {
+        Projection::Perspective(perspective)
+    }
+}
+
+impl From<Orthographic> for Projection {
+    fn from(orthographic: Orthographic) -> Self {
+        Projection::Orthographic(orthographic)
+    }
+}
+
+impl From<InfiniteReversedPerspective> for Projection {
+    fn from(perspective: InfiniteReversedPerspective) -> Self {
+        Projection::InfiniteReversed(perspective)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIMENSIONS: (u32, u32) = (800, 600);
+
+    fn view() -> glm::Mat4 {
+        glm::look_at(&glm::vec3(1.0, 2.0, 8.0), &glm::Vec3::zeros(), &glm::vec3(0.0, 1.0, 0.0))
+    }
+
+    fn projections() -> [Projection; 3] {
+        let aspect = DIMENSIONS.0 as f32 / DIMENSIONS.1 as f32;
+        [
+            Perspective { aspect, ..Perspective::default() }.into(),
+            Orthographic { aspect, ..Orthographic::default() }.into(),
+            InfiniteReversedPerspective { aspect, ..InfiniteReversedPerspective::default() }.into(),
+        ]
+    }
+
+    // pixel coordinates from the top left corner, as given to `screen_ray`
+    fn to_screen(projection: &Projection, point: &glm::Vec3) -> (f32, f32) {
+        let clip = projection.get() * view() * glm::vec4(point.x, point.y, point.z, 1.0);
+        let ndc = clip.xyz() / clip.w;
+        ((ndc.x + 1.0) / 2.0 * DIMENSIONS.0 as f32, (1.0 - ndc.y) / 2.0 * DIMENSIONS.1 as f32)
+    }
+
+    #[test]
+    fn screen_ray_passes_through_the_projected_point() {
+        let point = glm::vec3(0.5, -0.3, 1.0);
+        for projection in projections().iter() {
+            let (x, y) = to_screen(projection, &point);
+            let ray = projection.screen_ray(x, y, DIMENSIONS, &view());
+            let along = glm::dot(&(point - ray.origin), &ray.direction);
+            assert!(along > 0.0, "{:?} points away from the point", projection);
+            let distance = (ray.at(along) - point).norm();
+            assert!(distance < 1e-3, "{:?} misses the point by {}", projection, distance);
+        }
+    }
+
+    #[test]
+    fn screen_ray_starts_on_the_near_plane() {
+        for projection in projections().iter() {
+            let ray = projection.screen_ray(100.0, 450.0, DIMENSIONS, &view());
+            let view_depth = -(view() * glm::vec4(ray.origin.x, ray.origin.y, ray.origin.z, 1.0)).z;
+            assert!((view_depth - projection.near()).abs() < 1e-3, "{:?} starts at {}", projection, view_depth);
+        }
+    }
+
+    #[test]
+    fn unproject_inverts_the_projection() {
+        let point = glm::vec3(-1.5, 0.7, -3.0);
+        for projection in projections().iter() {
+            let clip = projection.get() * view() * glm::vec4(point.x, point.y, point.z, 1.0);
+            let unprojected = projection.unproject(&(clip.xyz() / clip.w), &view());
+            assert!((unprojected - point).norm() < 1e-3, "{:?} gives {}", projection, unprojected);
+        }
+    }
+}
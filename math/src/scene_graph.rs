@@ -0,0 +1,351 @@
+use std::cell::Cell;
+use std::fmt;
+
+use crate::glm;
+
+/// Local transform of a node as translation, rotation and scale, applied in reverse order.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Trs {
+    pub translation: glm::Vec3,
+    pub rotation: glm::Quat,
+    pub scale: glm::Vec3,
+}
+
+impl Trs {
+    pub fn new(translation: glm::Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::default()
+        }
+    }
+
+    /// Transform at `position` whose -z axis points along `front`, like the view of a camera.
+    pub fn looking(position: glm::Vec3, front: &glm::Vec3, up: &glm::Vec3) -> Self {
+        // inverse of the rotation of the matching view matrix
+        let view = glm::look_at(&glm::Vec3::zeros(), front, up);
+        Self::new(position).with_rotation(glm::quat_conjugate(&glm::to_quat(&view)))
+    }
+
+    pub fn with_rotation(mut self, rotation: glm::Quat) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, x: f32, y: f32, z: f32) -> Self {
+        self.scale = glm::vec3(x, y, z);
+        self
+    }
+
+    pub fn matrix(&self) -> glm::Mat4 {
+        let translation = glm::translation(&self.translation);
+        translation * glm::quat_to_mat4(&self.rotation) * glm::scaling(&self.scale)
+    }
+}
+
+impl Default for Trs {
+    fn default() -> Self {
+        Self {
+            translation: glm::Vec3::zeros(),
+            rotation: glm::quat_identity(),
+            scale: glm::vec3(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct NodeId(usize);
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Object placed by a node, as an index in the collection the application keeps for its kind.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Attachment {
+    Mesh(usize),
+    PointLight(usize),
+    SpotLight(usize),
+    Camera(usize),
+}
+
+#[derive(Debug)]
+pub enum SceneGraphError {
+    // the new parent is the node itself or one of its descendants
+    Cycle { node: NodeId, parent: NodeId },
+}
+
+impl fmt::Display for SceneGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneGraphError::Cycle { node, parent } => write!(f, "node {} cannot be a child of its descendant {}", node, parent),
+        }
+    }
+}
+
+impl std::error::Error for SceneGraphError {}
+
+#[derive(Debug)]
+struct Node {
+    name: Option<String>,
+    local: Trs,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    attachments: Vec<Attachment>,
+    // world matrix cache, a dirty node always has dirty descendants
+    world: Cell<glm::Mat4>,
+    dirty: Cell<bool>,
+}
+
+/// Hierarchy of nodes whose world matrix is the product of the local transforms from the root.
+/// World matrices are computed when asked for and cached until a local transform above changes.
+///
+/// Node ids are only meaningful for the graph that created them.
+#[derive(Debug, Default)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_node(&mut self, parent: Option<NodeId>, local: Trs) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            name: None,
+            local,
+            parent,
+            children: Vec::new(),
+            attachments: Vec::new(),
+            world: Cell::new(glm::identity()),
+            dirty: Cell::new(true),
+        });
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        id
+    }
+
+    fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.node(id).children
+    }
+
+    pub fn name(&self, id: NodeId) -> Option<&str> {
+        self.node(id).name.as_deref()
+    }
+
+    pub fn set_name(&mut self, id: NodeId, name: &str) {
+        self.node_mut(id).name = Some(name.to_string());
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|node| node.name.as_deref() == Some(name)).map(NodeId)
+    }
+
+    pub fn local(&self, id: NodeId) -> &Trs {
+        &self.node(id).local
+    }
+
+    pub fn set_local(&mut self, id: NodeId, local: Trs) {
+        self.node_mut(id).local = local;
+        self.invalidate(id);
+    }
+
+    pub fn set_translation(&mut self, id: NodeId, translation: glm::Vec3) {
+        self.node_mut(id).local.translation = translation;
+        self.invalidate(id);
+    }
+
+    pub fn set_rotation(&mut self, id: NodeId, rotation: glm::Quat) {
+        self.node_mut(id).local.rotation = rotation;
+        self.invalidate(id);
+    }
+
+    fn invalidate(&self, id: NodeId) {
+        let node = self.node(id);
+        if !node.dirty.replace(true) {
+            node.children.iter().for_each(|child| self.invalidate(*child));
+        }
+    }
+
+    /// Moves a node under `parent`, or to the roots with `None`, keeping its local transform so
+    /// it follows its new parent.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<(), SceneGraphError> {
+        if let Some(parent) = parent {
+            if parent == id || self.ancestors(parent).any(|ancestor| ancestor == id) {
+                return Err(SceneGraphError::Cycle { node: id, parent });
+            }
+        }
+        match self.node(id).parent {
+            Some(previous) => self.node_mut(previous).children.retain(|child| *child != id),
+            None => self.roots.retain(|root| *root != id),
+        }
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        self.node_mut(id).parent = parent;
+        self.invalidate(id);
+        Ok(())
+    }
+
+    /// Parent, grand parent and so on up to the root.
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.parent(id), move |ancestor| self.parent(*ancestor))
+    }
+
+    pub fn attach(&mut self, id: NodeId, attachment: Attachment) {
+        self.node_mut(id).attachments.push(attachment);
+    }
+
+    pub fn detach(&mut self, id: NodeId, attachment: Attachment) {
+        self.node_mut(id).attachments.retain(|attached| *attached != attachment);
+    }
+
+    pub fn attachments(&self, id: NodeId) -> &[Attachment] {
+        &self.node(id).attachments
+    }
+
+    /// Every attachment of the graph with its node.
+    pub fn attached(&self) -> impl Iterator<Item = (NodeId, Attachment)> + '_ {
+        self.nodes.iter().enumerate()
+            .flat_map(|(i, node)| node.attachments.iter().map(move |attachment| (NodeId(i), *attachment)))
+    }
+
+    pub fn world(&self, id: NodeId) -> glm::Mat4 {
+        let node = self.node(id);
+        if node.dirty.get() {
+            let local = node.local.matrix();
+            node.world.set(match node.parent {
+                Some(parent) => self.world(parent) * local,
+                None => local,
+            });
+            node.dirty.set(false);
+        }
+        node.world.get()
+    }
+
+    pub fn world_position(&self, id: NodeId) -> glm::Vec3 {
+        self.world(id).column(3).xyz()
+    }
+
+    /// Direction of the node's -z axis in world space, where cameras and spot lights look.
+    pub fn world_forward(&self, id: NodeId) -> glm::Vec3 {
+        glm::normalize(&(self.world(id) * glm::vec4(0.0, 0.0, -1.0, 0.0)).xyz())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_position(graph: &SceneGraph, id: NodeId, expected: glm::Vec3) {
+        let position = graph.world_position(id);
+        assert!((position - expected).norm() < 1e-5, "{} is at {} instead of {}", id, position, expected);
+    }
+
+    // root at x = 1, child 2 further and grandchild 4 further
+    fn chain() -> (SceneGraph, [NodeId; 3]) {
+        let mut graph = SceneGraph::new();
+        let root = graph.add_node(None, Trs::new(glm::vec3(1.0, 0.0, 0.0)));
+        let child = graph.add_node(Some(root), Trs::new(glm::vec3(2.0, 0.0, 0.0)));
+        let grandchild = graph.add_node(Some(child), Trs::new(glm::vec3(4.0, 0.0, 0.0)));
+        (graph, [root, child, grandchild])
+    }
+
+    #[test]
+    fn world_combines_the_ancestors() {
+        let (mut graph, [root, child, grandchild]) = chain();
+        graph.set_local(root, Trs::new(glm::vec3(1.0, 0.0, 0.0)).with_scale(2.0, 2.0, 2.0));
+        assert_position(&graph, child, glm::vec3(5.0, 0.0, 0.0));
+        assert_position(&graph, grandchild, glm::vec3(13.0, 0.0, 0.0));
+        assert_eq!(graph.ancestors(grandchild).collect::<Vec<_>>(), vec![child, root]);
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles() {
+        let (mut graph, [root, child, grandchild]) = chain();
+        assert!(matches!(graph.set_parent(child, Some(child)), Err(SceneGraphError::Cycle { .. })));
+        assert!(matches!(graph.set_parent(root, Some(grandchild)), Err(SceneGraphError::Cycle { .. })));
+        // the failed moves left the hierarchy as it was
+        assert_eq!(graph.roots(), &[root]);
+        assert_eq!(graph.parent(root), None);
+        assert_eq!(graph.children(child), &[grandchild]);
+    }
+
+    #[test]
+    fn set_parent_moves_nodes_to_the_roots_and_back() {
+        let (mut graph, [root, child, grandchild]) = chain();
+        assert_position(&graph, grandchild, glm::vec3(7.0, 0.0, 0.0));
+        graph.set_parent(child, None).unwrap();
+        assert_eq!(graph.roots(), &[root, child]);
+        assert!(graph.children(root).is_empty());
+        assert_eq!(graph.parent(child), None);
+        assert_position(&graph, child, glm::vec3(2.0, 0.0, 0.0));
+        assert_position(&graph, grandchild, glm::vec3(6.0, 0.0, 0.0));
+
+        graph.set_parent(child, Some(root)).unwrap();
+        assert_eq!(graph.roots(), &[root]);
+        assert_eq!(graph.children(root), &[child]);
+        assert_position(&graph, grandchild, glm::vec3(7.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn ancestor_changes_reach_cached_descendants() {
+        let (mut graph, [root, child, grandchild]) = chain();
+        assert_position(&graph, grandchild, glm::vec3(7.0, 0.0, 0.0));
+        graph.set_translation(root, glm::vec3(0.0, 1.0, 0.0));
+        assert_position(&graph, grandchild, glm::vec3(6.0, 1.0, 0.0));
+
+        // only the child is computed, the grandchild staying dirty below a clean node
+        graph.set_translation(root, glm::vec3(0.0, 2.0, 0.0));
+        assert_position(&graph, child, glm::vec3(2.0, 2.0, 0.0));
+        graph.set_translation(root, glm::vec3(0.0, 3.0, 0.0));
+        assert_position(&graph, grandchild, glm::vec3(6.0, 3.0, 0.0));
+        assert_position(&graph, child, glm::vec3(2.0, 3.0, 0.0));
+
+        graph.set_rotation(child, glm::quat_angle_axis(std::f32::consts::FRAC_PI_2, &glm::vec3(0.0, 0.0, 1.0)));
+        assert_position(&graph, grandchild, glm::vec3(2.0, 7.0, 0.0));
+    }
+
+    #[test]
+    fn finds_named_nodes_and_their_attachments() {
+        let (mut graph, [_, child, grandchild]) = chain();
+        graph.set_name(child, "arm");
+        graph.attach(grandchild, Attachment::Mesh(3));
+        graph.attach(grandchild, Attachment::PointLight(0));
+        graph.detach(grandchild, Attachment::Mesh(3));
+        assert_eq!(graph.find("arm"), Some(child));
+        assert_eq!(graph.find("leg"), None);
+        assert_eq!(graph.attached().collect::<Vec<_>>(), vec![(grandchild, Attachment::PointLight(0))]);
+    }
+}
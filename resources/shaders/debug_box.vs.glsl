@@ -0,0 +1,22 @@
+#version 330 core
+
+// corners of the unit cube, stretched over the bounding box of the mesh
+in vec3 position;
+#ifdef INSTANCED
+// model of each copy of an `InstanceBuffer`
+in mat4 instance_model;
+#endif
+
+uniform mat4 vp;
+#ifndef INSTANCED
+uniform mat4 model;
+#endif
+uniform vec3 boxMin;
+uniform vec3 boxMax;
+
+void main() {
+#ifdef INSTANCED
+    mat4 model = instance_model;
+#endif
+    gl_Position = vp * model * vec4(mix(boxMin, boxMax, position), 1.0);
+}
@@ -0,0 +1,13 @@
+#version 330 core
+
+in vec3 position;
+in vec4 color;
+
+out vec4 lineColor;
+
+uniform mat4 vp;
+
+void main() {
+    gl_Position = vp * vec4(position, 1.0);
+    lineColor = color;
+}
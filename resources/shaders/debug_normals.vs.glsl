@@ -0,0 +1,22 @@
+#version 330 core
+
+in vec3 position;
+in vec3 normal;
+#ifdef INSTANCED
+// model of each copy of an `InstanceBuffer`
+in mat4 instance_model;
+#endif
+
+out vec3 worldDirection;
+
+#ifndef INSTANCED
+uniform mat4 model;
+#endif
+
+void main() {
+#ifdef INSTANCED
+    mat4 model = instance_model;
+#endif
+    gl_Position = model * vec4(position, 1.0);
+    worldDirection = normalize(mat3(transpose(inverse(model))) * normal);
+}
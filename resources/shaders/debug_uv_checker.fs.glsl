@@ -0,0 +1,15 @@
+#version 330 core
+
+in vec2 texCoords;
+out vec4 FragColor;
+
+uniform float checkerCount;
+
+void main()
+{
+    vec2 cell = floor(texCoords * checkerCount);
+    float checker = mod(cell.x + cell.y, 2.0);
+    // the uv gradient shows the orientation of the mapping
+    vec3 gradient = vec3(fract(texCoords), 0.0);
+    FragColor = vec4(mix(gradient * 0.4, vec3(0.6) + gradient * 0.4, checker), 1.0);
+}
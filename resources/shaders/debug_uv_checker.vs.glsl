@@ -0,0 +1,23 @@
+#version 330 core
+
+in vec3 position;
+in vec2 tex_coords;
+#ifdef INSTANCED
+// model of each copy of an `InstanceBuffer`
+in mat4 instance_model;
+#endif
+
+out vec2 texCoords;
+
+uniform mat4 vp;
+#ifndef INSTANCED
+uniform mat4 model;
+#endif
+
+void main() {
+#ifdef INSTANCED
+    mat4 model = instance_model;
+#endif
+    gl_Position = vp * model * vec4(position, 1.0);
+    texCoords = tex_coords;
+}
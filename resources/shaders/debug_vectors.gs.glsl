@@ -0,0 +1,19 @@
+#version 330 core
+
+// one line per vertex, from the world space position along its direction
+layout (points) in;
+layout (line_strip, max_vertices = 2) out;
+
+in vec3 worldDirection[];
+
+uniform mat4 vp;
+uniform float vectorLength;
+
+void main() {
+    vec4 origin = gl_in[0].gl_Position;
+    gl_Position = vp * origin;
+    EmitVertex();
+    gl_Position = vp * (origin + vec4(worldDirection[0] * vectorLength, 0.0));
+    EmitVertex();
+    EndPrimitive();
+}
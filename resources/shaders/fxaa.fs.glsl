@@ -0,0 +1,55 @@
+#version 330 core
+
+// FXAA 3.11 "console" style pass, working on the luma of the resolved scene color
+#define FXAA_SPAN_MAX 8.0
+#define FXAA_REDUCE_MUL (1.0 / 8.0)
+#define FXAA_REDUCE_MIN (1.0 / 128.0)
+
+in vec2 texCoords;
+out vec4 FragColor;
+
+uniform sampler2D screenTexture;
+uniform vec2 inverseScreenSize;
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+void main()
+{
+    vec3 rgbNW = texture(screenTexture, texCoords + vec2(-1.0, -1.0) * inverseScreenSize).rgb;
+    vec3 rgbNE = texture(screenTexture, texCoords + vec2(1.0, -1.0) * inverseScreenSize).rgb;
+    vec3 rgbSW = texture(screenTexture, texCoords + vec2(-1.0, 1.0) * inverseScreenSize).rgb;
+    vec3 rgbSE = texture(screenTexture, texCoords + vec2(1.0, 1.0) * inverseScreenSize).rgb;
+    vec4 rgbM = texture(screenTexture, texCoords);
+
+    float lumaNW = luma(rgbNW);
+    float lumaNE = luma(rgbNE);
+    float lumaSW = luma(rgbSW);
+    float lumaSE = luma(rgbSE);
+    float lumaM = luma(rgbM.rgb);
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    vec2 dir;
+    dir.x = -((lumaNW + lumaNE) - (lumaSW + lumaSE));
+    dir.y = ((lumaNW + lumaSW) - (lumaNE + lumaSE));
+
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * (0.25 * FXAA_REDUCE_MUL), FXAA_REDUCE_MIN);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir * rcpDirMin, vec2(-FXAA_SPAN_MAX), vec2(FXAA_SPAN_MAX)) * inverseScreenSize;
+
+    vec3 rgbA = 0.5 * (
+        texture(screenTexture, texCoords + dir * (1.0 / 3.0 - 0.5)).rgb +
+        texture(screenTexture, texCoords + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 rgbB = rgbA * 0.5 + 0.25 * (
+        texture(screenTexture, texCoords + dir * -0.5).rgb +
+        texture(screenTexture, texCoords + dir * 0.5).rgb);
+
+    float lumaB = luma(rgbB);
+    if (lumaB < lumaMin || lumaB > lumaMax) {
+        FragColor = vec4(rgbA, rgbM.a);
+    } else {
+        FragColor = vec4(rgbB, rgbM.a);
+    }
+}
@@ -3,17 +3,42 @@
 in vec3 position;
 in vec3 normal;
 in vec2 tex_coords;
+in vec4 tangent;
+#ifdef INSTANCED
+// per instance attributes of an `InstanceBuffer`
+in mat4 instance_model;
+in mat3 instance_normal;
+in vec4 instance_tint;
+#endif
 
 out vec3 oNormal;
 out vec3 fragPos;
 out vec2 texCoords;
+out mat3 TBN;
+out vec4 tint;
 
 uniform mat4 vp;
+#ifndef INSTANCED
 uniform mat4 model;
+#endif
 
 void main() {
+#ifdef INSTANCED
+    mat4 model = instance_model;
+    mat3 normalMatrix = instance_normal;
+    tint = instance_tint;
+#else
+    mat3 normalMatrix = mat3(transpose(inverse(model)));// normal matrix costly, should calculate on cpu and use uniform
+    tint = vec4(1.0);
+#endif
     gl_Position = vp * model * vec4(position, 1.0);
     fragPos = vec3(model * vec4(position, 1.0));
     texCoords = tex_coords;
-    oNormal = mat3(transpose(inverse(model))) * normal;// normal matrix costly, should calculate on cpu and use uniform
+    oNormal = normalMatrix * normal;
+
+    vec3 N = normalize(oNormal);
+    vec3 T = normalize(normalMatrix * tangent.xyz);
+    T = normalize(T - dot(T, N) * N);// re-orthogonalize after the non uniform scales of the model
+    vec3 B = cross(N, T) * tangent.w;
+    TBN = mat3(T, B, N);
 }
@@ -3,6 +3,14 @@
 struct Material {
     sampler2D diffuse;
     sampler2D specular;
+    sampler2D normal;
+    bool hasNormalMap;
+    sampler2D height;
+    bool hasHeightMap;
+    int parallaxMode;// 0: simple, 1: steep, 2: occlusion
+    float heightScale;
+    float minLayers;
+    float maxLayers;
     float shininess;
 };
 
@@ -44,6 +52,8 @@ struct DirectionLight {
 in vec3 oNormal;
 in vec3 fragPos;
 in vec2 texCoords;
+in mat3 TBN;
+in vec4 tint;
 out vec4 FragColor;
 
 //uniform vec3 lightPos;
@@ -57,6 +67,44 @@ uniform DirectionLight dirLight;
 uniform PointLight pointLights[NR_POINT_LIGHTS];
 //uniform PointLight pointLight;
 
+// texture coordinates after the parallax offset
+vec2 fragTexCoords;
+
+// height maps are white at the top, the ray marching works on depth
+float sampleDepth(vec2 uv) {
+    return 1.0 - texture(material.height, uv).r;
+}
+
+vec2 parallaxMapping(vec2 uv, vec3 viewDir) {
+    if (material.parallaxMode == 0) {
+        vec2 p = viewDir.xy / viewDir.z * (sampleDepth(uv) * material.heightScale);
+        return uv - p;
+    }
+    // more layers when looking at the surface at grazing angles
+    float numLayers = mix(material.maxLayers, material.minLayers, abs(dot(vec3(0.0, 0.0, 1.0), viewDir)));
+    float layerDepth = 1.0 / numLayers;
+    float currentLayerDepth = 0.0;
+    vec2 deltaTexCoords = viewDir.xy / viewDir.z * material.heightScale / numLayers;
+
+    vec2 currentTexCoords = uv;
+    float currentDepthMapValue = sampleDepth(currentTexCoords);
+    while (currentLayerDepth < currentDepthMapValue) {
+        currentTexCoords -= deltaTexCoords;
+        currentDepthMapValue = sampleDepth(currentTexCoords);
+        currentLayerDepth += layerDepth;
+    }
+    if (material.parallaxMode == 1) {
+        return currentTexCoords;
+    }
+
+    // occlusion: interpolate between the layers before and after the collision
+    vec2 prevTexCoords = currentTexCoords + deltaTexCoords;
+    float afterDepth = currentDepthMapValue - currentLayerDepth;
+    float beforeDepth = sampleDepth(prevTexCoords) - currentLayerDepth + layerDepth;
+    float weight = afterDepth / (afterDepth - beforeDepth);
+    return prevTexCoords * weight + currentTexCoords * (1.0 - weight);
+}
+
 vec3 calcSpotLight(SpotLight light, vec3 normal, vec3 aFragPos, vec3 viewDir) {
     vec3 lightDir = normalize(light.position - aFragPos);
     float theta = dot(lightDir, normalize(-light.direction));
@@ -67,16 +115,16 @@ vec3 calcSpotLight(SpotLight light, vec3 normal, vec3 aFragPos, vec3 viewDir) {
     float attenuation = 1.0 / (light.constant + light.linear * distance +
     light.quadratic * (distance * distance));
 
-    vec3 ambient = light.ambient * vec3(texture(material.diffuse, texCoords));
+    vec3 ambient = light.ambient * vec3(texture(material.diffuse, fragTexCoords));
 
 
     float diff = max(dot(normal, lightDir), 0.0);
-    vec3 diffuse = light.diffuse * diff * vec3(texture(material.diffuse, texCoords));
+    vec3 diffuse = light.diffuse * diff * vec3(texture(material.diffuse, fragTexCoords));
 
 
     vec3 reflectDir = reflect(-lightDir, normal);
     float spec = pow(max(dot(viewDir, reflectDir), 0.0), material.shininess);
-    vec3 specular = light.specular * spec * vec3(texture(material.specular, texCoords));
+    vec3 specular = light.specular * spec * vec3(texture(material.specular, fragTexCoords));
 
     ambient*= attenuation;
     diffuse *= attenuation;
@@ -91,17 +139,17 @@ vec3 calcPointLight(PointLight light, vec3 normal, vec3 aFragPos, vec3 viewDir)
     float attenuation = 1.0 / (light.constant + light.linear * distance +
     light.quadratic * (distance * distance));
 
-    vec3 ambient = light.ambient * vec3(texture(material.diffuse, texCoords));
+    vec3 ambient = light.ambient * vec3(texture(material.diffuse, fragTexCoords));
     ambient*= attenuation;
 
     vec3 lightDir = normalize(light.position - aFragPos);
     float diff = max(dot(normal, lightDir), 0.0);
-    vec3 diffuse = light.diffuse * diff * vec3(texture(material.diffuse, texCoords));
+    vec3 diffuse = light.diffuse * diff * vec3(texture(material.diffuse, fragTexCoords));
     diffuse *= attenuation;
 
     vec3 reflectDir = reflect(-lightDir, normal);
     float spec = pow(max(dot(viewDir, reflectDir), 0.0), material.shininess);
-    vec3 specular = light.specular * spec * vec3(texture(material.specular, texCoords));
+    vec3 specular = light.specular * spec * vec3(texture(material.specular, fragTexCoords));
     specular *= attenuation;
 
     return (ambient + diffuse + specular);
@@ -109,23 +157,33 @@ vec3 calcPointLight(PointLight light, vec3 normal, vec3 aFragPos, vec3 viewDir)
 
 vec3 calcDirLight(DirectionLight light, vec3 normal, vec3 aFragPos, vec3 viewDir) {
 
-    vec3 ambient = light.ambient * vec3(texture(material.diffuse, texCoords));
+    vec3 ambient = light.ambient * vec3(texture(material.diffuse, fragTexCoords));
 
     vec3 lightDir = normalize(light.position - aFragPos);
     float diff = max(dot(normal, lightDir), 0.0);
-    vec3 diffuse = light.diffuse * diff * vec3(texture(material.diffuse, texCoords));
+    vec3 diffuse = light.diffuse * diff * vec3(texture(material.diffuse, fragTexCoords));
 
     vec3 reflectDir = reflect(-lightDir, normal);
     float spec = pow(max(dot(viewDir, reflectDir), 0.0), material.shininess);
-    vec3 specular = light.specular * spec * vec3(texture(material.specular, texCoords));
+    vec3 specular = light.specular * spec * vec3(texture(material.specular, fragTexCoords));
 
     return (ambient + diffuse + specular);
 }
 
 void main()
 {
-    vec3 norm = normalize(oNormal);
     vec3 viewDir = normalize(viewPos - fragPos);
+    fragTexCoords = texCoords;
+    if (material.hasHeightMap) {
+        vec3 tangentViewDir = normalize(transpose(TBN) * viewDir);
+        fragTexCoords = parallaxMapping(texCoords, tangentViewDir);
+    }
+    vec3 norm = normalize(oNormal);
+    if (material.hasNormalMap) {
+        // normal map stores tangent space normals in [0, 1]
+        vec3 tangentNormal = texture(material.normal, fragTexCoords).rgb * 2.0 - 1.0;
+        norm = normalize(TBN * tangentNormal);
+    }
     // phase 1: Directional lighting
     vec3 result = calcDirLight(dirLight, norm, fragPos, viewDir);
     // phase 2: Point lights
@@ -136,7 +194,7 @@ void main()
     if (toggleTorchLight)
     result += calcSpotLight(spotLight, norm, fragPos, viewDir);
 
-    FragColor = vec4(result, 1.0);
+    FragColor = vec4(result, 1.0) * tint;
 
 }
 
@@ -2,14 +2,23 @@
 
 in vec3 position;
 in vec3 normal;
+#ifdef INSTANCED
+// model of each copy of an `InstanceBuffer`
+in mat4 instance_model;
+#endif
 
 out vec3 oNormal;
 out vec3 fragPos;
 
 uniform mat4 vp;
+#ifndef INSTANCED
 uniform mat4 model;
+#endif
 
 void main() {
+#ifdef INSTANCED
+    mat4 model = instance_model;
+#endif
     gl_Position = vp * model * vec4(position, 1.0);
     fragPos = vec3(model * vec4(position, 1.0));
     oNormal = mat3(transpose(inverse(model))) * normal;// normal matrix costly, should calculate on cpu and use uniform
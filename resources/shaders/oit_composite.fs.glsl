@@ -0,0 +1,20 @@
+#version 330 core
+
+out vec4 FragColor;
+
+uniform sampler2D accum;
+uniform sampler2D weight;
+
+void main()
+{
+    ivec2 coords = ivec2(gl_FragCoord.xy);
+    vec4 accumulated = texelFetch(accum, coords, 0);
+    float revealage = accumulated.a;
+    // nothing transparent covers this pixel
+    if (revealage >= 0.9999) {
+        discard;
+    }
+    float weightSum = texelFetch(weight, coords, 0).r;
+    vec3 average = accumulated.rgb / max(weightSum, 0.00001);
+    FragColor = vec4(average, 1.0 - revealage);
+}
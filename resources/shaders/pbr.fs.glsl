@@ -0,0 +1,269 @@
+#version 330 core
+
+struct PbrMaterial {
+    vec3 albedo;
+    float alpha;
+    bool hasAlpha;
+    bool hasAlphaCutoff;
+    float alphaCutoff;
+    float metallic;
+    float roughness;
+    float ao;
+    vec3 emissive;
+
+    sampler2D albedoMap;
+    bool hasAlbedoMap;
+    sampler2D metallicMap;
+    bool hasMetallicMap;
+    sampler2D roughnessMap;
+    bool hasRoughnessMap;
+    sampler2D aoMap;
+    bool hasAoMap;
+    sampler2D emissiveMap;
+    bool hasEmissiveMap;
+    sampler2D normalMap;
+    bool hasNormalMap;
+
+    sampler2D heightMap;
+    bool hasHeightMap;
+    int parallaxMode;// 0: simple, 1: steep, 2: occlusion
+    float heightScale;
+    float minLayers;
+    float maxLayers;
+};
+
+struct SpotLight {
+    vec3 position;
+    vec3 direction;
+    float cutOff;
+    float outerCutOff;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+
+    float constant;
+    float linear;
+    float quadratic;
+};
+
+struct PointLight {
+    vec3 position;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+
+    float constant;
+    float linear;
+    float quadratic;
+};
+
+struct DirectionLight {
+    vec3 direction;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+};
+
+struct Ibl {
+    samplerCube irradiance;
+    samplerCube prefiltered;
+    sampler2D brdfLut;
+    float maxLod;
+};
+
+in vec3 oNormal;
+in vec3 fragPos;
+in vec2 texCoords;
+in mat3 TBN;
+out vec4 FragColor;
+
+uniform vec3 viewPos;
+uniform bool toggleTorchLight;
+// image based lighting replaces the constant ambient colors of the lights
+uniform bool useIbl;
+uniform Ibl ibl;
+
+uniform PbrMaterial material;
+uniform SpotLight spotLight;
+uniform DirectionLight dirLight;
+#define NR_POINT_LIGHTS 4
+uniform PointLight pointLights[NR_POINT_LIGHTS];
+
+const float PI = 3.14159265359;
+
+// texture coordinates after the parallax offset
+vec2 fragTexCoords;
+
+// height maps are white at the top, the ray marching works on depth
+float sampleDepth(vec2 uv) {
+    return 1.0 - texture(material.heightMap, uv).r;
+}
+
+vec2 parallaxMapping(vec2 uv, vec3 viewDir) {
+    if (material.parallaxMode == 0) {
+        vec2 p = viewDir.xy / viewDir.z * (sampleDepth(uv) * material.heightScale);
+        return uv - p;
+    }
+    float numLayers = mix(material.maxLayers, material.minLayers, abs(dot(vec3(0.0, 0.0, 1.0), viewDir)));
+    float layerDepth = 1.0 / numLayers;
+    float currentLayerDepth = 0.0;
+    vec2 deltaTexCoords = viewDir.xy / viewDir.z * material.heightScale / numLayers;
+
+    vec2 currentTexCoords = uv;
+    float currentDepthMapValue = sampleDepth(currentTexCoords);
+    while (currentLayerDepth < currentDepthMapValue) {
+        currentTexCoords -= deltaTexCoords;
+        currentDepthMapValue = sampleDepth(currentTexCoords);
+        currentLayerDepth += layerDepth;
+    }
+    if (material.parallaxMode == 1) {
+        return currentTexCoords;
+    }
+
+    vec2 prevTexCoords = currentTexCoords + deltaTexCoords;
+    float afterDepth = currentDepthMapValue - currentLayerDepth;
+    float beforeDepth = sampleDepth(prevTexCoords) - currentLayerDepth + layerDepth;
+    float weight = afterDepth / (afterDepth - beforeDepth);
+    return prevTexCoords * weight + currentTexCoords * (1.0 - weight);
+}
+
+float distributionGGX(vec3 N, vec3 H, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float NdotH = max(dot(N, H), 0.0);
+    float denom = NdotH * NdotH * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denom * denom);
+}
+
+float geometrySchlickGGX(float NdotV, float roughness) {
+    float r = roughness + 1.0;
+    float k = (r * r) / 8.0;
+    return NdotV / (NdotV * (1.0 - k) + k);
+}
+
+float geometrySmith(vec3 N, vec3 V, vec3 L, float roughness) {
+    return geometrySchlickGGX(max(dot(N, V), 0.0), roughness) * geometrySchlickGGX(max(dot(N, L), 0.0), roughness);
+}
+
+vec3 fresnelSchlick(float cosTheta, vec3 F0) {
+    return F0 + (1.0 - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+vec3 fresnelSchlickRoughness(float cosTheta, vec3 F0, float roughness) {
+    return F0 + (max(vec3(1.0 - roughness), F0) - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+// split sum approximation: diffuse from the irradiance map, specular from the prefiltered map and the BRDF LUT
+vec3 ambientIbl(vec3 N, vec3 V, vec3 albedo, float metallic, float roughness, vec3 F0) {
+    vec3 F = fresnelSchlickRoughness(max(dot(N, V), 0.0), F0, roughness);
+    vec3 kD = (1.0 - F) * (1.0 - metallic);
+    vec3 diffuse = texture(ibl.irradiance, N).rgb * albedo;
+
+    vec3 R = reflect(-V, N);
+    vec3 prefilteredColor = textureLod(ibl.prefiltered, R, roughness * ibl.maxLod).rgb;
+    vec2 brdf = texture(ibl.brdfLut, vec2(max(dot(N, V), 0.0), roughness)).rg;
+    vec3 specular = prefilteredColor * (F * brdf.x + brdf.y);
+    return kD * diffuse + specular;
+}
+
+// outgoing radiance of a single light, `radiance` already includes attenuation
+vec3 cookTorrance(vec3 N, vec3 V, vec3 L, vec3 radiance, vec3 albedo, float metallic, float roughness, vec3 F0) {
+    vec3 H = normalize(V + L);
+    float NDF = distributionGGX(N, H, roughness);
+    float G = geometrySmith(N, V, L, roughness);
+    vec3 F = fresnelSchlick(max(dot(H, V), 0.0), F0);
+
+    vec3 numerator = NDF * G * F;
+    float denominator = 4.0 * max(dot(N, V), 0.0) * max(dot(N, L), 0.0) + 0.0001;
+    vec3 specular = numerator / denominator;
+
+    vec3 kD = (vec3(1.0) - F) * (1.0 - metallic);
+    float NdotL = max(dot(N, L), 0.0);
+    return (kD * albedo / PI + specular) * radiance * NdotL;
+}
+
+float attenuation(float constant, float linear, float quadratic, float distance) {
+    return 1.0 / (constant + linear * distance + quadratic * (distance * distance));
+}
+
+void main()
+{
+    vec3 V = normalize(viewPos - fragPos);
+    fragTexCoords = texCoords;
+    if (material.hasHeightMap) {
+        fragTexCoords = parallaxMapping(texCoords, normalize(transpose(TBN) * V));
+    }
+
+    vec3 albedo = material.albedo;
+    float alpha = 1.0;
+    if (material.hasAlbedoMap) {
+        vec4 albedoSample = texture(material.albedoMap, fragTexCoords);
+        albedo *= pow(albedoSample.rgb, vec3(2.2));
+        alpha = albedoSample.a;
+    }
+    // opaque materials ignore alpha
+    alpha = material.hasAlpha ? alpha * material.alpha : 1.0;
+    if (material.hasAlphaCutoff) {
+        if (alpha < material.alphaCutoff) {
+            discard;
+        }
+        alpha = 1.0;
+    }
+    float metallic = material.metallic;
+    if (material.hasMetallicMap) {
+        metallic *= texture(material.metallicMap, fragTexCoords).r;
+    }
+    float roughness = material.roughness;
+    if (material.hasRoughnessMap) {
+        roughness *= texture(material.roughnessMap, fragTexCoords).r;
+    }
+    roughness = clamp(roughness, 0.04, 1.0);
+    float ao = material.ao;
+    if (material.hasAoMap) {
+        ao *= texture(material.aoMap, fragTexCoords).r;
+    }
+    vec3 emissive = material.emissive;
+    if (material.hasEmissiveMap) {
+        emissive *= pow(texture(material.emissiveMap, fragTexCoords).rgb, vec3(2.2));
+    }
+    vec3 N = normalize(oNormal);
+    if (material.hasNormalMap) {
+        N = normalize(TBN * (texture(material.normalMap, fragTexCoords).rgb * 2.0 - 1.0));
+    }
+
+    // dielectrics reflect around 4%, metals tint the reflection with their albedo
+    vec3 F0 = mix(vec3(0.04), albedo, metallic);
+    vec3 Lo = vec3(0.0);
+    vec3 ambient = dirLight.ambient;
+
+    Lo += cookTorrance(N, V, normalize(-dirLight.direction), dirLight.diffuse, albedo, metallic, roughness, F0);
+
+    for (int i = 0; i < NR_POINT_LIGHTS; i++) {
+        vec3 L = normalize(pointLights[i].position - fragPos);
+        float att = attenuation(pointLights[i].constant, pointLights[i].linear, pointLights[i].quadratic, length(pointLights[i].position - fragPos));
+        Lo += cookTorrance(N, V, L, pointLights[i].diffuse * att, albedo, metallic, roughness, F0);
+        ambient += pointLights[i].ambient * att;
+    }
+
+    if (toggleTorchLight) {
+        vec3 L = normalize(spotLight.position - fragPos);
+        float theta = dot(L, normalize(-spotLight.direction));
+        float intensity = clamp((theta - spotLight.outerCutOff) / (spotLight.cutOff - spotLight.outerCutOff), 0.0, 1.0);
+        float att = attenuation(spotLight.constant, spotLight.linear, spotLight.quadratic, length(spotLight.position - fragPos));
+        Lo += cookTorrance(N, V, L, spotLight.diffuse * att * intensity, albedo, metallic, roughness, F0);
+        ambient += spotLight.ambient * att;
+    }
+
+    vec3 ambientColor = ambient * albedo;
+    if (useIbl) {
+        ambientColor = ambientIbl(N, V, albedo, metallic, roughness, F0);
+    }
+    vec3 color = ambientColor * ao + Lo + emissive;
+    // reinhard tone mapping then gamma correction
+    color = color / (color + vec3(1.0));
+    color = pow(color, vec3(1.0 / 2.2));
+    FragColor = vec4(color, alpha);
+}
@@ -0,0 +1,11 @@
+#version  330 core
+
+in vec3 position;
+in vec2 tex_coords;
+
+out vec2 texCoords;
+
+void main() {
+    gl_Position = vec4(position.xy, 0.0, 1.0);
+    texCoords = tex_coords;
+}
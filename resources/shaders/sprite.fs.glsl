@@ -0,0 +1,12 @@
+#version 330 core
+
+in vec2 spriteCoords;
+in vec4 spriteTint;
+out vec4 FragColor;
+
+uniform sampler2D sprite;
+
+void main()
+{
+    FragColor = texture(sprite, spriteCoords) * spriteTint;
+}
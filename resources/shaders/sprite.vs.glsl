@@ -0,0 +1,16 @@
+#version 330 core
+
+in vec3 position;
+in vec2 tex_coords;
+in vec4 color;
+
+out vec2 spriteCoords;
+out vec4 spriteTint;
+
+uniform mat4 projection;
+
+void main() {
+    gl_Position = projection * vec4(position, 1.0);
+    spriteCoords = tex_coords;
+    spriteTint = color;
+}
@@ -0,0 +1,17 @@
+#version 330 core
+
+in vec2 glyphCoords;
+in vec4 glyphColor;
+out vec4 FragColor;
+
+// coverage of the glyphs in the red channel
+uniform sampler2D atlas;
+
+void main()
+{
+    float coverage = texture(atlas, glyphCoords).r;
+    if (coverage <= 0.0) {
+        discard;
+    }
+    FragColor = vec4(glyphColor.rgb, glyphColor.a * coverage);
+}
@@ -0,0 +1,16 @@
+#version 330 core
+
+in vec3 position;
+in vec2 tex_coords;
+in vec4 color;
+
+out vec2 glyphCoords;
+out vec4 glyphColor;
+
+uniform mat4 transform;
+
+void main() {
+    gl_Position = transform * vec4(position, 1.0);
+    glyphCoords = tex_coords;
+    glyphColor = color;
+}
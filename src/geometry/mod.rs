@@ -1 +1,4 @@
 pub mod cube;
+pub mod primitives;
+pub mod processing;
+pub mod simplify;
@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use graphics::VertexNormTan;
+use math::glm;
+
+/// Vertexes and triangle indexes of a generated shape. Front faces are counter-clockwise,
+/// matching `BackfaceCullingMode::CullClockwise`.
+pub type PrimitiveMesh = (Vec<VertexNormTan>, Vec<u32>);
+
+// point of a profile turned around the y axis, the normal being given in the (radius, y) plane
+#[derive(Clone, Copy)]
+struct ProfilePoint {
+    radius: f32,
+    y: f32,
+    normal: [f32; 2],
+    v: f32,
+}
+
+impl ProfilePoint {
+    fn new(radius: f32, y: f32, normal: [f32; 2], v: f32) -> Self {
+        Self { radius, y, normal, v }
+    }
+}
+
+// sine and cosine of `step` steps out of a full turn, the last step landing exactly on the
+// first so the seams of closed shapes share their positions
+fn turn(step: u32, steps: u32) -> (f32, f32) {
+    ((step % steps) as f32 / steps as f32 * TAU).sin_cos()
+}
+
+#[derive(Default)]
+struct Builder {
+    vertexes: Vec<VertexNormTan>,
+    indexes: Vec<u32>,
+}
+
+impl Builder {
+    // `bitangent` is the direction the v coordinate grows along, giving the handedness
+    fn vertex(&mut self, position: glm::Vec3, normal: glm::Vec3, uv: [f32; 2], tangent: glm::Vec3, bitangent: glm::Vec3) -> u32 {
+        let sign = if glm::cross(&normal, &tangent).dot(&bitangent) < 0.0 { -1.0 } else { 1.0 };
+        self.vertexes.push(VertexNormTan::new(
+            position.x, position.y, position.z, normal.into(), uv, [tangent.x, tangent.y, tangent.z, sign],
+        ));
+        self.vertexes.len() as u32 - 1
+    }
+
+    // triangles collapsed on a pole or an apex, whose row of vertexes shares one position, are
+    // dropped; the size of the shape does not matter
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        let [pa, pb, pc] = [a, b, c].map(|i| self.vertexes[i as usize].position());
+        if pa != pb && pb != pc && pc != pa {
+            self.indexes.extend_from_slice(&[a, b, c]);
+        }
+    }
+
+    // triangulates `columns` by `rows` quads whose first vertex is `start`, u growing with the
+    // column and v with the row; `flip` reverses the winding when that frame faces inwards
+    fn quads(&mut self, start: u32, columns: u32, rows: u32, flip: bool) {
+        for row in 0..rows {
+            for column in 0..columns {
+                let a = start + row * (columns + 1) + column;
+                let (b, c) = (a + 1, a + columns + 1);
+                let d = c + 1;
+                if flip {
+                    self.triangle(a, d, b);
+                    self.triangle(a, c, d);
+                } else {
+                    self.triangle(a, b, d);
+                    self.triangle(a, d, c);
+                }
+            }
+        }
+    }
+
+    // surface of revolution of `profile` around the y axis, u going around it
+    fn lathe(&mut self, profile: &[ProfilePoint], segments: u32) {
+        let start = self.vertexes.len() as u32;
+        for (j, point) in profile.iter().enumerate() {
+            // direction the profile follows, from its neighbours
+            let (previous, next) = (&profile[j.saturating_sub(1)], &profile[(j + 1).min(profile.len() - 1)]);
+            let (dr, dy) = (next.radius - previous.radius, next.y - previous.y);
+            for i in 0..=segments {
+                let u = i as f32 / segments as f32;
+                let (sin, cos) = turn(i, segments);
+                let position = glm::vec3(point.radius * sin, point.y, point.radius * cos);
+                let normal = glm::vec3(point.normal[0] * sin, point.normal[1], point.normal[0] * cos);
+                let tangent = glm::vec3(cos, 0.0, -sin);
+                let bitangent = glm::vec3(dr * sin, dy, dr * cos);
+                self.vertex(position, normal, [u, point.v], tangent, bitangent);
+            }
+        }
+        // the winding only depends on the way the profile runs relative to its normals
+        let first = &profile[0];
+        let second = &profile[1];
+        let facing = first.normal[0] * (second.y - first.y) - first.normal[1] * (second.radius - first.radius);
+        self.quads(start, segments, profile.len() as u32 - 1, facing < 0.0);
+    }
+
+    // disk of the xz plane at height `y`, facing up or down, with a planar mapping that reads
+    // the right way round from the side it faces
+    fn disk(&mut self, radius: f32, y: f32, segments: u32, rings: u32, up: bool) {
+        let start = self.vertexes.len() as u32;
+        let side = if up { 1.0 } else { -1.0 };
+        let normal = glm::vec3(0.0, side, 0.0);
+        let tangent = glm::vec3(side, 0.0, 0.0);
+        for ring in 0..=rings {
+            let distance = radius * ring as f32 / rings as f32;
+            for i in 0..=segments {
+                let (sin, cos) = turn(i, segments);
+                let (x, z) = (distance * sin, distance * cos);
+                let uv = [0.5 + side * x / (2.0 * radius), 0.5 - z / (2.0 * radius)];
+                self.vertex(glm::vec3(x, y, z), normal, uv, tangent, glm::vec3(0.0, 0.0, -1.0));
+            }
+        }
+        self.quads(start, segments, rings, up);
+    }
+
+    fn build(self) -> PrimitiveMesh {
+        (self.vertexes, self.indexes)
+    }
+}
+
+/// Sphere centered on the origin made of `segments` meridians and `rings` parallels, the
+/// texture wrapping once around it with its top at the north pole.
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> PrimitiveMesh {
+    let (segments, rings) = (segments.max(3), rings.max(2));
+    let profile = (0..=rings)
+        .map(|j| {
+            let v = j as f32 / rings as f32;
+            let (sin, cos) = (v * PI - FRAC_PI_2).sin_cos();
+            // exactly 0 at the poles so their vertexes share a position
+            let cos = cos.max(0.0);
+            ProfilePoint::new(radius * cos, radius * sin, [cos, sin], v)
+        })
+        .collect::<Vec<_>>();
+    let mut builder = Builder::default();
+    builder.lathe(&profile, segments);
+    builder.build()
+}
+
+/// Sphere made by splitting each face of an icosahedron in 4 `subdivisions` times, with an
+/// even triangle size. The texture is mapped like on `uv_sphere`, vertexes being split along
+/// the seam.
+pub fn icosphere(radius: f32, subdivisions: u32) -> PrimitiveMesh {
+    let t = (1.0 + 5f32.sqrt()) / 2.0;
+    let mut positions = [
+        [-1.0, t, 0.0], [1.0, t, 0.0], [-1.0, -t, 0.0], [1.0, -t, 0.0],
+        [0.0, -1.0, t], [0.0, 1.0, t], [0.0, -1.0, -t], [0.0, 1.0, -t],
+        [t, 0.0, -1.0], [t, 0.0, 1.0], [-t, 0.0, -1.0], [-t, 0.0, 1.0],
+    ].iter().map(|p| glm::normalize(&glm::Vec3::from(*p))).collect::<Vec<_>>();
+    let mut faces = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        // edges are shared by two faces, their middle is only added once
+        let mut middles = HashMap::new();
+        let mut middle = |a: usize, b: usize, positions: &mut Vec<glm::Vec3>| {
+            *middles.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                positions.push(glm::normalize(&(positions[a] + positions[b])));
+                positions.len() - 1
+            })
+        };
+        faces = faces.iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (middle(a, b, &mut positions), middle(b, c, &mut positions), middle(c, a, &mut positions));
+                vec![[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    // 0 towards +z and growing towards +x, as on `uv_sphere`
+    let longitude = |p: &glm::Vec3| (p.x.atan2(p.z) / TAU).rem_euclid(1.0);
+    let is_pole = |p: &glm::Vec3| p.x.abs() < 1e-6 && p.z.abs() < 1e-6;
+    let mut builder = Builder::default();
+    let mut unique = HashMap::new();
+    for face in faces.iter() {
+        let mut u = face.map(|i| longitude(&positions[i]));
+        // faces crossing the seam get their small u past 1, poles having no longitude of their own
+        let (min, max) = (0..3)
+            .filter(|corner| !is_pole(&positions[face[*corner]]))
+            .fold((f32::MAX, f32::MIN), |(min, max), corner| (min.min(u[corner]), max.max(u[corner])));
+        if max - min > 0.5 {
+            u.iter_mut().filter(|u| **u < 0.5).for_each(|u| *u += 1.0);
+        }
+        // the longitude of a pole is undefined, it takes the one of the opposite edge
+        for corner in 0..3 {
+            if is_pole(&positions[face[corner]]) {
+                u[corner] = (u[(corner + 1) % 3] + u[(corner + 2) % 3]) / 2.0;
+            }
+        }
+        let corners = [0, 1, 2].map(|corner| {
+            let (position, u) = (positions[face[corner]], u[corner]);
+            *unique.entry((face[corner], u.to_bits())).or_insert_with(|| {
+                let (sin, cos) = (u * TAU).sin_cos();
+                let v = 0.5 + position.y.asin() / PI;
+                let tangent = glm::vec3(cos, 0.0, -sin);
+                builder.vertex(position * radius, position, [u, v], tangent, glm::vec3(0.0, 1.0, 0.0))
+            })
+        });
+        builder.triangle(corners[0], corners[1], corners[2]);
+    }
+    builder.build()
+}
+
+/// Plane of the xz plane centered on the origin and facing up, split in `x_segments` by
+/// `z_segments` quads. The top of the texture is towards -z.
+pub fn plane(width: f32, depth: f32, x_segments: u32, z_segments: u32) -> PrimitiveMesh {
+    let (x_segments, z_segments) = (x_segments.max(1), z_segments.max(1));
+    let mut builder = Builder::default();
+    for j in 0..=z_segments {
+        let v = j as f32 / z_segments as f32;
+        for i in 0..=x_segments {
+            let u = i as f32 / x_segments as f32;
+            let position = glm::vec3((u - 0.5) * width, 0.0, (0.5 - v) * depth);
+            builder.vertex(position, glm::vec3(0.0, 1.0, 0.0), [u, v], glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, 0.0, -1.0));
+        }
+    }
+    builder.quads(0, x_segments, z_segments, false);
+    builder.build()
+}
+
+/// Disk of the xz plane centered on the origin and facing up, made of `rings` concentric rings.
+pub fn disk(radius: f32, segments: u32, rings: u32) -> PrimitiveMesh {
+    let mut builder = Builder::default();
+    builder.disk(radius, 0.0, segments.max(3), rings.max(1), true);
+    builder.build()
+}
+
+/// Cylinder along the y axis centered on the origin, its side being split in `height_segments`
+/// rows. The side and the caps have their own vertexes so their edges stay sharp.
+pub fn cylinder(radius: f32, height: f32, segments: u32, height_segments: u32, caps: bool) -> PrimitiveMesh {
+    let (segments, height_segments) = (segments.max(3), height_segments.max(1));
+    let profile = (0..=height_segments)
+        .map(|j| {
+            let v = j as f32 / height_segments as f32;
+            ProfilePoint::new(radius, (v - 0.5) * height, [1.0, 0.0], v)
+        })
+        .collect::<Vec<_>>();
+    let mut builder = Builder::default();
+    builder.lathe(&profile, segments);
+    if caps {
+        builder.disk(radius, -height / 2.0, segments, 1, false);
+        builder.disk(radius, height / 2.0, segments, 1, true);
+    }
+    builder.build()
+}
+
+/// Cone along the y axis with its base centered `height / 2` below the origin and its apex
+/// as far above.
+pub fn cone(radius: f32, height: f32, segments: u32, height_segments: u32, cap: bool) -> PrimitiveMesh {
+    let (segments, height_segments) = (segments.max(3), height_segments.max(1));
+    let slope = glm::normalize(&glm::vec2(height, radius));
+    let profile = (0..=height_segments)
+        .map(|j| {
+            let v = j as f32 / height_segments as f32;
+            ProfilePoint::new(radius * (1.0 - v), (v - 0.5) * height, [slope.x, slope.y], v)
+        })
+        .collect::<Vec<_>>();
+    let mut builder = Builder::default();
+    builder.lathe(&profile, segments);
+    if cap {
+        builder.disk(radius, -height / 2.0, segments, 1, false);
+    }
+    builder.build()
+}
+
+/// Cylinder of `height` along the y axis ended by two hemispheres of `rings` parallels, for a
+/// total height of `height + 2 * radius`. The texture is spread along the whole profile.
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> PrimitiveMesh {
+    let (segments, rings) = (segments.max(3), rings.max(1));
+    let length = PI * radius + height;
+    let mut profile = Vec::new();
+    for (center, from) in [(-height / 2.0, -FRAC_PI_2), (height / 2.0, 0.0)].iter() {
+        for j in 0..=rings {
+            let angle = from + j as f32 / rings as f32 * FRAC_PI_2;
+            let (sin, cos) = angle.sin_cos();
+            let cos = cos.max(0.0);
+            // distance along the profile from the bottom pole
+            let arc = (angle + FRAC_PI_2) * radius + if *center < 0.0 { 0.0 } else { height };
+            profile.push(ProfilePoint::new(radius * cos, center + radius * sin, [cos, sin], arc / length));
+        }
+    }
+    let mut builder = Builder::default();
+    builder.lathe(&profile, segments);
+    builder.build()
+}
+
+/// Torus around the y axis, `major_radius` being the distance from its center to the center
+/// of the tube. The texture wraps once around the axis and once around the tube.
+pub fn torus(major_radius: f32, minor_radius: f32, segments: u32, sides: u32) -> PrimitiveMesh {
+    let (segments, sides) = (segments.max(3), sides.max(3));
+    let profile = (0..=sides)
+        .map(|j| {
+            let v = j as f32 / sides as f32;
+            let (sin, cos) = turn(j, sides);
+            ProfilePoint::new(major_radius + minor_radius * cos, minor_radius * sin, [cos, sin], v)
+        })
+        .collect::<Vec<_>>();
+    let mut builder = Builder::default();
+    builder.lathe(&profile, segments);
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // generated shapes with the point their faces point away from, the torus being checked
+    // against the circle at the center of its tube
+    fn shapes() -> Vec<(&'static str, PrimitiveMesh, fn(&glm::Vec3) -> glm::Vec3)> {
+        let origin: fn(&glm::Vec3) -> glm::Vec3 = |_| glm::Vec3::zeros();
+        let below: fn(&glm::Vec3) -> glm::Vec3 = |_| glm::vec3(0.0, -1.0, 0.0);
+        let tube: fn(&glm::Vec3) -> glm::Vec3 = |p| glm::normalize(&glm::vec3(p.x, 0.0, p.z)) * 2.0;
+        vec![
+            ("uv_sphere", uv_sphere(1.0, 16, 8), origin),
+            ("icosphere", icosphere(1.0, 2), origin),
+            ("plane", plane(2.0, 1.0, 3, 2), below),
+            ("disk", disk(1.0, 12, 2), below),
+            ("cylinder", cylinder(1.0, 2.0, 12, 2, true), origin),
+            ("cone", cone(1.0, 2.0, 12, 2, true), origin),
+            ("capsule", capsule(0.5, 1.0, 12, 4), origin),
+            ("torus", torus(2.0, 0.5, 16, 8), tube),
+        ]
+    }
+
+    fn corners(mesh: &PrimitiveMesh, triangle: &[u32]) -> [VertexNormTan; 3] {
+        [0, 1, 2].map(|i| mesh.0[triangle[i] as usize])
+    }
+
+    #[test]
+    fn faces_point_outwards() {
+        for (name, mesh, center) in shapes() {
+            assert!(!mesh.1.is_empty(), "{} has no triangles", name);
+            for triangle in mesh.1.chunks_exact(3) {
+                let [a, b, c] = corners(&mesh, triangle).map(|vertex| glm::Vec3::from(vertex.position()));
+                let normal = glm::cross(&(b - a), &(c - a));
+                let centroid = (a + b + c) / 3.0;
+                assert!(normal.dot(&(centroid - center(&centroid))) > 0.0, "{} has an inward face at {}", name, centroid);
+            }
+        }
+    }
+
+    #[test]
+    fn tangent_handedness_follows_the_uvs() {
+        for (name, mesh, _) in shapes() {
+            for triangle in mesh.1.chunks_exact(3) {
+                let vertexes = corners(&mesh, triangle);
+                let [a, b, c] = vertexes.map(|vertex| glm::Vec3::from(vertex.position()));
+                let [ta, tb, tc] = vertexes.map(|vertex| glm::Vec2::from(vertex.tex_coords()));
+                let (e1, e2, d1, d2) = (b - a, c - a, tb - ta, tc - ta);
+                let det = d1.x * d2.y - d2.x * d1.y;
+                if det.abs() < 1e-8 {
+                    continue;
+                }
+                // directions u and v grow along on the triangle
+                let tangent = (e1 * d2.y - e2 * d1.y) / det;
+                let bitangent = (e2 * d1.x - e1 * d2.x) / det;
+                let normal = glm::cross(&e1, &e2);
+                let sign = if glm::cross(&normal, &tangent).dot(&bitangent) < 0.0 { -1.0 } else { 1.0 };
+                for vertex in vertexes.iter() {
+                    assert_eq!(vertex.tangent()[3], sign, "{} at {:?}", name, vertex.position());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn small_shapes_keep_their_triangles() {
+        let (_, small) = uv_sphere(0.001, 16, 8);
+        let (_, large) = uv_sphere(1.0, 16, 8);
+        assert_eq!(small.len(), large.len());
+        // a pole fan per cap and two triangles per other quad
+        assert_eq!(large.len(), (16 * 2 + 16 * 6 * 2) * 3);
+        assert_eq!(cone(0.001, 0.002, 12, 1, false).1.len(), 12 * 3);
+    }
+
+    #[test]
+    fn icosphere_and_uv_sphere_share_their_mapping() {
+        let (vertexes, _) = icosphere(1.0, 2);
+        for vertex in vertexes.iter() {
+            let [x, y, z] = vertex.position();
+            let [u, v] = vertex.tex_coords();
+            assert!((v - (0.5 + y.asin() / PI)).abs() < 1e-5);
+            if x.abs() > 1e-3 || z.abs() > 1e-3 {
+                // seam vertexes may have u = 1 instead of 0
+                let (sin, cos) = (u * TAU).sin_cos();
+                let horizontal = glm::normalize(&glm::vec2(x, z));
+                assert!((sin - horizontal.x).abs() < 1e-4 && (cos - horizontal.y).abs() < 1e-4, "u {} at {:?}", u, vertex.position());
+            }
+        }
+        let (vertexes, _) = uv_sphere(1.0, 16, 8);
+        let front = vertexes.iter().find(|vertex| vertex.tex_coords() == [0.25, 0.5]).unwrap();
+        assert!((glm::Vec3::from(front.position()) - glm::vec3(1.0, 0.0, 0.0)).norm() < 1e-5);
+    }
+}
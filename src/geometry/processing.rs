@@ -0,0 +1,522 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use graphics::{Mesh, Submesh};
+use math::glm;
+
+// cache size Forsyth scores are tuned for, larger than most post transform caches on purpose
+const FORSYTH_CACHE_SIZE: usize = 32;
+const FORSYTH_DECAY_POWER: f32 = 1.5;
+const FORSYTH_LAST_TRIANGLE_SCORE: f32 = 0.75;
+const FORSYTH_VALENCE_SCALE: f32 = 2.0;
+const FORSYTH_VALENCE_POWER: f32 = -0.5;
+
+/// Cache size used to split triangles in clusters when ordering them for overdraw.
+pub const VERTEX_CACHE_SIZE: usize = 16;
+
+fn gather<T: Copy>(stream: &mut Option<Vec<T>>, sources: &[usize]) {
+    if let Some(values) = stream {
+        *values = sources.iter().map(|source| values[*source]).collect();
+    }
+}
+
+/// Rebuilds every stream so that the new vertex `i` is the old vertex `sources[i]`. Indexes
+/// are left untouched.
+pub fn remap_vertexes(mesh: &mut Mesh, sources: &[usize]) {
+    mesh.positions = sources.iter().map(|source| mesh.positions[*source]).collect();
+    gather(&mut mesh.normals, sources);
+    gather(&mut mesh.tex_coords0, sources);
+    gather(&mut mesh.tex_coords1, sources);
+    gather(&mut mesh.tangents, sources);
+    gather(&mut mesh.colors, sources);
+    gather(&mut mesh.joints, sources);
+    gather(&mut mesh.weights, sources);
+}
+
+// every float of a vertex, the attributes that must match for two vertexes to be welded
+fn vertex_key(mesh: &Mesh, vertex: usize) -> Vec<f32> {
+    let mut key = mesh.positions[vertex].to_vec();
+    let streams: [Option<&[f32]>; 6] = [
+        mesh.normals.as_ref().map(|values| &values[vertex][..]),
+        mesh.tex_coords0.as_ref().map(|values| &values[vertex][..]),
+        mesh.tex_coords1.as_ref().map(|values| &values[vertex][..]),
+        mesh.tangents.as_ref().map(|values| &values[vertex][..]),
+        mesh.colors.as_ref().map(|values| &values[vertex][..]),
+        mesh.weights.as_ref().map(|values| &values[vertex][..]),
+    ];
+    key.extend(streams.iter().flatten().flat_map(|values| values.iter()));
+    if let Some(joints) = &mesh.joints {
+        key.extend(joints[vertex].iter().map(|joint| *joint as f32));
+    }
+    key
+}
+
+// exact position as a hash key, -0 being the same point as 0
+pub(crate) fn position_key(position: [f32; 3]) -> [u32; 3] {
+    position.map(|value| (value + 0.0).to_bits())
+}
+
+fn face_normal(mesh: &Mesh, triangle: &[u32]) -> glm::Vec3 {
+    let [a, b, c] = [0, 1, 2].map(|i| glm::Vec3::from(mesh.positions[triangle[i] as usize]));
+    // not normalized, its length is twice the triangle area
+    glm::cross(&(b - a), &(c - a))
+}
+
+/// Merges vertexes whose attributes all differ by at most `tolerance`, the first one found
+/// being kept, and drops unused vertexes. Returns the number of vertexes removed.
+pub fn weld_vertexes(mesh: &mut Mesh, tolerance: f32) -> usize {
+    let count = mesh.vertex_count();
+    let cell_size = tolerance.max(f32::EPSILON);
+    let cell = |position: [f32; 3]| position.map(|value| (value / cell_size).floor() as i64);
+    // kept vertexes bucketed by position so only the neighbouring cells are compared
+    let mut grid: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    let mut keys: Vec<Vec<f32>> = Vec::new();
+    let mut sources = Vec::new();
+    let mut remap = vec![u32::MAX; count];
+
+    let used = {
+        let mut used = vec![false; count];
+        mesh.indexes.iter().for_each(|index| used[*index as usize] = true);
+        used
+    };
+    for vertex in (0..count).filter(|vertex| used[*vertex]) {
+        let key = vertex_key(mesh, vertex);
+        let [x, y, z] = cell(mesh.positions[vertex]);
+        let neighbours = (-1..=1)
+            .flat_map(|dx| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| [x + dx, y + dy, z + dz])));
+        let matching = neighbours
+            .filter_map(|neighbour| grid.get(&neighbour))
+            .flatten()
+            .find(|kept| keys[**kept].iter().zip(key.iter()).all(|(a, b)| (a - b).abs() <= tolerance))
+            .copied();
+        remap[vertex] = match matching {
+            Some(kept) => kept as u32,
+            None => {
+                grid.entry([x, y, z]).or_default().push(sources.len());
+                keys.push(key);
+                sources.push(vertex);
+                sources.len() as u32 - 1
+            }
+        };
+    }
+
+    for index in mesh.indexes.iter_mut() {
+        *index = remap[*index as usize];
+    }
+    remap_vertexes(mesh, &sources);
+    count - sources.len()
+}
+
+/// Drops the triangles using a vertex twice or whose area is at most `min_area`, shrinking
+/// the submeshes accordingly. Returns the number of triangles removed.
+pub fn remove_degenerate_triangles(mesh: &mut Mesh, min_area: f32) -> usize {
+    let mut indexes = Vec::with_capacity(mesh.indexes.len());
+    let mut submeshes = mesh.submeshes.clone();
+    for submesh in submeshes.iter_mut() {
+        let start = indexes.len();
+        for triangle in mesh.indexes[submesh.indexes.clone()].chunks_exact(3) {
+            let repeated = triangle[0] == triangle[1] || triangle[1] == triangle[2] || triangle[0] == triangle[2];
+            if !repeated && face_normal(mesh, triangle).norm() / 2.0 > min_area {
+                indexes.extend_from_slice(triangle);
+            }
+        }
+        submesh.indexes = start..indexes.len();
+    }
+    let removed = (mesh.indexes.len() - indexes.len()) / 3;
+    mesh.indexes = indexes;
+    mesh.submeshes = submeshes;
+    removed
+}
+
+/// Gives every triangle its own vertexes carrying the normal of its face.
+pub fn compute_flat_normals(mesh: &mut Mesh) {
+    let normals = mesh.indexes.chunks_exact(3)
+        .flat_map(|triangle| {
+            let normal = face_normal(mesh, triangle);
+            let normal: [f32; 3] = if normal.norm() > f32::EPSILON { glm::normalize(&normal).into() } else { [0.0, 1.0, 0.0] };
+            [normal; 3]
+        })
+        .collect::<Vec<_>>();
+    let sources = mesh.indexes.iter().map(|index| *index as usize).collect::<Vec<_>>();
+    remap_vertexes(mesh, &sources);
+    mesh.indexes = (0..sources.len() as u32).collect();
+    mesh.normals = Some(normals);
+}
+
+/// Averages the normals of the faces around each position, weighted by their area, only
+/// across edges whose faces make an angle below `crease_angle` radians. Vertexes are split
+/// where a crease gives a corner another normal.
+pub fn compute_smooth_normals(mesh: &mut Mesh, crease_angle: f32) {
+    let cos_crease = crease_angle.cos();
+    let faces = mesh.indexes.chunks_exact(3).map(|triangle| face_normal(mesh, triangle)).collect::<Vec<_>>();
+    let units = faces.iter()
+        .map(|face| if face.norm() > f32::EPSILON { glm::normalize(face) } else { glm::Vec3::zeros() })
+        .collect::<Vec<_>>();
+    // faces around each position, vertexes split on seams still being smoothed together
+    let mut around: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (corner, index) in mesh.indexes.iter().enumerate() {
+        around.entry(position_key(mesh.positions[*index as usize])).or_default().push(corner / 3);
+    }
+
+    let mut unique = HashMap::new();
+    let mut sources = Vec::new();
+    let mut normals = Vec::new();
+    let mut indexes = Vec::with_capacity(mesh.indexes.len());
+    for (corner, index) in mesh.indexes.iter().enumerate() {
+        let face = corner / 3;
+        let sum = around[&position_key(mesh.positions[*index as usize])].iter()
+            .filter(|other| units[**other].dot(&units[face]) >= cos_crease)
+            .fold(glm::Vec3::zeros(), |sum, other| sum + faces[*other]);
+        let normal: [f32; 3] = if sum.norm() > f32::EPSILON { glm::normalize(&sum).into() } else { [0.0, 1.0, 0.0] };
+        let vertex = *unique.entry((*index, normal.map(f32::to_bits))).or_insert_with(|| {
+            sources.push(*index as usize);
+            normals.push(normal);
+            sources.len() as u32 - 1
+        });
+        indexes.push(vertex);
+    }
+    remap_vertexes(mesh, &sources);
+    mesh.indexes = indexes;
+    mesh.normals = Some(normals);
+}
+
+/// Cache misses per triangle of `indexes` drawn through a FIFO post transform cache of
+/// `cache_size` vertexes, from 0.5 at best to 3.
+pub fn average_cache_miss_ratio(indexes: &[u32], cache_size: usize) -> f32 {
+    let mut cache = VecDeque::with_capacity(cache_size);
+    let mut misses = 0;
+    for index in indexes.iter() {
+        if !cache.contains(index) {
+            misses += 1;
+            if cache.len() == cache_size {
+                cache.pop_front();
+            }
+            cache.push_back(*index);
+        }
+    }
+    misses as f32 / (indexes.len() / 3).max(1) as f32
+}
+
+fn forsyth_vertex_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        // the vertexes of the last triangle get a fixed score so it is not reused right away
+        Some(position) if position < 3 => FORSYTH_LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scale = 1.0 / (FORSYTH_CACHE_SIZE - 3) as f32;
+            (1.0 - (position - 3) as f32 * scale).powf(FORSYTH_DECAY_POWER)
+        }
+        None => 0.0,
+    };
+    // vertexes with few triangles left are worth finishing
+    cache_score + FORSYTH_VALENCE_SCALE * (remaining as f32).powf(FORSYTH_VALENCE_POWER)
+}
+
+// Tom Forsyth's linear-speed vertex cache optimisation of a triangle list
+fn forsyth_order(indexes: &[u32], vertex_count: usize) -> Vec<u32> {
+    let triangle_count = indexes.len() / 3;
+    let mut triangles_of = vec![Vec::new(); vertex_count];
+    for (corner, index) in indexes.iter().enumerate() {
+        triangles_of[*index as usize].push(corner / 3);
+    }
+    let mut remaining = triangles_of.iter().map(Vec::len).collect::<Vec<_>>();
+    let mut cache_position: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut vertex_scores = (0..vertex_count).map(|vertex| forsyth_vertex_score(None, remaining[vertex])).collect::<Vec<_>>();
+    let triangle_score = |triangle: usize, vertex_scores: &[f32]| {
+        indexes[triangle * 3..triangle * 3 + 3].iter().map(|index| vertex_scores[*index as usize]).sum::<f32>()
+    };
+    let mut triangle_scores = (0..triangle_count).map(|triangle| triangle_score(triangle, &vertex_scores)).collect::<Vec<_>>();
+    let mut emitted = vec![false; triangle_count];
+    let mut cache: Vec<u32> = Vec::with_capacity(FORSYTH_CACHE_SIZE + 3);
+    let mut order = Vec::with_capacity(indexes.len());
+    // next triangle to look at when the cache offers none
+    let mut cursor = 0;
+
+    let mut best = None;
+    for _ in 0..triangle_count {
+        let triangle = match best {
+            Some(triangle) => triangle,
+            None => {
+                let triangle = (cursor..triangle_count)
+                    .filter(|triangle| !emitted[*triangle])
+                    .max_by(|a, b| triangle_scores[*a].partial_cmp(&triangle_scores[*b]).unwrap())
+                    .unwrap();
+                cursor = (cursor..triangle_count).find(|triangle| !emitted[*triangle]).unwrap_or(triangle_count);
+                triangle
+            }
+        };
+        emitted[triangle] = true;
+        let corners = &indexes[triangle * 3..triangle * 3 + 3];
+        order.extend_from_slice(corners);
+
+        // the triangle vertexes move to the front of the cache, pushing the others back
+        for index in corners.iter() {
+            remaining[*index as usize] -= 1;
+            cache.retain(|cached| cached != index);
+        }
+        let mut updated = corners.to_vec();
+        updated.append(&mut cache);
+        cache = updated;
+        for (position, vertex) in cache.iter().enumerate() {
+            let vertex = *vertex as usize;
+            cache_position[vertex] = if position < FORSYTH_CACHE_SIZE { Some(position) } else { None };
+            vertex_scores[vertex] = forsyth_vertex_score(cache_position[vertex], remaining[vertex]);
+        }
+
+        // only the triangles of the cached vertexes changed score, the best of them is next
+        best = None;
+        let mut best_score = f32::MIN;
+        for vertex in cache.iter() {
+            for other in triangles_of[*vertex as usize].iter().filter(|other| !emitted[**other]) {
+                triangle_scores[*other] = triangle_score(*other, &vertex_scores);
+                if triangle_scores[*other] > best_score {
+                    best_score = triangle_scores[*other];
+                    best = Some(*other);
+                }
+            }
+        }
+        cache.truncate(FORSYTH_CACHE_SIZE);
+    }
+    order
+}
+
+// indexes of the whole triangles of a submesh, a trailing incomplete one being left alone
+fn triangles_of(submesh: &Submesh) -> Range<usize> {
+    let start = submesh.indexes.start;
+    start..start + submesh.indexes.len() / 3 * 3
+}
+
+/// Reorders the triangles of each submesh for the post transform vertex cache, then the
+/// vertexes in the order the triangles first use them.
+pub fn optimize_vertex_cache(mesh: &mut Mesh) {
+    let vertex_count = mesh.vertex_count();
+    for submesh in mesh.submeshes.clone().iter() {
+        let triangles = triangles_of(submesh);
+        let ordered = forsyth_order(&mesh.indexes[triangles.clone()], vertex_count);
+        mesh.indexes[triangles].copy_from_slice(&ordered);
+    }
+    optimize_vertex_fetch(mesh);
+}
+
+/// Sorts the vertexes in the order the indexes first use them, so draws read the vertex
+/// buffer mostly forwards. Unused vertexes are dropped.
+pub fn optimize_vertex_fetch(mesh: &mut Mesh) {
+    let mut remap = vec![u32::MAX; mesh.vertex_count()];
+    let mut sources = Vec::new();
+    for index in mesh.indexes.iter_mut() {
+        if remap[*index as usize] == u32::MAX {
+            remap[*index as usize] = sources.len() as u32;
+            sources.push(*index as usize);
+        }
+        *index = remap[*index as usize];
+    }
+    remap_vertexes(mesh, &sources);
+}
+
+/// Reorders the triangles of each submesh so the ones likely to hide others are drawn
+/// first, keeping the vertex cache efficiency of a previous `optimize_vertex_cache`.
+///
+/// Triangles are cut in clusters where the cache order restarts, at triangles missing all
+/// their vertexes, and the clusters are sorted by how far out they face from the center
+/// of the submesh (Sander, Nehab and Barczak, "Fast Triangle Reordering for Vertex
+/// Locality and Reduced Overdraw").
+pub fn optimize_overdraw(mesh: &mut Mesh) {
+    for submesh in mesh.submeshes.clone().iter() {
+        let indexes = &mesh.indexes[triangles_of(submesh)];
+        let triangle_count = indexes.len() / 3;
+        if triangle_count == 0 {
+            continue;
+        }
+
+        let mut clusters = vec![0];
+        let mut cache = VecDeque::with_capacity(VERTEX_CACHE_SIZE);
+        for (triangle, corners) in indexes.chunks_exact(3).enumerate() {
+            let mut misses = 0;
+            for index in corners.iter() {
+                if !cache.contains(index) {
+                    misses += 1;
+                    if cache.len() == VERTEX_CACHE_SIZE {
+                        cache.pop_front();
+                    }
+                    cache.push_back(*index);
+                }
+            }
+            if misses == 3 && triangle > 0 {
+                clusters.push(triangle);
+            }
+        }
+        clusters.push(triangle_count);
+
+        let area_centroid = |triangles: std::ops::Range<usize>| {
+            triangles.fold((glm::Vec3::zeros(), glm::Vec3::zeros(), 0.0), |(normal, centroid, area), triangle| {
+                let corners = &indexes[triangle * 3..triangle * 3 + 3];
+                let face = face_normal(mesh, corners);
+                let center = corners.iter().map(|index| glm::Vec3::from(mesh.positions[*index as usize])).sum::<glm::Vec3>() / 3.0;
+                (normal + face, centroid + center * face.norm(), area + face.norm())
+            })
+        };
+        let (_, centroid, area) = area_centroid(0..triangle_count);
+        let center = if area > f32::EPSILON { centroid / area } else { glm::Vec3::zeros() };
+        let mut ranges = clusters.windows(2)
+            .map(|bounds| {
+                let (normal, centroid, area) = area_centroid(bounds[0]..bounds[1]);
+                let cluster_center = if area > f32::EPSILON { centroid / area } else { center };
+                let normal = if normal.norm() > f32::EPSILON { glm::normalize(&normal) } else { normal };
+                ((cluster_center - center).dot(&normal), bounds[0]..bounds[1])
+            })
+            .collect::<Vec<_>>();
+        // outermost first, the sort being stable keeps the cache order of equal clusters
+        ranges.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        let sorted = ranges.iter()
+            .flat_map(|(_, triangles)| indexes[triangles.start * 3..triangles.end * 3].iter().copied())
+            .collect::<Vec<_>>();
+        mesh.indexes[triangles_of(submesh)].copy_from_slice(&sorted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::cube::{cube_indexes, cube_vertexes_2d};
+    use crate::geometry::primitives::uv_sphere;
+
+    fn cube() -> Mesh {
+        let indexes = cube_indexes().iter().map(|index| *index as u32).collect::<Vec<_>>();
+        Mesh::from_vertexes(&cube_vertexes_2d(), &indexes)
+    }
+
+    // triangles as the exact attributes of their corners, starting from the lowest corner
+    // so the winding is kept, sorted to compare meshes whatever their order
+    fn triangle_set(mesh: &Mesh) -> Vec<Vec<Vec<u32>>> {
+        let mut triangles = mesh.indexes.chunks_exact(3)
+            .map(|triangle| {
+                let mut corners = triangle.iter()
+                    .map(|index| vertex_key(mesh, *index as usize).iter().map(|value| value.to_bits()).collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+                let lowest = (0..3).min_by_key(|i| corners[*i].clone()).unwrap();
+                corners.rotate_left(lowest);
+                corners
+            })
+            .collect::<Vec<_>>();
+        triangles.sort();
+        triangles
+    }
+
+    fn corner_normals(mesh: &Mesh) -> Vec<[f32; 3]> {
+        let normals = mesh.normals.as_ref().unwrap();
+        mesh.indexes.iter().map(|index| normals[*index as usize]).collect()
+    }
+
+    fn is_axis(normal: &[f32; 3]) -> bool {
+        let mut components = normal.map(f32::abs);
+        components.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        components == [0.0, 0.0, 1.0]
+    }
+
+    #[test]
+    fn welds_only_matching_vertexes() {
+        // corners share positions but not normals nor texture coordinates
+        let mut mesh = cube();
+        assert_eq!(weld_vertexes(&mut mesh, 1e-3), 0);
+        assert_eq!(mesh.vertex_count(), 24);
+
+        let mut mesh = cube();
+        mesh.normals = None;
+        mesh.tex_coords0 = None;
+        let before = triangle_set(&mesh);
+        assert_eq!(weld_vertexes(&mut mesh, 1e-3), 16);
+        assert_eq!(mesh.vertex_count(), 8);
+        assert_eq!(triangle_set(&mesh), before);
+    }
+
+    #[test]
+    fn welds_within_the_tolerance() {
+        let mut mesh = cube();
+        mesh.normals = None;
+        mesh.tex_coords0 = None;
+        mesh.positions[0][0] += 0.01;
+        let mut strict = mesh.clone();
+        assert_eq!(weld_vertexes(&mut strict, 1e-3), 15);
+        assert_eq!(weld_vertexes(&mut mesh, 0.02), 16);
+    }
+
+    #[test]
+    fn flat_normals_are_those_of_the_faces() {
+        let mut mesh = cube();
+        let expected = corner_normals(&mesh);
+        compute_flat_normals(&mut mesh);
+        assert_eq!(mesh.vertex_count(), 36);
+        assert_eq!(corner_normals(&mesh), expected);
+    }
+
+    #[test]
+    fn smooth_normals_stop_at_creases() {
+        let mut mesh = cube();
+        let expected = corner_normals(&mesh);
+        compute_smooth_normals(&mut mesh, 80f32.to_radians());
+        assert!(corner_normals(&mesh).iter().all(is_axis));
+        assert_eq!(corner_normals(&mesh), expected);
+
+        // above the 90 degrees of the edges every corner gets the average of its faces
+        let mut mesh = cube();
+        compute_smooth_normals(&mut mesh, 100f32.to_radians());
+        let center = glm::vec3(0.5, 0.5, 0.5);
+        let mut at_position: HashMap<[u32; 3], [f32; 3]> = HashMap::new();
+        for (index, normal) in mesh.indexes.iter().zip(corner_normals(&mesh).iter()) {
+            let position = mesh.positions[*index as usize];
+            let outwards = glm::Vec3::from(position) - center;
+            assert!(normal.iter().zip(outwards.iter()).all(|(n, o)| n.abs() > 0.1 && n.signum() == o.signum()), "{:?} at {:?}", normal, position);
+            assert_eq!(*at_position.entry(position_key(position)).or_insert(*normal), *normal);
+        }
+    }
+
+    #[test]
+    fn removes_collapsed_triangles() {
+        let mut mesh = cube();
+        let before = triangle_set(&mesh);
+        // a point in the middle of the edge from 0 to 2 makes a triangle without area
+        let [a, b] = [mesh.positions[0], mesh.positions[2]];
+        mesh.positions.push([(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0, (a[2] + b[2]) / 2.0]);
+        mesh.normals.as_mut().unwrap().push([0.0, 0.0, -1.0]);
+        mesh.tex_coords0.as_mut().unwrap().push([0.5, 0.5]);
+        mesh.push_submesh(&[0, 24, 2, 1, 1, 3], 1);
+        assert_eq!(remove_degenerate_triangles(&mut mesh, 1e-6), 2);
+        assert_eq!(mesh.indexes.len(), 36);
+        assert_eq!(mesh.submeshes[0].indexes, 0..36);
+        assert_eq!(mesh.submeshes[1].indexes, 36..36);
+        assert_eq!(triangle_set(&mesh), before);
+    }
+
+    #[test]
+    fn reordering_keeps_the_triangles() {
+        let (vertexes, indexes) = uv_sphere(1.0, 24, 12);
+        let mut sphere = Mesh::from_tangent_vertexes(&vertexes, &indexes);
+        // rings drawn in a scattered order
+        let triangles = sphere.indexes.chunks_exact(3).map(|triangle| triangle.to_vec()).collect::<Vec<_>>();
+        sphere.indexes = (0..triangles.len()).map(|i| i * 7 % triangles.len()).flat_map(|i| triangles[i].clone()).collect();
+
+        for mut mesh in [cube(), sphere].iter().cloned() {
+            let before = triangle_set(&mesh);
+            let acmr = average_cache_miss_ratio(&mesh.indexes, VERTEX_CACHE_SIZE);
+            optimize_vertex_cache(&mut mesh);
+            assert_eq!(triangle_set(&mesh), before);
+            let optimized = average_cache_miss_ratio(&mesh.indexes, VERTEX_CACHE_SIZE);
+            assert!(optimized <= acmr, "{} > {}", optimized, acmr);
+            optimize_overdraw(&mut mesh);
+            assert_eq!(triangle_set(&mesh), before);
+        }
+    }
+
+    #[test]
+    fn reordering_leaves_incomplete_triangles_alone() {
+        let mut mesh = cube();
+        mesh.push_submesh(&[0, 1, 2, 3], 1);
+        optimize_vertex_cache(&mut mesh);
+        optimize_overdraw(&mut mesh);
+        assert_eq!(mesh.submeshes[1].indexes, 36..40);
+        assert_eq!(mesh.indexes.len(), 40);
+    }
+}
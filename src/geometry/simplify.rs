@@ -0,0 +1,383 @@
+use std::collections::{HashMap, HashSet};
+
+use graphics::Mesh;
+use math::glm;
+
+use crate::geometry::processing::{optimize_vertex_fetch, position_key};
+
+/// Triangle ratios of the levels of detail generated for the scene meshes, from the finest.
+pub const LOD_RATIOS: [f32; 3] = [0.5, 0.25, 0.1];
+/// Fractions of the viewport height under which each level of `LOD_RATIOS` is drawn.
+pub const LOD_SCREEN_SIZES: [f32; 3] = [0.25, 0.12, 0.05];
+
+// weight of the planes keeping borders in place, relative to the planes of the faces
+const BORDER_WEIGHT: f64 = 10.0;
+// collapses turning a face further than this cosine are refused
+const MIN_NORMAL_COSINE: f64 = 0.2;
+
+/// Symmetric 4x4 matrix summing the squared distances to a set of planes.
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    // plane of `normal` through `point`, `weight` usually being the area of the face
+    fn plane(normal: glm::DVec3, point: glm::DVec3, weight: f64) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        let d = -normal.dot(&point);
+        Self([a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d].map(|value| value * weight))
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        self.0.iter_mut().zip(other.0.iter()).for_each(|(value, other)| *value += other);
+    }
+
+    fn error(&self, p: &glm::DVec3) -> f64 {
+        let [aa, ab, ac, ad, bb, bc, bd, cc, cd, dd] = self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        (aa * x * x + 2.0 * ab * x * y + 2.0 * ac * x * z + 2.0 * ad * x
+            + bb * y * y + 2.0 * bc * y * z + 2.0 * bd * y
+            + cc * z * z + 2.0 * cd * z
+            + dd).abs()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VertexKind {
+    Manifold,
+    // on an open edge or between two submeshes, only moves along it
+    Border,
+    // split in two vertexes by differing attributes, only moves along the split
+    Seam,
+    Locked,
+}
+
+fn position(mesh: &Mesh, vertex: u32) -> glm::DVec3 {
+    let [x, y, z] = mesh.positions[vertex as usize];
+    glm::vec3(x as f64, y as f64, z as f64)
+}
+
+fn face_normal(mesh: &Mesh, triangle: &[u32; 3]) -> glm::DVec3 {
+    let [a, b, c] = triangle.map(|vertex| position(mesh, vertex));
+    glm::cross(&(b - a), &(c - a))
+}
+
+// signed, positive for counter-clockwise texture coordinates
+fn uv_area(uvs: &[[f32; 2]], triangle: &[u32; 3]) -> f32 {
+    let [a, b, c] = triangle.map(|vertex| uvs[vertex as usize]);
+    (b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])
+}
+
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+/// Quadric error metric simplification (Garland and Heckbert) keeping about `target_ratio`
+/// of the triangles.
+///
+/// Edges are collapsed onto one of their vertexes so the attributes are never interpolated.
+/// Vertexes split along UV seams only move along the seam, together with their twin, and
+/// vertexes of open edges or of edges between submeshes only move along those edges, which
+/// keeps seams, borders and submeshes in place. The result may keep more triangles than
+/// asked when no collapse is left.
+pub fn simplify(mesh: &Mesh, target_ratio: f32) -> Mesh {
+    // vertexes sharing a position are one point of the surface
+    let mut position_ids = HashMap::new();
+    let point_of = mesh.positions.iter()
+        .map(|position| {
+            let count = position_ids.len();
+            *position_ids.entry(position_key(*position)).or_insert(count)
+        })
+        .collect::<Vec<_>>();
+    let point_count = position_ids.len();
+
+    let mut triangles = Vec::new();
+    let mut submesh_of = Vec::new();
+    for (submesh, range) in mesh.submeshes.iter().enumerate() {
+        for triangle in mesh.indexes[range.indexes.clone()].chunks_exact(3) {
+            triangles.push([triangle[0], triangle[1], triangle[2]]);
+            submesh_of.push(submesh);
+        }
+    }
+    let target = ((triangles.len() as f32 * target_ratio.clamp(0.0, 1.0)) as usize).max(1);
+
+    let mut quadrics = vec![Quadric::default(); point_count];
+    for triangle in triangles.iter() {
+        let normal = face_normal(mesh, triangle);
+        let area = normal.norm() / 2.0;
+        if area > 0.0 {
+            let quadric = Quadric::plane(normal / (area * 2.0), position(mesh, triangle[0]), area);
+            triangle.iter().for_each(|vertex| quadrics[point_of[*vertex as usize]].add(&quadric));
+        }
+    }
+
+    // planes through the border edges, perpendicular to their face
+    let mut edge_faces: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (index, triangle) in triangles.iter().enumerate() {
+        for corner in 0..3 {
+            let (a, b) = (point_of[triangle[corner] as usize], point_of[triangle[(corner + 1) % 3] as usize]);
+            edge_faces.entry(edge_key(a, b)).or_default().push(index);
+        }
+    }
+    for (index, triangle) in triangles.iter().enumerate() {
+        let normal = face_normal(mesh, triangle);
+        for corner in 0..3 {
+            let (a, b) = (triangle[corner], triangle[(corner + 1) % 3]);
+            let faces = &edge_faces[&edge_key(point_of[a as usize], point_of[b as usize])];
+            let border = faces.len() != 2 || faces.iter().any(|face| submesh_of[*face] != submesh_of[index]);
+            let edge = position(mesh, b) - position(mesh, a);
+            let plane = glm::cross(&edge, &normal);
+            if border && plane.norm() > 0.0 {
+                let quadric = Quadric::plane(glm::normalize(&plane), position(mesh, a), BORDER_WEIGHT * edge.norm_squared());
+                quadrics[point_of[a as usize]].add(&quadric);
+                quadrics[point_of[b as usize]].add(&quadric);
+            }
+        }
+    }
+
+    let mut alive = vec![true; triangles.len()];
+    let mut alive_count = triangles.len();
+    // each pass collapses the cheapest edges not touching an edge collapsed in the same pass
+    while alive_count > target {
+        let mut around = vec![Vec::new(); point_count];
+        let mut point_edges: HashMap<(usize, usize), (usize, HashSet<usize>)> = HashMap::new();
+        let mut vertex_edges = HashSet::new();
+        let mut vertexes_of = vec![Vec::new(); point_count];
+        for (index, triangle) in triangles.iter().enumerate().filter(|(index, _)| alive[*index]) {
+            for corner in 0..3 {
+                let (a, b) = (triangle[corner], triangle[(corner + 1) % 3]);
+                let (pa, pb) = (point_of[a as usize], point_of[b as usize]);
+                around[pa].push(index);
+                if !vertexes_of[pa].contains(&a) {
+                    vertexes_of[pa].push(a);
+                }
+                let edge = point_edges.entry(edge_key(pa, pb)).or_insert((0, HashSet::new()));
+                edge.0 += 1;
+                edge.1.insert(submesh_of[index]);
+                vertex_edges.insert((a.min(b), a.max(b)));
+            }
+        }
+        let is_border = |edge: &(usize, HashSet<usize>)| edge.0 != 2 || edge.1.len() > 1;
+
+        let mut kinds = vec![VertexKind::Manifold; point_count];
+        for ((pa, pb), edge) in point_edges.iter() {
+            if is_border(edge) {
+                kinds[*pa] = VertexKind::Border;
+                kinds[*pb] = VertexKind::Border;
+            }
+        }
+        for (point, vertexes) in vertexes_of.iter().enumerate() {
+            kinds[point] = match (kinds[point], vertexes.len()) {
+                (_, count) if count > 2 => VertexKind::Locked,
+                (VertexKind::Border, 2) => VertexKind::Locked,
+                (kind, count) if count <= 1 => kind,
+                (_, _) => VertexKind::Seam,
+            };
+        }
+
+        // candidate collapses of `from` onto `to`, with every vertex of `from` paired with
+        // the vertex of `to` it shares an edge with
+        let mut candidates = Vec::new();
+        for ((pa, pb), edge) in point_edges.iter() {
+            for (from, to) in [(*pa, *pb), (*pb, *pa)].iter().copied() {
+                let allowed = match kinds[from] {
+                    VertexKind::Locked => false,
+                    VertexKind::Border => is_border(edge),
+                    VertexKind::Seam | VertexKind::Manifold => !is_border(edge),
+                };
+                if !allowed {
+                    continue;
+                }
+                let pairs = vertexes_of[from].iter()
+                    .map(|a| vertexes_of[to].iter().find(|b| vertex_edges.contains(&(*a.min(b), *a.max(b)))).map(|b| (*a, *b)))
+                    .collect::<Option<Vec<_>>>();
+                if let Some(pairs) = pairs {
+                    let mut quadric = quadrics[from];
+                    quadric.add(&quadrics[to]);
+                    candidates.push((quadric.error(&position(mesh, vertexes_of[to][0])), from, to, pairs));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut touched = vec![false; point_count];
+        let mut collapsed = 0;
+        // once a collapse waits for the next pass, costlier ones wait too rather than going first
+        let mut max_error = f64::INFINITY;
+        for (error, from, to, pairs) in candidates.iter() {
+            if alive_count <= target || *error > max_error {
+                break;
+            }
+            if touched[*from] || touched[*to] {
+                max_error = max_error.min(*error);
+                continue;
+            }
+            // link condition: the edge faces must be the only ones shared by both ends, or
+            // the collapse would pinch the surface
+            let neighbours = |point: usize| around[point].iter()
+                .filter(|index| alive[**index])
+                .flat_map(|index| triangles[*index].iter().map(|vertex| point_of[*vertex as usize]))
+                .filter(|other| *other != point)
+                .collect::<HashSet<_>>();
+            let shared = neighbours(*from).intersection(&neighbours(*to)).count();
+            let edge_faces = around[*from].iter()
+                .filter(|index| alive[**index] && triangles[**index].iter().any(|vertex| point_of[*vertex as usize] == *to))
+                .count();
+            if shared != edge_faces {
+                continue;
+            }
+            // the faces kept around `from` must not flip or fold once moved, on the surface
+            // or in texture space
+            let moved = |triangle: &[u32; 3]| triangle.map(|vertex| {
+                pairs.iter().find(|(from_vertex, _)| *from_vertex == vertex).map_or(vertex, |(_, to_vertex)| *to_vertex)
+            });
+            let folds = around[*from].iter()
+                .filter(|index| alive[**index])
+                .map(|index| &triangles[*index])
+                .filter(|triangle| !triangle.iter().any(|vertex| point_of[*vertex as usize] == *to))
+                .any(|triangle| {
+                    let (before, after) = (face_normal(mesh, triangle), face_normal(mesh, &moved(triangle)));
+                    let length = before.norm() * after.norm();
+                    let uv_flip = mesh.tex_coords0.as_ref()
+                        .is_some_and(|uvs| uv_area(uvs, triangle) * uv_area(uvs, &moved(triangle)) < 0.0);
+                    length <= 0.0 || before.dot(&after) < MIN_NORMAL_COSINE * length || uv_flip
+                });
+            if folds {
+                continue;
+            }
+
+            for index in around[*from].iter() {
+                if !alive[*index] {
+                    continue;
+                }
+                let triangle = &mut triangles[*index];
+                for vertex in triangle.iter_mut() {
+                    if let Some((_, to_vertex)) = pairs.iter().find(|(from_vertex, _)| from_vertex == vertex) {
+                        *vertex = *to_vertex;
+                    }
+                }
+                let points = triangle.map(|vertex| point_of[vertex as usize]);
+                if points[0] == points[1] || points[1] == points[2] || points[0] == points[2] {
+                    alive[*index] = false;
+                    alive_count -= 1;
+                }
+            }
+            let from_quadric = quadrics[*from];
+            quadrics[*to].add(&from_quadric);
+            // the triangles around `from` are now around `to` too, neighbours looking at them
+            // later in the pass find them through their own lists
+            touched[*from] = true;
+            touched[*to] = true;
+            collapsed += 1;
+        }
+        if collapsed == 0 {
+            break;
+        }
+    }
+
+    let mut simplified = mesh.clone();
+    simplified.indexes.clear();
+    for (submesh, range) in simplified.submeshes.iter_mut().enumerate() {
+        let start = simplified.indexes.len();
+        for (index, triangle) in triangles.iter().enumerate() {
+            if alive[index] && submesh_of[index] == submesh {
+                simplified.indexes.extend_from_slice(triangle);
+            }
+        }
+        range.indexes = start..simplified.indexes.len();
+    }
+    optimize_vertex_fetch(&mut simplified);
+    simplified
+}
+
+/// Simplifications of `mesh` keeping each of `ratios` of its triangles, from the original.
+pub fn generate_lods(mesh: &Mesh, ratios: &[f32]) -> Vec<Mesh> {
+    ratios.iter()
+        .map(|ratio| if *ratio >= 1.0 { mesh.clone() } else { simplify(mesh, *ratio) })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use graphics::LodChain;
+
+    use super::*;
+    use crate::geometry::primitives::{cylinder, icosphere, uv_sphere};
+
+    fn mesh((vertexes, indexes): crate::geometry::primitives::PrimitiveMesh) -> Mesh {
+        Mesh::from_tangent_vertexes(&vertexes, &indexes)
+    }
+
+    fn used_positions(mesh: &Mesh) -> HashSet<[u32; 3]> {
+        mesh.indexes.iter().map(|index| position_key(mesh.positions[*index as usize])).collect()
+    }
+
+    #[test]
+    fn borders_survive() {
+        // open cylinder, both rims are borders
+        let original = mesh(cylinder(1.0, 2.0, 24, 8, false));
+        let simplified = simplify(&original, 0.25);
+        let rims = original.positions.iter()
+            .filter(|position| position[1].abs() == 1.0)
+            .map(|position| position_key(*position))
+            .collect::<HashSet<_>>();
+        assert_eq!(rims.len(), 48);
+        assert!(rims.is_subset(&used_positions(&simplified)));
+        assert_eq!(simplified.indexes.len() / 3, original.indexes.len() / 3 / 4);
+    }
+
+    #[test]
+    fn seams_stay_closed() {
+        let original = mesh(uv_sphere(1.0, 24, 12));
+        let simplified = simplify(&original, 0.25);
+        let uvs = simplified.tex_coords0.as_ref().unwrap();
+        // the meridian where u wraps from 1 back to 0, without the poles
+        let on_seam = |position: &[f32; 3]| position[0].abs() < 1e-6 && position[2] > 0.0 && position[1].abs() < 1.0 - 1e-6;
+        let mut twins: HashMap<[u32; 3], HashSet<u32>> = HashMap::new();
+        for index in simplified.indexes.iter().map(|index| *index as usize) {
+            let (position, u) = (simplified.positions[index], uvs[index][0]);
+            if u == 0.0 || u == 1.0 {
+                assert!(on_seam(&position), "seam vertex moved to {:?}", position);
+            }
+            if on_seam(&position) {
+                twins.entry(position_key(position)).or_default().insert(u.to_bits());
+            }
+        }
+        // every seam point left is used from both sides, so no crack opened
+        assert!(!twins.is_empty());
+        assert!(twins.values().all(|us| us.len() == 2), "{:?}", twins);
+        assert!(used_positions(&simplified).is_subset(&used_positions(&original)));
+    }
+
+    #[test]
+    fn closed_meshes_reach_the_target() {
+        for original in [mesh(icosphere(1.0, 3)), mesh(uv_sphere(1.0, 24, 12))].iter() {
+            let triangles = original.indexes.len() / 3;
+            for ratio in [0.5, 0.25].iter() {
+                let target = (triangles as f32 * ratio) as usize;
+                let simplified = simplify(original, *ratio);
+                let count = simplified.indexes.len() / 3;
+                // a collapse removes two triangles of a closed mesh
+                assert!(count <= target && count + 2 > target, "{} triangles for a target of {}", count, target);
+            }
+        }
+    }
+
+    #[test]
+    fn lod_levels_follow_the_screen_size() {
+        let original = mesh(icosphere(1.0, 2));
+        let lods = generate_lods(&original, &LOD_RATIOS);
+        let counts = lods.iter().map(|lod| lod.indexes.len()).collect::<Vec<_>>();
+        assert!(counts.windows(2).all(|pair| pair[0] > pair[1]), "{:?}", counts);
+
+        let chain = lods.iter().zip(LOD_SCREEN_SIZES.iter())
+            .fold(LodChain::new(original.indexes.len()), |chain, (lod, screen_size)| chain.with_level(lod.indexes.len(), *screen_size));
+        assert_eq!(chain.len(), 4);
+        let expected = [(1.0, 0), (0.25, 0), (0.2, 1), (0.12, 1), (0.1, 2), (0.05, 2), (0.01, 3), (0.0, 3)];
+        for (screen_size, level) in expected.iter() {
+            assert_eq!(chain.select_index(*screen_size), *level, "screen size {}", screen_size);
+        }
+        assert_eq!(*chain.select(0.01), counts[2]);
+    }
+}
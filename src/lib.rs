@@ -1,6 +1,7 @@
-use debug_ui::{EguiGlium, Grid, Layout, SidePanel, TopBottomPanel, Ui, Widget};
+use debug_ui::{Align2, Color32, ComboBox, EguiGlium, Grid, Layout, Pos2, SidePanel, Slider, TextStyle, TopBottomPanel, Ui, Widget};
 use debug_ui::color::Hsva;
 use debug_ui::Window as DWindow;
+use graphics::{AntiAliasing, DebugShading, DepthMode, DebugViewSettings, ScreenLabel, TransparencyMode};
 use graphics::glium::Display;
 use graphics::glium::glutin::window::Fullscreen;
 
@@ -30,6 +31,11 @@ pub struct State {
     pub background_color: [f32; 4],
     pub light_bulb_color: [[f32; 4]; 4],
     pub frame_time: u128,
+    pub anti_aliasing: AntiAliasing,
+    pub transparency: TransparencyMode,
+    pub depth_mode: DepthMode,
+    pub debug_view: DebugViewSettings,
+    pub debug_lights: bool,
     pub quit: bool,
 }
 
@@ -45,6 +51,11 @@ impl Default for State {
                 [1.0, 1.0, 1.0, 1.0]
             ],
             frame_time: 0,
+            anti_aliasing: AntiAliasing::default(),
+            transparency: TransparencyMode::default(),
+            depth_mode: DepthMode::default(),
+            debug_view: DebugViewSettings::default(),
+            debug_lights: false,
             quit: false,
         }
     }
@@ -67,6 +78,51 @@ fn show_widgets(ui: &mut Ui, state: &mut State) {
         ui.color_edit_button_rgba_premultiplied(&mut state.light_bulb_color[i]);
         ui.end_row();
     }
+    ui.add(label("Anti-aliasing"));
+    ComboBox::from_id_source("anti_aliasing")
+        .selected_text(state.anti_aliasing.name())
+        .show_ui(ui, |ui| {
+            for mode in AntiAliasing::ALL.iter() {
+                ui.selectable_value(&mut state.anti_aliasing, *mode, mode.name());
+            }
+        });
+    ui.end_row();
+    ui.add(label("Transparency"));
+    ComboBox::from_id_source("transparency")
+        .selected_text(state.transparency.name())
+        .show_ui(ui, |ui| {
+            for mode in TransparencyMode::ALL.iter() {
+                ui.selectable_value(&mut state.transparency, *mode, mode.name());
+            }
+        });
+    ui.end_row();
+    ui.add(label("Depth"));
+    ComboBox::from_id_source("depth_mode")
+        .selected_text(state.depth_mode.name())
+        .show_ui(ui, |ui| {
+            for mode in DepthMode::ALL.iter() {
+                ui.selectable_value(&mut state.depth_mode, *mode, mode.name());
+            }
+        });
+    ui.end_row();
+}
+
+fn show_debug_view(ui: &mut Ui, settings: &mut DebugViewSettings) {
+    ui.horizontal(|ui| {
+        ui.add(label("Shading"));
+        ComboBox::from_id_source("debug_shading")
+            .selected_text(settings.shading.name())
+            .show_ui(ui, |ui| {
+                for shading in DebugShading::ALL.iter() {
+                    ui.selectable_value(&mut settings.shading, *shading, shading.name());
+                }
+            });
+    });
+    ui.checkbox(&mut settings.wireframe, "Wireframe");
+    ui.checkbox(&mut settings.normals, "Normals");
+    ui.checkbox(&mut settings.tangents, "Tangents");
+    ui.checkbox(&mut settings.bounding_boxes, "Bounding boxes");
+    ui.add(Slider::new(&mut settings.vector_length, 0.05..=1.0).text("Vector length"));
 }
 
 pub fn show_window(egui: &mut EguiGlium, state: &mut State) {
@@ -93,8 +149,23 @@ pub fn show_window(egui: &mut EguiGlium, state: &mut State) {
             });
     });
     if state.open_debug {
+        let debug_view = &mut state.debug_view;
+        let debug_lights = &mut state.debug_lights;
         DWindow::new("Debug Window").min_width(150.).open(&mut state.open_debug).show(egui.ctx(), |ui| {
-            ui.add(label("Debug label"));
+            show_debug_view(ui, debug_view);
+            ui.checkbox(debug_lights, "Light gizmos");
         });
     }
-}
\ No newline at end of file
+}
+
+/// Paints the labels of `DebugDraw` over the scene, positions are in physical pixels.
+pub fn show_debug_labels(egui: &mut EguiGlium, labels: &[ScreenLabel]) {
+    let painter = egui.ctx().debug_painter();
+    let pixels_per_point = egui.ctx().pixels_per_point();
+    for label in labels.iter() {
+        let position = Pos2::new(label.x / pixels_per_point, label.y / pixels_per_point);
+        let [r, g, b, a] = label.color;
+        let color = Color32::from_rgba_unmultiplied((r * 255.) as u8, (g * 255.) as u8, (b * 255.) as u8, (a * 255.) as u8);
+        painter.text(position, Align2::CENTER_BOTTOM, &label.text, TextStyle::Body, color);
+    }
+}
@@ -2,7 +2,7 @@ use std::f32::consts::{FRAC_PI_2, PI};
 use std::time::Instant;
 
 use debug_ui::EguiGlium;
-use graphics::{Colors, DirectionalLight, draw_params, glium, GVec3, load_glsl, load_png_texture, load_tif_texture, Material, PointLight, SpotLight, Vertex};
+use graphics::{AntiAliasing, BlendMode, Colors, DebugDraw, DebugView, DepthFunction, DepthMode, DepthState, DirectionalLight, DrawItem, EnvironmentSource, Fxaa, generate_tangents, glium, GVec3, Ibl, IblSettings, Instance, InstanceBuffer, load_glsl, load_gltf, load_material_file, load_obj, load_material_library, load_png_texture, load_font, load_texture_atlas, load_tif_texture, LodChain, Mesh, ParallaxSettings, PbrMaterial, PointLight, RenderQueue, RenderState, RenderStateCache, SceneTarget, SpotLight, SpriteBatch, SpriteRenderer, TextAlign, TextRenderer, TextStyle, TextureAtlas, TransparencyMode, Vertex, VertexNormTan, WeightedOit};
 use graphics::glium::glutin::dpi::{PhysicalPosition, PhysicalSize, Size};
 use graphics::glium::glutin::event::{Event, StartCause};
 use graphics::glium::glutin::event_loop::ControlFlow;
@@ -10,18 +10,22 @@ use graphics::glium::glutin::GlProfile;
 use graphics::glium::glutin::window::WindowBuilder;
 use graphics::glium::Surface;
 use graphics::glium::uniform;
-use graphics::glium::uniforms::AsUniformValue;
+use graphics::glium::uniforms::{AsUniformValue, UniformValue};
 use graphics::uniform::{StructToUniform, UniformStorage};
-use math::{CameraSystem, Perspective, RawMat4, TransformBuilder};
-use math::glm::{cross, look_at, Mat4, normalize, vec3};
-use rust_opengl::{show_window, State};
+use math::{Aabb, Attachment, CameraSystem, Frustum, InfiniteReversedPerspective, Perspective, Projection, RawMat4, SceneGraph, TransformBuilder, Trs};
+use math::glm::{cross, look_at, Mat4, normalize, quat_angle_axis, vec3, Vec3};
+use rust_opengl::{show_debug_labels, show_window, State};
 use rust_opengl::geometry::cube::{cube_indexes, cube_vertexes_2d};
+use rust_opengl::geometry::primitives::{capsule, cone, cylinder, icosphere, torus};
+use rust_opengl::geometry::processing::optimize_vertex_cache;
+use rust_opengl::geometry::simplify::{generate_lods, LOD_RATIOS, LOD_SCREEN_SIZES};
 use rust_opengl::set_fullscreen;
-use rust_opengl::tick::{TICK_DRAW_ID, TICK_FRAME_ID, TICK_RENDER_EGUI_ID, TICK_RENDER_ID, TickSystem};
+use rust_opengl::tick::{COUNTER_CULLED_ID, COUNTER_VISIBLE_ID, TICK_DRAW_ID, TICK_FRAME_ID, TICK_RENDER_EGUI_ID, TICK_RENDER_ID, TickSystem};
 use ui::{Binding, Gesture, Input};
 
 pub type LoopType = glium::glutin::event_loop::EventLoop<()>;
 pub type VertexBuffer = glium::VertexBuffer<Vertex>;
+pub type TangentVertexBuffer = glium::VertexBuffer<VertexNormTan>;
 pub type IndexBuffer = glium::IndexBuffer<u16>;
 
 const CAMERA_SPEED: f32 = 10.;
@@ -30,6 +34,9 @@ const WIDTH: f32 = 1366f32;
 const HEIGHT: f32 = 768f32;
 const FOV_MIN: f32 = 0.0174533f32;
 const FOV_MAX: f32 = 0.785398f32;
+const ENVIRONMENT_HDR: &str = "resources/textures/environment.hdr";
+const ENVIRONMENT_CACHE: &str = "resources/cache/environment.ibl";
+const GLASS_OPACITY: f32 = 0.4;
 
 // compared € [to_compare - epsilon; to_compare + epsilon]
 #[inline]
@@ -47,7 +54,8 @@ fn main() {
     let y_axis = vec3(0.0, 1.0, 0.0f32);
     let x_axis = vec3(1.0, 0.0, 0.0f32);
     let custom_axis = vec3(1.0, 0.3, 0.5f32);
-    let draw_params = draw_params();
+    let mut render_states = RenderStateCache::new();
+    let bulb_state = RenderState::default();
     let mut tick_system = TickSystem::new();
     tick_system.register_listener(TICK_FRAME_ID);
     tick_system.register_listener(TICK_DRAW_ID);
@@ -56,8 +64,22 @@ fn main() {
     let wb = WindowBuilder::new()
         .with_title("3D Playground")
         .with_inner_size(Size::Physical(PhysicalSize::new(WIDTH as u32, HEIGHT as u32)));
-    let cb = glium::glutin::ContextBuilder::new().with_gl_profile(GlProfile::Core);
+    let anti_aliasing = startup_anti_aliasing();
+    // single sampled, MSAA is resolved offscreen by the scene target before being blitted here
+    let cb = glium::glutin::ContextBuilder::new()
+        .with_gl_profile(GlProfile::Core);
     let display = glium::Display::new(wb, cb, &event_loop).unwrap();
+    // the clip range follows the depth mode of the render states from the first frame
+    assert!(render_states.depth_mode().apply(&display), "cannot set the initial depth mode");
+    let fxaa = Fxaa::new(&display);
+    let mut scene_target = SceneTarget::new(&display, anti_aliasing, DepthMode::default(), WIDTH as u32, HEIGHT as u32);
+    let mut oit = WeightedOit::new(&display, WIDTH as u32, HEIGHT as u32);
+    let mut debug_view = DebugView::new(&display);
+    let mut debug_draw = DebugDraw::new(&display);
+    let mut text_renderer = TextRenderer::new(&display);
+    let mut sprite_renderer = SpriteRenderer::new(&display);
+    let hud_atlas = load_texture_atlas(&display, "resources/sprites/hud.json").unwrap_or_else(|err| panic!("{}", err));
+    let hud_font = text_renderer.add_font(load_font("resources/fonts/DejaVuSans.ttf").unwrap_or_else(|err| panic!("{}", err)));
     let mut egui = EguiGlium::new(&display);
     let mut input = Input::create();
     let binding = Binding::create();
@@ -67,20 +89,39 @@ fn main() {
     let bricks_tex = load_png_texture("resources/textures/bricks.png", &display).unwrap();
     let rock_soil_albedo = load_tif_texture("resources/textures/TexturesCom_Rock_Soil_512_albedo.tif", &display).unwrap();
     let rock_soil_rough = load_tif_texture("resources/textures/TexturesCom_Rock_Soil_512_roughness.tif", &display).unwrap();
+    let rock_soil_normal = load_tif_texture("resources/textures/TexturesCom_Rock_Soil_512_normal.tif", &display).unwrap();
+    let rock_soil_height = load_tif_texture("resources/textures/TexturesCom_Rock_Soil_512_height.tif", &display).unwrap();
     let rubiks_tex = load_png_texture("resources/textures/rubiks cube.png", &display).unwrap();
-    let container_diffuse = load_png_texture("resources/textures/container2.png", &display).unwrap();
-    let container_specular = load_png_texture("resources/textures/container2_specular.png", &display).unwrap();
-    let crate_mat = Material::new(container_diffuse, container_specular, 0.6);
-    let rock_soil_mat = Material::new(rock_soil_albedo, rock_soil_rough, 1.);
-    // let ruby = Material::new(GVec3::new(0.1745, 0.01175, 0.01175), GVec3::new(0.61424, 0.04136, 0.04136), GVec3::new(0.727811, 0.626959, 0.626959), 0.6);
+    let crate_mat = load_material_file(&display, "resources/materials/crate.ron").unwrap_or_else(|err| panic!("{}", err));
+    let rock_soil_mat = PbrMaterial::new(GVec3::new(1.0, 1.0, 1.0), 0.0, 1.0)
+        .with_albedo_map(rock_soil_albedo)
+        .with_roughness_map(rock_soil_rough)
+        .with_normal_map(rock_soil_normal)
+        .with_height_map(rock_soil_height, ParallaxSettings::default());
+    let material_library = load_material_library("resources/material_info_datas.csv").unwrap_or_else(|err| panic!("{}", err));
+    let ruby = *material_library.get("ruby").unwrap();
+    let glass_state = RenderState {
+        blend: BlendMode::Alpha,
+        depth: DepthState {
+            test: DepthFunction::Less,
+            write: false,
+        },
+        ..RenderState::default()
+    };
+    let glass_materials = ["emerald", "turquoise", "pearl"]
+        .iter()
+        .map(|name| material_library.get(name).unwrap().with_render_state(glass_state))
+        .collect::<Vec<_>>();
     let square = [
         Vertex::new(0.0, 0.0, 0.0, [0.0, 0.0, 1.0], [1.0, 0.0]),
         Vertex::new(1.0, 0.0, 0.0, [0.0, 0.0, 1.0], [1.0, 1.0]),
         Vertex::new(0.0, 1.0, 0.0, [0.0, 0.0, 1.0], [0.0, 0.0]),
         Vertex::new(1.0, 1.0, 0.0, [0.0, 0.0, 1.0], [0.0, 1.0])
     ];
-    let square_vertexes = VertexBuffer::new(&display, &square).unwrap();
-    let square_indexes = IndexBuffer::new(&display, glium::index::PrimitiveType::TrianglesList, &[0, 1, 3, 3, 2, 0]).unwrap();
+    let square_index_data = [0, 1, 3, 3, 2, 0u16];
+    let square_bounds = Aabb::from_points(square.iter().map(|v| v.position().into())).unwrap();
+    let square_vertexes = TangentVertexBuffer::new(&display, &generate_tangents(&square, &square_index_data)).unwrap();
+    let square_indexes = IndexBuffer::new(&display, glium::index::PrimitiveType::TrianglesList, &square_index_data).unwrap();
 
     let floor_model = TransformBuilder::new()
         .scale(100., 100., 100.)
@@ -88,16 +129,37 @@ fn main() {
         .translate(-0.2, -0.4, -0.1)
         .build();
     let sample_vertex_src = load_glsl("resources/shaders/material_lightcaster.vs.glsl");
-    let sample_fragment_src = load_glsl("resources/shaders/material_lightcaster_all.fs.glsl");
     let lighting_vertex_src = load_glsl("resources/shaders/lighting.vs.glsl");
     let lighting_fragment_src = load_glsl("resources/shaders/lighting.fs.glsl");
     let lighting_program =
         glium::Program::from_source(&display, &lighting_vertex_src, &lighting_fragment_src, None)
             .unwrap();
-    let sample_program =
-        glium::Program::from_source(&display, &sample_vertex_src, &sample_fragment_src, None)
+    let phong_vertex_src = load_glsl("resources/shaders/material_lighting.vs.glsl");
+    let phong_fragment_src = load_glsl("resources/shaders/material_phong.fs.glsl");
+    let phong_program =
+        glium::Program::from_source(&display, &phong_vertex_src, &phong_fragment_src, None)
+            .unwrap();
+    let transparent_fragment_src = load_glsl("resources/shaders/transparent_phong.fs.glsl");
+    let transparent_program =
+        glium::Program::from_source(&display, &phong_vertex_src, &transparent_fragment_src, None)
+            .unwrap();
+    let pbr_fragment_src = load_glsl("resources/shaders/pbr.fs.glsl");
+    let pbr_program =
+        glium::Program::from_source(&display, &sample_vertex_src, &pbr_fragment_src, None)
             .unwrap();
-    let cube_vertexes = VertexBuffer::new(&display, &cube_vertexes_2d()).unwrap();
+    // image based lighting is only enabled when an environment panorama is shipped
+    let ibl = if std::path::Path::new(ENVIRONMENT_HDR).exists() {
+        let source = EnvironmentSource::Equirectangular(ENVIRONMENT_HDR.to_string());
+        Ibl::load_or_compute(&display, &source, &IblSettings::default(), ENVIRONMENT_CACHE)
+            .map_err(|err| println!("Image based lighting disabled: {:?}", err))
+            .ok()
+    } else {
+        None
+    };
+    // bound while `useIbl` is false, the ibl samplers must not share a unit with the 2d maps
+    let ibl_placeholder = Ibl::placeholder(&display);
+    let cube_bounds = Aabb::from_points(cube_vertexes_2d().iter().map(|v| v.position().into())).unwrap();
+    let cube_vertexes = TangentVertexBuffer::new(&display, &generate_tangents(&cube_vertexes_2d(), &cube_indexes())).unwrap();
     let cube_indexes = IndexBuffer::new(&display, glium::index::PrimitiveType::TrianglesList, &cube_indexes()).unwrap();
     let cube_models = [
         TransformBuilder::new().translate(0.0, 0.0, 1.0).build(),
@@ -111,14 +173,64 @@ fn main() {
         TransformBuilder::new().translate(1.5, 0.2, -1.5).rotate(to_radians(310.), &z_axis).build(),
         TransformBuilder::new().translate(-1.3, 1.0, -1.5).build(),
     ];
+    let crate_instances = cube_models.iter().map(|model| Instance::new(model.get())).collect::<Vec<_>>();
+    let crate_instance_buffer = InstanceBuffer::new(&display, &crate_instances).unwrap();
+    if let Err(err) = crate_mat.instanced_program(&display) {
+        panic!("{}", err);
+    }
+    let crate_position = crate_instances.iter().map(Instance::position).sum::<Vec3>() / crate_instances.len() as f32;
+    // the instances are culled together, as one box in world space
+    let crate_culling_bounds = cube_models.iter().map(|model| cube_bounds.transform(model.get())).reduce(|a, b| a.merge(&b)).unwrap();
+    let ruby_model = TransformBuilder::new().translate(3.0, 0.0, -1.0).build();
+    let pyramid = load_obj("resources/models/pyramid.obj").unwrap_or_else(|err| panic!("{}", err));
+    let pyramid_meshes = pyramid.meshes.iter()
+        .map(|mesh| {
+            let (vertexes, indexes) = mesh.buffers(&display);
+            let bounds = Aabb::from_points(mesh.vertexes.iter().map(|v| v.position().into())).unwrap();
+            let material = pyramid.material(mesh).map(|material| material.to_phong()).unwrap_or(ruby);
+            (vertexes, indexes, bounds, material)
+        })
+        .collect::<Vec<_>>();
+    let pyramid_model = TransformBuilder::new().translate(-3.0, -0.5, -2.0).build();
+    let sample_scene = load_gltf(&display, "resources/models/sample_scene.gltf").unwrap_or_else(|err| panic!("{}", err));
+    let mut sample_primitives = Vec::new();
+    for (transform, node) in sample_scene.world_transforms().iter().zip(sample_scene.nodes.iter()) {
+        for primitive in node.mesh.iter().flat_map(|mesh| sample_scene.meshes[*mesh].primitives.iter()) {
+            let mesh = Mesh::from_tangent_vertexes(&primitive.vertexes, &primitive.indexes).upload(&display).unwrap();
+            sample_primitives.push((*transform, LodChain::new(mesh), primitive.bounds, primitive.material));
+        }
+    }
+    let primitive_shapes = [
+        (icosphere(0.4, 3), vec3(-1.5, 2.5, -4.0)),
+        (torus(0.35, 0.12, 32, 16), vec3(-0.5, 2.5, -4.0)),
+        (cylinder(0.3, 0.8, 24, 1, true), vec3(0.5, 2.5, -4.0)),
+        (cone(0.35, 0.8, 24, 1, true), vec3(1.5, 2.5, -4.0)),
+        (capsule(0.25, 0.5, 24, 8), vec3(2.5, 2.5, -4.0)),
+    ];
+    for ((vertexes, indexes), position) in primitive_shapes.iter() {
+        let mut mesh = Mesh::from_tangent_vertexes(vertexes, indexes);
+        optimize_vertex_cache(&mut mesh);
+        let transform = TransformBuilder::new().translate(position.x, position.y, position.z).build();
+        let lods = generate_lods(&mesh, &LOD_RATIOS);
+        let lods = lods.iter().zip(LOD_SCREEN_SIZES.iter())
+            .fold(LodChain::new(mesh.upload(&display).unwrap()), |lods, (lod, screen_size)| lods.with_level(lod.upload(&display).unwrap(), *screen_size));
+        sample_primitives.push((*transform.get(), lods, mesh.bounds(), None));
+    }
+    let default_pbr_mat = PbrMaterial::default();
+    let glass_models = [
+        TransformBuilder::new().translate(3.5, 0.5, 0.5).build(),
+        TransformBuilder::new().translate(3.8, 0.8, 1.2).scale(0.8, 0.8, 0.8).build(),
+        TransformBuilder::new().translate(4.1, 0.3, 1.9).scale(0.6, 0.6, 0.6).build(),
+    ];
     let mut uniform_color = Colors::MAGENTA;
     let mut camera = CameraSystem::default();
     let (mut w, mut h) = (display.get_framebuffer_dimensions().0, display.get_framebuffer_dimensions().1);
-    let mut perspective = Perspective::default();
-    let vp = perspective.get() * &camera.view();
+    let mut projection = Projection::default();
+    projection.resize(w, h);
+    let vp = projection.get() * &camera.view();
     let mut pre_vp: RawMat4 = vp.into();
     let mut dir_light = DirectionalLight::new(
-        GVec3::new(1.2, 2.0, 2.0),
+        GVec3::new(-1.2, -2.0, -2.0),
         GVec3::new(0.05, 0.05, 0.1),
         GVec3::new(0.2, 0.2, 0.7),
         GVec3::new(0.7, 0.7, 0.7));
@@ -148,12 +260,16 @@ fn main() {
             GVec3::new(1.0, 1.0, 1.0),
             1.0, 0.09, 0.0032),
     ];
-    let mut light_bulbs = [
-        TransformBuilder::new().translate(light_points[0].position.data.x, light_points[0].position.data.y, light_points[0].position.data.z).scale(0.2, 0.2, 0.2).build(),
-        TransformBuilder::new().translate(light_points[1].position.data.x, light_points[1].position.data.y, light_points[1].position.data.z).scale(0.2, 0.2, 0.2).build(),
-        TransformBuilder::new().translate(light_points[2].position.data.x, light_points[2].position.data.y, light_points[2].position.data.z).scale(0.2, 0.2, 0.2).build(),
-        TransformBuilder::new().translate(light_points[3].position.data.x, light_points[3].position.data.y, light_points[3].position.data.z).scale(0.2, 0.2, 0.2).build(),
-    ];
+    let mut scene = SceneGraph::new();
+    // the first light circles the scene, carried by a pivot at the origin
+    let light_pivot = scene.add_node(None, Trs::default());
+    for (i, light) in light_points.iter().enumerate() {
+        let light_node = scene.add_node(Some(light_pivot).filter(|_| i == 0), Trs::new(light.position.data));
+        scene.attach(light_node, Attachment::PointLight(i));
+        // bulb meshes are numbered like their light, whose color they take
+        let bulb = scene.add_node(Some(light_node), Trs::default().with_scale(0.2, 0.2, 0.2));
+        scene.attach(bulb, Attachment::Mesh(i));
+    }
     let mut light_spot = SpotLight::new(
         GVec3::new(4.0, 4.0, 2.0),
         {
@@ -167,9 +283,17 @@ fn main() {
         1.0, 0.045, 0.0075,
         to_radians(12.5).cos(), to_radians(17.5).cos(),
     );
+    // the torch light is held by the camera
+    let camera_node = scene.add_node(None, Trs::looking(camera.pos, &camera.front, &camera.up));
+    scene.attach(camera_node, Attachment::Camera(0));
+    let torch = scene.add_node(Some(camera_node), Trs::default());
+    scene.attach(torch, Attachment::SpotLight(0));
     // let mut light_bulb = TransformBuilder::new().translate(light.position.0, light.position.1, light.position.2).scale(0.2, 0.2, 0.2).build();
     let (mut yaw, mut pitch) = (FRAC_PI_2 * 2., 0.0);
-    let mut state = State::default();
+    let mut state = State {
+        anti_aliasing,
+        ..State::default()
+    };
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::NewEvents(cause) => match cause {
@@ -190,62 +314,186 @@ fn main() {
             egui.begin_frame(&display);
 
             show_window(&mut egui, &mut state);
+            if state.debug_lights {
+                draw_light_gizmos(&mut debug_draw, &light_points, &light_spot, &dir_light);
+            }
+            let labels = debug_draw.screen_labels(&pre_vp.into(), display.get_framebuffer_dimensions());
+            show_debug_labels(&mut egui, &labels);
 
             let (needs_repaint, shapes) = egui.end_frame(&display);
 
 
             let mut frame = display.draw();
+            let dimensions = display.get_framebuffer_dimensions();
+            let depth_mode = render_states.depth_mode();
+            if scene_target.is_outdated(state.anti_aliasing, depth_mode, dimensions) {
+                scene_target = SceneTarget::new(&display, state.anti_aliasing, depth_mode, dimensions.0, dimensions.1);
+            }
+            if oit.is_outdated(dimensions) {
+                oit = WeightedOit::new(&display, dimensions.0, dimensions.1);
+            }
+            let mut target = scene_target.framebuffer(&display);
             let bgc = {
                 let c = &state.background_color;
                 (c[0], c[1], c[2], c[3])
             };
-            frame.clear_color_and_depth(bgc, 1.);
+            target.clear_all(bgc, depth_mode.clear_depth(), 0);
 
-
-            for i in 0..light_bulbs.len() {
-                let model = light_bulbs[i].get_raw();
+            debug_view.settings = state.debug_view;
+            let mut queue = RenderQueue::new().with_debug_view(&debug_view).with_frustum(Frustum::from_matrix(&pre_vp.into()));
+            for (node, attachment) in scene.attached() {
+                let i = match attachment {
+                    Attachment::Mesh(i) => i,
+                    _ => continue,
+                };
+                let model = scene.world(node);
                 let mut my_storage = UniformStorage::default();
                 my_storage.add("vp", pre_vp.as_uniform_value());
-                my_storage.add("model", model.as_uniform_value());
+                my_storage.add("model", UniformValue::Mat4(model.into()));
                 my_storage.add("color", state.light_bulb_color[i].as_uniform_value());
-                frame.draw(&cube_vertexes, &cube_indexes, &lighting_program, &my_storage, &draw_params).unwrap();
+                queue.push(DrawItem::new(&cube_vertexes, &cube_indexes, &lighting_program, my_storage, bulb_state, scene.world_position(node)).with_bounds(cube_bounds).with_model(model));
             }
 
             let view_pos: [f32; 3] = camera.pos.into();
             let view: RawMat4 = camera.view().into();
+            let use_ibl = ibl.is_some();
             {
                 let model = floor_model.get_raw();
                 let mut my_storage = UniformStorage::default();
                 my_storage.add("vp", pre_vp.as_uniform_value());
                 my_storage.add("view", view.as_uniform_value());
-                my_storage.add("model", model.as_uniform_value());
+                my_storage.add("model", UniformValue::Mat4(model));
                 my_storage.add("viewPos", view_pos.as_uniform_value());
                 my_storage.add("toggleTorchLight", toggle_torchlight.as_uniform_value());
                 rock_soil_mat.as_uniform("material", &mut my_storage);
+                my_storage.add("useIbl", use_ibl.as_uniform_value());
+                ibl.as_ref().unwrap_or(&ibl_placeholder).as_uniform("ibl", &mut my_storage);
                 dir_light.as_uniform("dirLight", &mut my_storage);
                 light_spot.as_uniform("spotLight", &mut my_storage);
                 for (i, lp) in light_points.iter().enumerate() {
                     lp.as_uniform(&format!("pointLights[{}]", i), &mut my_storage);
                 }
-                frame.draw(&square_vertexes, &square_indexes, &sample_program, &my_storage, &draw_params).unwrap();
+                queue.push(DrawItem::new(&square_vertexes, &square_indexes, &pbr_program, my_storage, rock_soil_mat.render_state, floor_model.position()).with_bounds(square_bounds).with_model(*floor_model.get()));
             }
 
-            for x in cube_models.iter() {
-                let model = x.get_raw();
+            let perspective = perspective_of(&projection);
+            for (transform, lods, bounds, material) in sample_primitives.iter() {
+                let model: RawMat4 = (*transform).into();
+                let mesh = match bounds {
+                    Some(bounds) => {
+                        let center = (transform * bounds.center().push(1.0)).xyz();
+                        let scale = (0..3).map(|i| transform.column(i).xyz().norm()).fold(0.0, f32::max);
+                        lods.select(camera.screen_size(&perspective, &center, bounds.radius() * scale))
+                    }
+                    None => lods.select(f32::INFINITY),
+                };
+                let material = material.map_or(&default_pbr_mat, |material| &sample_scene.materials[material]);
                 let mut my_storage = UniformStorage::default();
                 my_storage.add("vp", pre_vp.as_uniform_value());
                 my_storage.add("view", view.as_uniform_value());
-                my_storage.add("model", model.as_uniform_value());
+                my_storage.add("model", UniformValue::Mat4(model));
                 my_storage.add("viewPos", view_pos.as_uniform_value());
                 my_storage.add("toggleTorchLight", toggle_torchlight.as_uniform_value());
-                crate_mat.as_uniform("material", &mut my_storage);
+                material.as_uniform("material", &mut my_storage);
+                my_storage.add("useIbl", use_ibl.as_uniform_value());
+                ibl.as_ref().unwrap_or(&ibl_placeholder).as_uniform("ibl", &mut my_storage);
                 dir_light.as_uniform("dirLight", &mut my_storage);
                 light_spot.as_uniform("spotLight", &mut my_storage);
                 for (i, lp) in light_points.iter().enumerate() {
                     lp.as_uniform(&format!("pointLights[{}]", i), &mut my_storage);
                 }
-                frame.draw(&cube_vertexes, &cube_indexes, &sample_program, &my_storage, &draw_params).unwrap();
+                let position = transform.column(3).xyz();
+                let mut item = DrawItem::new(mesh.vertexes(), mesh.indexes(), &pbr_program, my_storage, material.render_state, position);
+                item.bounds = *bounds;
+                queue.push(item.with_bucket(material.bucket()).with_model(*transform));
             }
+            if let Some(instances) = crate_instance_buffer.per_instance() {
+                let mut my_storage = UniformStorage::default();
+                my_storage.add("vp", pre_vp.as_uniform_value());
+                my_storage.add("view", view.as_uniform_value());
+                my_storage.add("viewPos", view_pos.as_uniform_value());
+                my_storage.add("toggleTorchLight", toggle_torchlight.as_uniform_value());
+                crate_mat.add_uniforms(&mut my_storage);
+                dir_light.as_uniform("dirLight", &mut my_storage);
+                light_spot.as_uniform("spotLight", &mut my_storage);
+                for (i, lp) in light_points.iter().enumerate() {
+                    lp.as_uniform(&format!("pointLights[{}]", i), &mut my_storage);
+                }
+                // compiled at startup, the material returns the same program every frame
+                let crate_instanced_program = crate_mat.instanced_program(&display).unwrap();
+                queue.push(DrawItem::new(&cube_vertexes, &cube_indexes, crate_instanced_program, my_storage, crate_mat.render_state, crate_position).with_instances(instances).with_bounds(cube_bounds).with_culling_bounds(crate_culling_bounds));
+            }
+            {
+                let model = ruby_model.get_raw();
+                let mut my_storage = UniformStorage::default();
+                my_storage.add("vp", pre_vp.as_uniform_value());
+                my_storage.add("model", UniformValue::Mat4(model));
+                my_storage.add("viewPos", view_pos.as_uniform_value());
+                my_storage.add("toggleTorchLight", toggle_torchlight.as_uniform_value());
+                ruby.as_uniform("material", &mut my_storage);
+                dir_light.as_uniform("dirLight", &mut my_storage);
+                light_spot.as_uniform("spotLight", &mut my_storage);
+                for (i, lp) in light_points.iter().enumerate() {
+                    lp.as_uniform(&format!("pointLights[{}]", i), &mut my_storage);
+                }
+                queue.push(DrawItem::new(&cube_vertexes, &cube_indexes, &phong_program, my_storage, ruby.render_state, ruby_model.position()).with_bounds(cube_bounds).with_model(*ruby_model.get()));
+            }
+            for (vertexes, indexes, bounds, material) in pyramid_meshes.iter() {
+                let model = pyramid_model.get_raw();
+                let mut my_storage = UniformStorage::default();
+                my_storage.add("vp", pre_vp.as_uniform_value());
+                my_storage.add("model", UniformValue::Mat4(model));
+                my_storage.add("viewPos", view_pos.as_uniform_value());
+                my_storage.add("toggleTorchLight", toggle_torchlight.as_uniform_value());
+                material.as_uniform("material", &mut my_storage);
+                dir_light.as_uniform("dirLight", &mut my_storage);
+                light_spot.as_uniform("spotLight", &mut my_storage);
+                for (i, lp) in light_points.iter().enumerate() {
+                    lp.as_uniform(&format!("pointLights[{}]", i), &mut my_storage);
+                }
+                queue.push(DrawItem::new(vertexes, indexes, &phong_program, my_storage, material.render_state, pyramid_model.position()).with_bounds(*bounds).with_model(*pyramid_model.get()));
+            }
+            for (glass_model, glass) in glass_models.iter().zip(glass_materials.iter()) {
+                let model = glass_model.get_raw();
+                let mut my_storage = UniformStorage::default();
+                my_storage.add("vp", pre_vp.as_uniform_value());
+                my_storage.add("model", UniformValue::Mat4(model));
+                my_storage.add("viewPos", view_pos.as_uniform_value());
+                my_storage.add("toggleTorchLight", toggle_torchlight.as_uniform_value());
+                my_storage.add("opacity", GLASS_OPACITY.as_uniform_value());
+                glass.as_uniform("material", &mut my_storage);
+                dir_light.as_uniform("dirLight", &mut my_storage);
+                light_spot.as_uniform("spotLight", &mut my_storage);
+                for (i, lp) in light_points.iter().enumerate() {
+                    lp.as_uniform(&format!("pointLights[{}]", i), &mut my_storage);
+                }
+                queue.push(DrawItem::new(&cube_vertexes, &cube_indexes, &transparent_program, my_storage, glass.render_state, glass_model.position()).with_bounds(cube_bounds).with_model(*glass_model.get()));
+            }
+
+            tick_system.set_counter(COUNTER_VISIBLE_ID, queue.len());
+            tick_system.set_counter(COUNTER_CULLED_ID, queue.culled());
+            let view_matrix = camera.view();
+            queue.draw_opaque(&mut target, &view_matrix, &mut render_states);
+            // weighted OIT reads the opaque depth, multisampled scenes fall back to sorting
+            match (state.transparency, scene_target.depth()) {
+                (TransparencyMode::WeightedOit, Some(depth)) => queue.draw_transparent_oit(&display, &mut target, &view_matrix, &oit, depth, &mut render_states),
+                _ => queue.draw_transparent(&mut target, &view_matrix, &mut render_states),
+            }
+            debug_draw.draw(&display, &mut target, &pre_vp, &mut render_states);
+            let label_position = ruby_model.position() + vec3(0., 0.8, 0.);
+            text_renderer.queue_world(hud_font, "Ruby", label_position, 0.01, &TextStyle { size: 32., align: TextAlign::Center, ..TextStyle::default() });
+            text_renderer.draw_world(&display, &mut target, &view_matrix, &pre_vp.into(), &mut render_states);
+            drop(target);
+            scene_target.present(&display, &mut frame, &fxaa);
+
+            if let Some(fps) = tick_system.fps() {
+                text_renderer.queue_screen(hud_font, &format!("{:.0} FPS", fps), 10., 10., &TextStyle::default());
+            }
+            if let (Some(visible), Some(culled)) = (tick_system.counter(COUNTER_VISIBLE_ID), tick_system.counter(COUNTER_CULLED_ID)) {
+                text_renderer.queue_screen(hud_font, &format!("{} drawn, {} culled", visible, culled), 10., 30., &TextStyle::default());
+            }
+            text_renderer.draw_screen(&display, &mut frame, &mut render_states);
+            draw_hud(&display, &mut frame, &mut sprite_renderer, &hud_atlas, toggle_torchlight, &mut render_states);
 
             tick_system.start_tick(TICK_RENDER_EGUI_ID);
             egui.paint(&display, &mut frame, shapes);
@@ -288,16 +536,16 @@ fn main() {
                     yaw.sin() * pitch.cos(),
                 );
                 camera.front = direction.normalize();
-                light_spot.direction.data = direction.normalize();
             }
             let step = input.poll_analog2d(&binding.scroll);
-            if !float_eq(step.y, 0.0, 1e-3) {
-                perspective.fov -= step.y;
-                if perspective.fov < FOV_MIN {
-                    perspective.fov = FOV_MIN;
-                } else if perspective.fov > FOV_MAX {
-                    perspective.fov = FOV_MAX;
+            if let (false, Some(fov)) = (float_eq(step.y, 0.0, 1e-3), projection.fov()) {
+                let mut fov = fov - step.y;
+                if fov < FOV_MIN {
+                    fov = FOV_MIN;
+                } else if fov > FOV_MAX {
+                    fov = FOV_MAX;
                 }
+                projection.set_fov(fov);
             }
 
             if input.poll_gesture(&binding.swap_color) {
@@ -306,23 +554,31 @@ fn main() {
 
             // rotate_camera_around_scene(&mut camera, &before_run);
 
-            pre_vp = (perspective.get() * camera.view()).into();
+            if render_states.depth_mode() != state.depth_mode {
+                if !state.depth_mode.apply(&display) {
+                    eprintln!("{} depths need OpenGL 4.5, falling back to {}", state.depth_mode, DepthMode::Standard);
+                    state.depth_mode = DepthMode::Standard;
+                }
+                render_states.set_depth_mode(state.depth_mode);
+                projection = projection_for(state.depth_mode, &projection);
+            }
+            let (width, height) = display.get_framebuffer_dimensions();
+            projection.resize(width, height);
+            pre_vp = (projection.get() * camera.view()).into();
 
             if let Some(duration) = tick_system.duration_since_frame_start() {
                 let step = input.poll_analog2d(&binding.movement);
                 if step.y != 0. {
                     camera.pos += camera.front * step.y * CAMERA_SPEED * (duration as f32);
-                    light_spot.position.data = camera.pos.clone();
                 }
                 if step.x != 0. {
                     camera.pos += normalize(&cross(&camera.front, &camera.up)) * step.x * CAMERA_SPEED * (duration as f32);
-                    light_spot.position.data = camera.pos.clone();
                 }
-                // rotate_light_around_scene(&mut light_position, duration as f32);
-                // light_bulb.move_to(light_position.data.x, light_position.data.y, light_position.data.z);
-                rotate_light_around_scene(&mut light_points[0].position, duration as f32);
-                light_bulbs[0].move_to(light_points[0].position.data.x, light_points[0].position.data.y, light_points[0].position.data.z);
+                let rotation = quat_angle_axis(PI / 14. * duration as f32, &vec3(0.0, 0.0, 1.0)) * scene.local(light_pivot).rotation;
+                scene.set_rotation(light_pivot, rotation);
             }
+            scene.set_local(camera_node, Trs::looking(camera.pos, &camera.front, &camera.up));
+            sync_scene_lights(&scene, &mut light_points, &mut light_spot);
             input.tick_reset();
             tick_system.end_tick(TICK_FRAME_ID);
             // tick_system.debug_tick(TICK_FRAME_ID);
@@ -348,6 +604,55 @@ fn main() {
     });
 }
 
+// anti-aliasing can be picked at startup with `--aa=<off|msaa2|msaa4|msaa8|fxaa>`
+fn startup_anti_aliasing() -> AntiAliasing {
+    std::env::args()
+        .find_map(|arg| arg.strip_prefix("--aa=").map(|mode| mode.to_string()))
+        .map(|mode| mode.parse().unwrap_or_else(|err| {
+            println!("{}, anti-aliasing disabled", err);
+            AntiAliasing::Off
+        }))
+        .unwrap_or_default()
+}
+
+fn perspective_of(projection: &Projection) -> Perspective {
+    Perspective {
+        aspect: projection.aspect(),
+        fov: projection.fov().unwrap_or_else(|| Perspective::default().fov),
+        near: projection.near(),
+        ..Perspective::default()
+    }
+}
+
+// reversed depths pair with the infinite projection, the field of view is kept
+fn projection_for(depth_mode: DepthMode, projection: &Projection) -> Projection {
+    let perspective = perspective_of(projection);
+    match depth_mode {
+        DepthMode::Standard => perspective.into(),
+        DepthMode::ReversedZ => InfiniteReversedPerspective::from(&perspective).into(),
+    }
+}
+
+fn draw_hud(display: &glium::Display, frame: &mut glium::Frame, sprite_renderer: &mut SpriteRenderer, hud_atlas: &TextureAtlas, torchlight: bool, render_states: &mut RenderStateCache) {
+    let width = frame.get_dimensions().0 as f32;
+    let mut sprites = SpriteBatch::new();
+    let (panel, light) = (hud_atlas.sprite("panel").unwrap(), hud_atlas.sprite("light").unwrap());
+    sprites.push(panel.with_position(width - 42., 10.).with_scale(1.5, 1.5).with_origin(0.5, 0.).with_depth(1.));
+    sprites.push(light.with_position(width - 42., 18.).with_origin(0.5, 0.).with_tint(if torchlight { Colors::YELLOW } else { Colors::GREY }));
+    sprites.draw(display, frame, sprite_renderer, render_states);
+}
+
+fn draw_light_gizmos(debug_draw: &mut DebugDraw, light_points: &[PointLight; 4], light_spot: &SpotLight, dir_light: &DirectionalLight) {
+    for (i, light) in light_points.iter().enumerate() {
+        let position = light.position.data;
+        debug_draw.wire_sphere(position, light.attenuation_radius(1. / 256.), Colors::YELLOW, None);
+        debug_draw.label(position, &format!("Point light {}", i), Colors::YELLOW, None);
+    }
+    debug_draw.cone(light_spot.position.data, light_spot.direction.data, light_spot.outer_cut_off.acos(), 5., Colors::TEAL, None);
+    debug_draw.arrow(vec3(0., 3., 0.), vec3(0., 3., 0.) + normalize(&dir_light.direction.data), Colors::WHITE, None);
+    debug_draw.axes(&Mat4::identity(), 1., None);
+}
+
 fn update_light_color(lights: &mut [PointLight; 4], state: &mut State) {
     for i in 0..lights.len() {
         let l = &mut lights[i];
@@ -369,8 +674,18 @@ fn _rotate_camera_around_scene(camera: &mut Mat4, run_start: &Instant) {
                       &vec3(0.0, 1.0, 0.0f32));
 }
 
-fn rotate_light_around_scene(light_pos: &mut GVec3, delta: f32) {
-    *light_pos.data = *math::glm::rotate_vec3(&mut light_pos.data, PI / 14. * delta, &vec3(0.0, 0.0, 1.0));
+// lights follow the nodes they are attached to
+fn sync_scene_lights(scene: &SceneGraph, light_points: &mut [PointLight], light_spot: &mut SpotLight) {
+    for (node, attachment) in scene.attached() {
+        match attachment {
+            Attachment::PointLight(i) => light_points[i].position.data = scene.world_position(node),
+            Attachment::SpotLight(_) => {
+                light_spot.position.data = scene.world_position(node);
+                light_spot.direction.data = scene.world_forward(node);
+            }
+            _ => {}
+        }
+    }
 }
 
 fn _rotate_light_around_scene_raw(light_pos: &mut (f32, f32, f32), delta: f32) {
@@ -65,10 +65,17 @@ pub const TICK_DRAW_ID: &str = "Draw";
 pub const TICK_RENDER_ID: &str = "Render";
 pub const TICK_RENDER_EGUI_ID: &str = "EguiRender";
 
+pub type CounterID = &'static str;
+
+pub const COUNTER_VISIBLE_ID: &str = "Visible";
+pub const COUNTER_CULLED_ID: &str = "Culled";
+
 pub struct TickSystem {
     tick_listeners: Vec<TickID>,
     running_tick: HashMap<TickID, TickState>,
     tick_history: HashMap<TickID, TickHistory>,
+    // last value reported for each counter
+    counters: HashMap<CounterID, usize>,
     remaining_time: f64,
 }
 
@@ -78,6 +85,7 @@ impl TickSystem {
             tick_listeners: vec![],
             running_tick: Default::default(),
             tick_history: Default::default(),
+            counters: Default::default(),
             remaining_time: 1.0,
         }
     }
@@ -118,6 +126,21 @@ impl TickSystem {
         None
     }
 
+    pub fn set_counter(&mut self, id: CounterID, value: usize) {
+        self.counters.insert(id, value);
+    }
+
+    pub fn counter(&self, id: CounterID) -> Option<usize> {
+        self.counters.get(&id).copied()
+    }
+
+    /// Frames per second averaged over the frames of the current history.
+    pub fn fps(&self) -> Option<f64> {
+        self.tick_history.get(&TICK_FRAME_ID)
+            .filter(|history| !history.datas.is_empty() && history.average > 0.0)
+            .map(|history| 1.0 / history.average)
+    }
+
     pub fn debug_tick(&self, id: TickID) {
         if let Some(history) = self.tick_history.get(&id) {
             println!("({:2}) {:7} lasted {:5.3} ms, avg ± {:5.3} (-{:5.3}, +{:5.3})",
@@ -139,6 +162,9 @@ impl TickSystem {
                      (history.max - history.average) * 1000.,
             );
         }
+        for (id, value) in self.counters.iter() {
+            println!("    {:7} {}", id, value);
+        }
     }
 
     pub fn reset(&mut self) {